@@ -0,0 +1,33 @@
+use advent_of_code::solve;
+use criterion::{criterion_group, criterion_main, Criterion};
+use std::fs::read_to_string;
+
+/// The 2019 problems whose solutions are dominated by running Intcode programs.
+const INTCODE_DAYS: [u8; 12] = [2, 5, 7, 9, 11, 13, 15, 17, 19, 21, 23, 25];
+
+pub fn criterion_benchmark(c: &mut Criterion) {
+    #![allow(clippy::unwrap_used)]
+    for day in INTCODE_DAYS {
+        let input_path = format!("src/year2019/day{day:02}_input.txt");
+        let input = read_to_string(input_path).unwrap();
+
+        for part in 1..=(if day == 25 { 1 } else { 2 }) {
+            let benchmark_name = format!("intcode_2019_{day:02}_{part}");
+            c.bench_function(&benchmark_name, |b| {
+                b.iter(|| solve(2019, day, part, &input));
+            });
+        }
+    }
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default()
+        .sample_size(20)
+        .warm_up_time(std::time::Duration::new(1, 0))
+        .nresamples(10_000)
+        .measurement_time(std::time::Duration::new(3, 0));
+    targets = criterion_benchmark
+}
+
+criterion_main!(benches);
@@ -0,0 +1,13 @@
+pub mod arcade;
+#[cfg(feature = "online")]
+pub mod client;
+pub mod elfcode;
+pub mod intcode;
+pub mod store;
+
+/// Reads the given file, or stdin if none is given.
+pub fn read_file_or_stdin(path: Option<&str>) -> Result<String, String> {
+    path.map_or_else(crate::read_stdin, |path| {
+        std::fs::read_to_string(path).map_err(|error| format!("Unable to read {path}: {error}"))
+    })
+}
@@ -0,0 +1,175 @@
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use advent_of_code::arcade::{Arcade, Tile};
+use advent_of_code::int_code::{Program, Word};
+
+const HELP: &str = "Move the paddle with a/d or the arrow keys, any other key waits, q quits";
+
+/// A key pressed while playing.
+enum Key {
+    Joystick(Word),
+    Quit,
+}
+
+/// Sets the terminal to read key presses immediately without echoing them, and restores it
+/// when dropped.
+struct RawTerminal {
+    saved_settings: String,
+}
+
+impl RawTerminal {
+    fn enable() -> Result<Self, String> {
+        let saved_settings = stty(&["-g"])?;
+        stty(&["-icanon", "-echo", "min", "1"])?;
+        Ok(Self { saved_settings })
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        stty(&[self.saved_settings.trim()]).ok();
+    }
+}
+
+fn stty(arguments: &[&str]) -> Result<String, String> {
+    let output = Command::new("stty")
+        .args(arguments)
+        .stdin(Stdio::inherit())
+        .output()
+        .map_err(|error| format!("Unable to run stty: {error}"))?;
+    if !output.status.success() {
+        return Err("Unable to configure the terminal - is stdin a terminal?".to_string());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn read_key(stdin: &mut dyn Read) -> Result<Key, String> {
+    let mut read_byte = || {
+        let mut byte = [0];
+        stdin
+            .read_exact(&mut byte)
+            .map(|()| byte[0])
+            .map_err(|error| format!("Unable to read key: {error}"))
+    };
+    Ok(match read_byte()? {
+        b'a' | b'h' => Key::Joystick(-1),
+        b'd' | b'l' => Key::Joystick(1),
+        b'q' => Key::Quit,
+        // Arrow keys are sent as escape sequences, such as "\x1b[D" for left:
+        0x1b if read_byte()? == b'[' => match read_byte()? {
+            b'D' => Key::Joystick(-1),
+            b'C' => Key::Joystick(1),
+            _ => Key::Joystick(0),
+        },
+        _ => Key::Joystick(0),
+    })
+}
+
+/// Renders the screen and score with ANSI escape codes, starting at the top left corner.
+fn render(arcade: &Arcade) -> String {
+    let mut result = String::from("\x1b[H");
+    for (y, row) in arcade.screen().iter().enumerate() {
+        for &tile in row {
+            result.push_str(match tile {
+                Tile::Empty => " ",
+                Tile::Wall => "\x1b[90m█",
+                Tile::Block => ["\x1b[31m▒", "\x1b[33m▒", "\x1b[32m▒", "\x1b[36m▒"][y % 4],
+                Tile::Paddle => "\x1b[97m▀",
+                Tile::Ball => "\x1b[97m●",
+            });
+        }
+        result.push_str("\x1b[0m\x1b[K\n");
+    }
+    result.push_str(&format!(
+        "Score: {}   Blocks: {}\x1b[K\n",
+        arcade.score(),
+        arcade.block_count()
+    ));
+    result
+}
+
+/// Plays the arcade game in the terminal, either with the keyboard as joystick or as a
+/// replay of the automatic solution at `frames_per_second` joystick moves per second.
+#[allow(clippy::print_stdout)]
+pub fn play(program: Program, replay: bool, frames_per_second: u32) -> Result<(), String> {
+    let mut arcade = Arcade::new(program, true)?;
+    arcade.run()?;
+
+    let terminal = if replay {
+        None
+    } else {
+        Some(RawTerminal::enable()?)
+    };
+    let mut stdin = std::io::stdin().lock();
+    let mut stdout = std::io::stdout();
+    let frame_duration = Duration::from_secs(1) / frames_per_second;
+
+    // Clear the screen and hide the cursor:
+    print!("\x1b[2J\x1b[?25l");
+    let result = loop {
+        print!("{}", render(&arcade));
+        if arcade.is_game_over() {
+            break Ok(());
+        }
+        if replay {
+            std::thread::sleep(frame_duration);
+        } else {
+            println!("{HELP}\x1b[K");
+        }
+        if let Err(error) = stdout.flush() {
+            break Err(format!("Unable to write output: {error}"));
+        }
+
+        let direction = if replay {
+            arcade.automatic_joystick()
+        } else {
+            match read_key(&mut stdin) {
+                Ok(Key::Joystick(direction)) => direction,
+                Ok(Key::Quit) => break Ok(()),
+                Err(error) => break Err(error),
+            }
+        };
+        arcade.move_joystick(direction);
+        if let Err(error) = arcade.run() {
+            break Err(error);
+        }
+    };
+    drop(terminal);
+
+    print!("\x1b[?25h");
+    if arcade.is_game_over() {
+        let outcome = if arcade.block_count() == 0 {
+            "You won"
+        } else {
+            "Game over"
+        };
+        println!("{outcome} - final score: {}", arcade.score());
+    }
+    result
+}
+
+#[test]
+#[allow(clippy::unwrap_used)]
+fn test() {
+    // Draws a wall, a block, the paddle and the ball on two rows, and a score:
+    let program =
+        Program::parse("104,0,104,0,104,1,104,1,104,0,104,2,104,0,104,1,104,3,104,1,104,1,104,4,104,-1,104,0,104,7,99")
+            .unwrap();
+    let mut arcade = Arcade::new(program, false).unwrap();
+    arcade.run().unwrap();
+    assert_eq!(
+        render(&arcade),
+        "\x1b[H\x1b[90m█\x1b[31m▒\x1b[0m\x1b[K\n\x1b[97m▀\x1b[97m●\x1b[0m\x1b[K\nScore: 7   Blocks: 1\x1b[K\n"
+    );
+
+    let key = |bytes: &[u8]| match read_key(&mut &bytes[..]).unwrap() {
+        Key::Joystick(direction) => Some(direction),
+        Key::Quit => None,
+    };
+    assert_eq!(key(b"a"), Some(-1));
+    assert_eq!(key(b"\x1b[C"), Some(1));
+    assert_eq!(key(b" "), Some(0));
+    assert_eq!(key(b"q"), None);
+}
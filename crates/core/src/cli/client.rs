@@ -0,0 +1,306 @@
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::store::InputStore;
+
+const DEFAULT_BASE_URL: &str = "https://adventofcode.com";
+
+/// Minimum time between two requests, also across separate invocations of the program.
+const DEFAULT_REQUEST_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The user agent of requests, which names who to contact about them if given.
+fn user_agent(contact: Option<&str>) -> String {
+    let user_agent = concat!(
+        "github.com/fornwall/advent-of-code v",
+        env!("CARGO_PKG_VERSION")
+    );
+    contact.map_or_else(
+        || user_agent.to_string(),
+        |contact| format!("{user_agent} by {contact}"),
+    )
+}
+
+/// The outcome of submitting an answer, as interpreted from the returned page.
+#[derive(Debug, PartialEq, Eq)]
+pub enum SubmissionOutcome {
+    Correct,
+    Incorrect(String),
+    TooRecent(String),
+    AlreadySolved,
+    Unknown(String),
+}
+
+/// A client for the Advent of Code site, authenticated by a session token.
+pub struct Client {
+    agent: ureq::Agent,
+    base_url: String,
+    session: String,
+    request_interval: Duration,
+    last_request_file: Option<PathBuf>,
+}
+
+impl Client {
+    pub fn new(base_url: &str, session: &str, contact: Option<&str>) -> Self {
+        Self {
+            agent: ureq::AgentBuilder::new()
+                .user_agent(&user_agent(contact))
+                .timeout(Duration::from_secs(30))
+                .build(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            session: session.trim().to_string(),
+            request_interval: DEFAULT_REQUEST_INTERVAL,
+            last_request_file: None,
+        }
+    }
+
+    /// Creates a client from `$AOC_SESSION`, with the base URL from `$AOC_BASE_URL`, the
+    /// contact given in the user agent from `$AOC_CONTACT` and the minimum number of
+    /// seconds between requests from `$AOC_REQUEST_INTERVAL` if set.
+    ///
+    /// The time of the last request is kept in the input store, so that rate limiting
+    /// also applies across invocations.
+    pub fn from_env(store: &InputStore) -> Result<Self, String> {
+        let session = std::env::var("AOC_SESSION")
+            .ok()
+            .filter(|session| !session.trim().is_empty())
+            .ok_or("No session token - set AOC_SESSION to the value of the session cookie")?;
+        let base_url = std::env::var("AOC_BASE_URL").unwrap_or_else(|_| DEFAULT_BASE_URL.into());
+
+        let contact = std::env::var("AOC_CONTACT")
+            .ok()
+            .filter(|contact| !contact.trim().is_empty());
+
+        let mut client = Self::new(&base_url, &session, contact.as_deref());
+        if let Ok(value) = std::env::var("AOC_REQUEST_INTERVAL") {
+            client.request_interval = Duration::from_secs(
+                value
+                    .parse::<u64>()
+                    .map_err(|_| "Unable to parse AOC_REQUEST_INTERVAL")?,
+            );
+        }
+        client.last_request_file = Some(store.root().join(".last-request"));
+        Ok(client)
+    }
+
+    /// Returns the input for the given day, only downloading it if not already in the store.
+    pub fn fetch_input(
+        &self,
+        store: &InputStore,
+        year: u16,
+        day: u8,
+        name: &str,
+        force: bool,
+    ) -> Result<String, String> {
+        if !force && store.contains(year, day, name)? {
+            return store.load(year, day, name);
+        }
+
+        self.wait_for_rate_limit()?;
+        let url = format!("{}/{year}/day/{day}/input", self.base_url);
+        let input = self
+            .agent
+            .get(&url)
+            .set("Cookie", &format!("session={}", self.session))
+            .call()
+            .map_err(|error| describe_error(&url, error))?
+            .into_string()
+            .map_err(|error| format!("Unable to read response from {url}: {error}"))?;
+
+        store.save(year, day, name, &input)?;
+        Ok(input)
+    }
+
+    pub fn submit_answer(
+        &self,
+        year: u16,
+        day: u8,
+        part: u8,
+        answer: &str,
+    ) -> Result<SubmissionOutcome, String> {
+        self.wait_for_rate_limit()?;
+        let url = format!("{}/{year}/day/{day}/answer", self.base_url);
+        let page = self
+            .agent
+            .post(&url)
+            .set("Cookie", &format!("session={}", self.session))
+            .send_form(&[("level", &part.to_string()), ("answer", answer)])
+            .map_err(|error| describe_error(&url, error))?
+            .into_string()
+            .map_err(|error| format!("Unable to read response from {url}: {error}"))?;
+        Ok(parse_submission_response(&page))
+    }
+
+    /// Sleeps until at least `request_interval` has passed since the previous request.
+    #[allow(clippy::print_stderr)]
+    fn wait_for_rate_limit(&self) -> Result<(), String> {
+        let Some(last_request_file) = &self.last_request_file else {
+            return Ok(());
+        };
+        let now = SystemTime::now();
+
+        if let Some(last_request) = std::fs::read_to_string(last_request_file)
+            .ok()
+            .and_then(|contents| contents.trim().parse::<u64>().ok())
+            .map(|millis| UNIX_EPOCH + Duration::from_millis(millis))
+        {
+            let since_last = now.duration_since(last_request).unwrap_or_default();
+            if since_last < self.request_interval {
+                let wait = self.request_interval - since_last;
+                eprintln!("Waiting {wait:.1?} before sending request");
+                std::thread::sleep(wait);
+            }
+        }
+
+        if let Some(parent) = last_request_file.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|error| format!("Unable to create {}: {error}", parent.display()))?;
+        }
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        std::fs::write(last_request_file, millis.to_string())
+            .map_err(|error| format!("Unable to write {}: {error}", last_request_file.display()))
+    }
+}
+
+fn describe_error(url: &str, error: ureq::Error) -> String {
+    match error {
+        ureq::Error::Status(code @ (400 | 401 | 403), _) => {
+            format!("Request to {url} failed with status {code} - is the session token valid?")
+        }
+        ureq::Error::Status(404, _) => {
+            format!("Request to {url} failed with status 404 - is the puzzle unlocked yet?")
+        }
+        ureq::Error::Status(code, _) => format!("Request to {url} failed with status {code}"),
+        ureq::Error::Transport(transport) => format!("Request to {url} failed: {transport}"),
+    }
+}
+
+fn parse_submission_response(page: &str) -> SubmissionOutcome {
+    // The interesting part of the page is the first paragraph inside <article>:
+    let message = page
+        .split_once("<article>")
+        .and_then(|(_, rest)| rest.split_once("</article>"))
+        .map_or(page, |(article, _)| article);
+    let message = strip_tags(message);
+
+    if message.contains("That's the right answer") {
+        SubmissionOutcome::Correct
+    } else if message.contains("That's not the right answer") {
+        SubmissionOutcome::Incorrect(message)
+    } else if message.contains("You gave an answer too recently") {
+        SubmissionOutcome::TooRecent(message)
+    } else if message.contains("You don't seem to be solving the right level") {
+        SubmissionOutcome::AlreadySolved
+    } else {
+        SubmissionOutcome::Unknown(message)
+    }
+}
+
+fn strip_tags(html: &str) -> String {
+    let mut result = String::with_capacity(html.len());
+    let mut inside_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => inside_tag = true,
+            '>' => inside_tag = false,
+            _ if !inside_tag => result.push(c),
+            _ => {}
+        }
+    }
+    result.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[allow(clippy::unwrap_used)]
+#[test]
+fn test() {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    // A stand-in for the site answering a single input request and a single answer submission:
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+    let server = std::thread::spawn(move || {
+        let mut requests = Vec::new();
+        for (stream, body) in listener.incoming().take(2).zip([
+            "1,2,3\n",
+            "<main><article><p>That's the right answer! <a href=\"/2019\">Return</a></p></article></main>",
+        ]) {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request = String::new();
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if let Some(length) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                    content_length = length.trim().parse().unwrap();
+                }
+                if line == "\r\n" {
+                    break;
+                }
+                request.push_str(&line);
+            }
+            let mut request_body = vec![0; content_length];
+            reader.read_exact(&mut request_body).unwrap();
+            request.push_str(&String::from_utf8(request_body).unwrap());
+            requests.push(request);
+
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            )
+            .unwrap();
+        }
+        requests
+    });
+
+    let root = std::env::temp_dir().join(format!("aoc-client-test-{}", std::process::id()));
+    let store = InputStore::new(root.clone());
+    let client = Client::new(&base_url, "abc123", Some("someone@example.com"));
+
+    assert_eq!(
+        client
+            .fetch_input(&store, 2019, 2, "default", false)
+            .unwrap(),
+        "1,2,3\n"
+    );
+    // Cached, so no new request is made:
+    assert_eq!(
+        client
+            .fetch_input(&store, 2019, 2, "default", false)
+            .unwrap(),
+        "1,2,3\n"
+    );
+    assert_eq!(
+        client.submit_answer(2019, 2, 1, "4").unwrap(),
+        SubmissionOutcome::Correct
+    );
+
+    let requests = server.join().unwrap();
+    assert!(requests[0].starts_with("GET /2019/day/2/input "));
+    assert!(requests[0].contains("session=abc123"));
+    assert!(requests[0].contains(" by someone@example.com\r\n"));
+    assert!(!user_agent(None).contains(" by "));
+    assert!(requests[1].starts_with("POST /2019/day/2/answer "));
+    assert!(requests[1].ends_with("level=1&answer=4"));
+
+    assert_eq!(
+        parse_submission_response(
+            "<article><p>You gave an answer too recently; you have 30s left to wait.</p></article>"
+        ),
+        SubmissionOutcome::TooRecent(
+            "You gave an answer too recently; you have 30s left to wait.".to_string()
+        )
+    );
+    assert_eq!(
+        parse_submission_response(
+            "<article><p>You don't seem to be solving the right level.</p></article>"
+        ),
+        SubmissionOutcome::AlreadySolved
+    );
+
+    std::fs::remove_dir_all(root).unwrap();
+}
@@ -0,0 +1,11 @@
+use advent_of_code::elfcode::decompiler::decompile as decompile_program;
+use advent_of_code::elfcode::Computer;
+
+use super::read_file_or_stdin;
+
+#[allow(clippy::print_stdout)]
+pub fn decompile(path: Option<&str>) -> Result<(), String> {
+    let program = Computer::parse(&read_file_or_stdin(path)?)?;
+    print!("{}", decompile_program(&program));
+    Ok(())
+}
@@ -0,0 +1,120 @@
+mod ascii;
+mod debug;
+
+use advent_of_code::int_code::assembler::assemble as assemble_source;
+use advent_of_code::int_code::control_flow::ControlFlowGraph;
+use advent_of_code::int_code::coverage::Coverage;
+use advent_of_code::int_code::disassembler::disassemble as disassemble_program;
+use advent_of_code::int_code::state::diff as diff_states;
+use advent_of_code::int_code::{Program, RunState, Word};
+
+use super::read_file_or_stdin;
+
+/// Reads a program, or a program state saved by the debugger, from the given file or stdin.
+fn read_program(path: Option<&str>) -> Result<Program, String> {
+    let text = read_file_or_stdin(path)?;
+    if text.starts_with("intcode-state") {
+        Program::deserialize_state(&text)
+    } else {
+        Program::parse(&text)
+    }
+}
+
+#[allow(clippy::print_stdout)]
+pub fn assemble(path: Option<&str>) -> Result<(), String> {
+    let words = assemble_source(&read_file_or_stdin(path)?)?;
+    let words = words.iter().map(ToString::to_string).collect::<Vec<_>>();
+    println!("{}", words.join(","));
+    Ok(())
+}
+
+#[allow(clippy::print_stdout)]
+pub fn disassemble(path: Option<&str>) -> Result<(), String> {
+    let program = read_program(path)?;
+    print!("{}", disassemble_program(&program));
+    Ok(())
+}
+
+/// Runs a program speaking ASCII, with lines from the script file if given followed by
+/// lines from stdin as input.
+pub fn run(path: &str, script_path: Option<&str>) -> Result<(), String> {
+    let mut program = read_program(Some(path))?;
+    let mut stdout = std::io::stdout();
+    if let Some(script_path) = script_path {
+        let script = read_file_or_stdin(Some(script_path))?;
+        if ascii::run(&mut program, &mut script.as_bytes(), &mut stdout, true)? {
+            return Ok(());
+        }
+    }
+    if ascii::run(
+        &mut program,
+        &mut std::io::stdin().lock(),
+        &mut stdout,
+        false,
+    )? {
+        Ok(())
+    } else {
+        Err("Input ended while the program needs input".to_string())
+    }
+}
+
+/// Prints the control-flow graph of a program in the Graphviz DOT format.
+#[allow(clippy::print_stdout)]
+pub fn control_flow_graph(path: Option<&str>) -> Result<(), String> {
+    let program = read_program(path)?;
+    print!("{}", ControlFlowGraph::new(&program).to_dot(None));
+    Ok(())
+}
+
+/// Runs a program with input from stdin, either as ASCII text or as integers separated
+/// by commas or whitespace, and prints the coverage as an annotated listing or as a
+/// control-flow graph in the DOT format.
+#[allow(clippy::print_stdout)]
+pub fn coverage(path: &str, ascii_input: bool, dot: bool) -> Result<(), String> {
+    let initial_program = read_program(Some(path))?;
+    let mut program = initial_program.clone();
+    let input = crate::read_stdin()?;
+    if ascii_input {
+        program.input_string(&input);
+    } else {
+        for value in input
+            .split([',', ' ', '\t', '\n', '\r'])
+            .filter(|v| !v.is_empty())
+        {
+            let value = value
+                .parse::<Word>()
+                .map_err(|_| format!("Invalid input value: '{value}'"))?;
+            program.input(value);
+        }
+    }
+
+    let mut coverage = Coverage::new();
+    while let RunState::Output(_) = coverage.run(&mut program, 1_000_000_000)? {}
+
+    if dot {
+        let graph = ControlFlowGraph::with_entry_points(
+            &initial_program,
+            std::iter::once(initial_program.instruction_pointer())
+                .chain(coverage.executed_addresses()),
+        );
+        print!("{}", graph.to_dot(Some(&coverage)));
+    } else {
+        print!("{}", coverage.annotated_listing(&initial_program));
+    }
+    Ok(())
+}
+
+pub fn debug(path: &str, trace_path: Option<&str>) -> Result<(), String> {
+    debug::run(read_program(Some(path))?, trace_path)
+}
+
+/// Prints the differences between two programs or saved program states.
+#[allow(clippy::print_stdout)]
+pub fn diff(before_path: &str, after_path: &str) -> Result<(), String> {
+    let before = read_program(Some(before_path))?;
+    let after = read_program(Some(after_path))?;
+    for difference in diff_states(&before, &after) {
+        println!("{difference}");
+    }
+    Ok(())
+}
@@ -0,0 +1,102 @@
+use std::io::{BufRead, Write};
+
+use advent_of_code::int_code::{Program, RunState};
+
+/// Runs a program speaking ASCII, giving it lines read from `input` and writing its output
+/// as text, returning whether it halted before the input ended.
+///
+/// Output values outside of the ASCII range, such as the answers of the 2019 ASCII problems,
+/// are written as numbers on lines of their own. With `echo_input`, lines read are also
+/// written to `out`, so that scripted input shows up among the output.
+pub fn run(
+    program: &mut Program,
+    input: &mut dyn BufRead,
+    out: &mut dyn Write,
+    echo_input: bool,
+) -> Result<bool, String> {
+    let write_error = |error: std::io::Error| format!("Unable to write output: {error}");
+    let mut at_line_start = true;
+    loop {
+        match program.run(1_000_000)? {
+            RunState::Output(value) => {
+                if let Ok(byte @ 0..=127) = u8::try_from(value) {
+                    out.write_all(&[byte]).map_err(write_error)?;
+                    at_line_start = byte == b'\n';
+                } else {
+                    if !at_line_start {
+                        writeln!(out).map_err(write_error)?;
+                    }
+                    writeln!(out, "{value}").map_err(write_error)?;
+                    at_line_start = true;
+                }
+            }
+            RunState::NeedsInput => {
+                out.flush().map_err(write_error)?;
+                let mut line = String::new();
+                if input
+                    .read_line(&mut line)
+                    .map_err(|error| format!("Unable to read input: {error}"))?
+                    == 0
+                {
+                    return Ok(false);
+                }
+                let line = line.trim_end_matches(['\n', '\r']);
+                if echo_input {
+                    writeln!(out, "{line}").map_err(write_error)?;
+                }
+                program.input_string(line);
+                program.input_string("\n");
+                at_line_start = true;
+            }
+            RunState::Halted => {
+                out.flush().map_err(write_error)?;
+                return Ok(true);
+            }
+            RunState::BudgetExhausted => {}
+        }
+    }
+}
+
+#[test]
+#[allow(clippy::unwrap_used)]
+fn test() {
+    use advent_of_code::int_code::assembler::assemble;
+
+    // Echoes a line of input, followed by a non-ASCII value:
+    let words = assemble(
+        "
+    loop:
+        in [char]
+        out [char]
+        eq [char], #10, [done]
+        jf [done], #loop
+        out #1000
+        hlt
+    char: data 0
+    done: data 0
+    ",
+    )
+    .unwrap();
+    let words = words.iter().map(ToString::to_string).collect::<Vec<_>>();
+    let program = Program::parse(&words.join(",")).unwrap();
+
+    let run_with = |input: &str, echo_input: bool| {
+        let mut out = Vec::new();
+        run(
+            &mut program.clone(),
+            &mut input.as_bytes(),
+            &mut out,
+            echo_input,
+        )
+        .map(|halted| (halted, String::from_utf8(out).unwrap()))
+    };
+    assert_eq!(
+        run_with("hi\r\n", false),
+        Ok((true, "hi\n1000\n".to_string()))
+    );
+    assert_eq!(
+        run_with("hi\nunused\n", true),
+        Ok((true, "hi\nhi\n1000\n".to_string()))
+    );
+    assert_eq!(run_with("", false), Ok((false, String::new())));
+}
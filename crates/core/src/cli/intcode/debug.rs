@@ -0,0 +1,456 @@
+use std::io::{BufRead, Write};
+
+use advent_of_code::int_code::debugger::{Debugger, StopReason};
+use advent_of_code::int_code::disassembler::{Instruction, INSTRUCTIONS};
+use advent_of_code::int_code::state::diff;
+use advent_of_code::int_code::{Program, Word};
+
+/// The maximum number of instructions executed by a single `continue` command.
+const MAX_CONTINUE_INSTRUCTIONS: u64 = 1_000_000_000;
+
+const HELP: &str = "Commands:
+  step [count]              (s)  Execute one or more instructions
+  continue                  (c)  Run until stopped by a breakpoint, watchpoint, halt or input
+  break <address>           (b)  Toggle a breakpoint at an address
+  break-op <opcode>              Toggle a breakpoint on an opcode or mnemonic, e.g. 'in'
+  watch <address>           (w)  Toggle a watchpoint on writes to a memory address
+  input <value>...          (i)  Queue input values
+  ascii <text>                   Queue text followed by a newline as input values
+  info                           Show registers, pending input, breakpoints and watchpoints
+  memory <address> [count]  (x)  Show memory contents
+  set <address> <value>          Write to memory
+  list [address] [count]    (l)  Disassemble instructions, by default at the instruction pointer
+  trace <file>|off               Write executed instructions to a file
+  save <file>                    Save the program state to a file
+  load <file>                    Restore the program state from a file
+  diff <file>                    Show differences from a saved program state
+  help                      (h)  Show this help
+  quit                      (q)  Exit the debugger";
+
+/// An interactive debugging session, reading commands and writing their results.
+pub struct Session {
+    debugger: Debugger,
+}
+
+impl Session {
+    pub fn new(program: Program) -> Self {
+        Self {
+            debugger: Debugger::new(program),
+        }
+    }
+
+    pub fn set_trace_file(&mut self, path: &str) -> Result<(), String> {
+        let file = std::fs::File::create(path)
+            .map_err(|error| format!("Unable to create {path}: {error}"))?;
+        self.debugger
+            .set_trace(Some(Box::new(std::io::BufWriter::new(file))));
+        Ok(())
+    }
+
+    /// Executes a command line, returning whether the session should continue.
+    pub fn execute(&mut self, line: &str, out: &mut dyn Write) -> Result<bool, String> {
+        let mut words = line.split_whitespace();
+        let Some(command) = words.next() else {
+            return Ok(true);
+        };
+        let arguments = words.collect::<Vec<_>>();
+        let write_error = |error: std::io::Error| format!("Unable to write output: {error}");
+
+        match (command, &arguments[..]) {
+            ("step" | "s", []) => self.run(out, |debugger| debugger.step())?,
+            ("step" | "s", [count]) => {
+                let count = parse_number::<u64>(count)?;
+                self.run(out, |debugger| {
+                    for _ in 1..count {
+                        match debugger.step()? {
+                            StopReason::Stepped => {}
+                            reason => return Ok(reason),
+                        }
+                    }
+                    debugger.step()
+                })?;
+            }
+            ("continue" | "c", []) => {
+                self.run(out, |debugger| debugger.resume(MAX_CONTINUE_INSTRUCTIONS))?
+            }
+            ("break" | "b", [address]) => {
+                let address = parse_number(address)?;
+                let set = self.debugger.toggle_breakpoint(address);
+                writeln!(out, "Breakpoint at {address} {}", describe_toggle(set))
+                    .map_err(write_error)?;
+            }
+            ("break-op", [opcode]) => {
+                let opcode = parse_opcode(opcode)?;
+                let set = self.debugger.toggle_opcode_breakpoint(opcode);
+                writeln!(
+                    out,
+                    "Breakpoint on opcode {opcode} {}",
+                    describe_toggle(set)
+                )
+                .map_err(write_error)?;
+            }
+            ("watch" | "w", [address]) => {
+                let address = parse_number(address)?;
+                let set = self.debugger.toggle_watchpoint(address);
+                writeln!(out, "Watchpoint at {address} {}", describe_toggle(set))
+                    .map_err(write_error)?;
+            }
+            ("input" | "i", values) if !values.is_empty() => {
+                let values = values
+                    .iter()
+                    .map(|value| parse_number::<Word>(value))
+                    .collect::<Result<Vec<_>, _>>()?;
+                for value in values {
+                    self.debugger.program_mut().input(value);
+                }
+            }
+            ("ascii", _) => {
+                let text = line.trim_start()[command.len()..]
+                    .trim_start()
+                    .trim_end_matches(['\n', '\r']);
+                if !text.is_ascii() {
+                    return Err("Only ASCII text can be input".to_string());
+                }
+                self.debugger.program_mut().input_string(text);
+                self.debugger.program_mut().input(Word::from(b'\n'));
+            }
+            ("info", []) => self.write_info(out).map_err(write_error)?,
+            ("memory" | "x", [address]) => self.write_memory(out, parse_number(address)?, 1)?,
+            ("memory" | "x", [address, count]) => {
+                self.write_memory(out, parse_number(address)?, parse_number(count)?)?;
+            }
+            ("set", [address, value]) => {
+                let address = parse_number(address)?;
+                let value = parse_number(value)?;
+                self.debugger.program_mut().write_memory(address, value)?;
+            }
+            ("list" | "l", []) => {
+                let address = self.debugger.program().instruction_pointer();
+                self.write_listing(out, address, 10).map_err(write_error)?;
+            }
+            ("list" | "l", [address]) => {
+                self.write_listing(out, parse_number(address)?, 10)
+                    .map_err(write_error)?;
+            }
+            ("list" | "l", [address, count]) => {
+                self.write_listing(out, parse_number(address)?, parse_number(count)?)
+                    .map_err(write_error)?;
+            }
+            ("save", [path]) => {
+                std::fs::write(path, self.debugger.program().serialize_state())
+                    .map_err(|error| format!("Unable to write {path}: {error}"))?;
+                writeln!(out, "Saved state to {path}").map_err(write_error)?;
+            }
+            ("load", [path]) => {
+                *self.debugger.program_mut() = super::read_program(Some(path))?;
+                writeln!(out, "Loaded state from {path}").map_err(write_error)?;
+            }
+            ("diff", [path]) => {
+                let saved = super::read_program(Some(path))?;
+                let differences = diff(&saved, self.debugger.program());
+                if differences.is_empty() {
+                    writeln!(out, "No differences").map_err(write_error)?;
+                }
+                for difference in differences {
+                    writeln!(out, "{difference}").map_err(write_error)?;
+                }
+            }
+            ("trace", ["off"]) => self.debugger.set_trace(None),
+            ("trace", [path]) => self.set_trace_file(path)?,
+            ("help" | "h", []) => writeln!(out, "{HELP}").map_err(write_error)?,
+            ("quit" | "q", []) => return Ok(false),
+            _ => {
+                return Err(format!(
+                    "Invalid command '{}' - use 'help' to list commands",
+                    line.trim()
+                ));
+            }
+        }
+        Ok(true)
+    }
+
+    fn run(
+        &mut self,
+        out: &mut dyn Write,
+        action: impl FnOnce(&mut Debugger) -> Result<StopReason, String>,
+    ) -> Result<(), String> {
+        let reason = action(&mut self.debugger)?;
+        let write_error = |error: std::io::Error| format!("Unable to write output: {error}");
+
+        let output = self.debugger.take_output();
+        if !output.is_empty() {
+            if output.iter().all(|&value| (1..128).contains(&value)) {
+                let text = output
+                    .iter()
+                    .map(|&value| value as u8 as char)
+                    .collect::<String>();
+                write!(out, "{text}").map_err(write_error)?;
+                if !text.ends_with('\n') {
+                    writeln!(out).map_err(write_error)?;
+                }
+            } else {
+                let values = output.iter().map(ToString::to_string).collect::<Vec<_>>();
+                writeln!(out, "Output: {}", values.join(",")).map_err(write_error)?;
+            }
+        }
+
+        let address = self.debugger.program().instruction_pointer();
+        match reason {
+            StopReason::Stepped => Ok(()),
+            StopReason::Breakpoint(address) => writeln!(out, "Breakpoint at {address}"),
+            StopReason::OpcodeBreakpoint(opcode) => {
+                writeln!(out, "Breakpoint on opcode {opcode} at {address}")
+            }
+            StopReason::Watchpoint {
+                address: watched,
+                old_value,
+                new_value,
+            } => writeln!(
+                out,
+                "Watchpoint at {watched}: {old_value} -> {new_value}, before {address}"
+            ),
+            StopReason::Halted => writeln!(out, "Program halted"),
+            StopReason::NeedsInput => writeln!(out, "Program needs input at {address}"),
+            StopReason::BudgetExhausted => writeln!(
+                out,
+                "Stopped after {MAX_CONTINUE_INSTRUCTIONS} instructions"
+            ),
+        }
+        .map_err(write_error)?;
+
+        if !self.debugger.program().is_halted() {
+            self.write_listing(out, address, 1).map_err(write_error)?;
+        }
+        Ok(())
+    }
+
+    fn write_info(&self, out: &mut dyn Write) -> std::io::Result<()> {
+        let program = self.debugger.program();
+        let join = |values: &mut dyn Iterator<Item = String>| values.collect::<Vec<_>>().join(", ");
+
+        writeln!(
+            out,
+            "Instruction pointer: {}",
+            program.instruction_pointer()
+        )?;
+        writeln!(out, "Relative base: {}", program.relative_base())?;
+        writeln!(
+            out,
+            "Executed instructions: {}",
+            self.debugger.executed_instructions()
+        )?;
+        writeln!(
+            out,
+            "State: {}",
+            if program.is_halted() {
+                "halted"
+            } else if program.needs_input() {
+                "needs input"
+            } else {
+                "running"
+            }
+        )?;
+        writeln!(
+            out,
+            "Pending input: {}",
+            join(&mut program.pending_input().iter().map(ToString::to_string))
+        )?;
+        writeln!(
+            out,
+            "Breakpoints: {}",
+            join(&mut self.debugger.breakpoints().iter().map(ToString::to_string))
+        )?;
+        writeln!(
+            out,
+            "Opcode breakpoints: {}",
+            join(
+                &mut self
+                    .debugger
+                    .opcode_breakpoints()
+                    .iter()
+                    .map(ToString::to_string)
+            )
+        )?;
+        writeln!(
+            out,
+            "Watchpoints: {}",
+            join(&mut self.debugger.watchpoints().iter().map(ToString::to_string))
+        )
+    }
+
+    fn write_memory(
+        &self,
+        out: &mut dyn Write,
+        address: usize,
+        count: usize,
+    ) -> Result<(), String> {
+        let program = self.debugger.program();
+        let end = address.saturating_add(count);
+        for chunk_start in (address..end).step_by(8) {
+            let values = (chunk_start..chunk_start.saturating_add(8).min(end))
+                .map(|address| program.read_memory(address).to_string())
+                .collect::<Vec<_>>();
+            writeln!(out, "{chunk_start}: {}", values.join(" "))
+                .map_err(|error| format!("Unable to write output: {error}"))?;
+        }
+        Ok(())
+    }
+
+    fn write_listing(
+        &self,
+        out: &mut dyn Write,
+        mut address: usize,
+        count: usize,
+    ) -> std::io::Result<()> {
+        let program = self.debugger.program();
+        for _ in 0..count {
+            let marker = if address == program.instruction_pointer() {
+                "=>"
+            } else {
+                "  "
+            };
+            if let Some(instruction) = Instruction::decode(program.memory(), address) {
+                writeln!(out, "{marker} {address}: {instruction}")?;
+                address += instruction.size();
+            } else if address < program.memory().len() {
+                writeln!(
+                    out,
+                    "{marker} {address}: data {}",
+                    program.read_memory(address)
+                )?;
+                address += 1;
+            } else {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(text: &str) -> Result<T, String> {
+    text.parse::<T>()
+        .map_err(|_| format!("Invalid number '{text}'"))
+}
+
+fn parse_opcode(text: &str) -> Result<u8, String> {
+    INSTRUCTIONS
+        .iter()
+        .find(|(opcode, mnemonic, _)| *mnemonic == text || opcode.to_string() == text)
+        .map(|(opcode, _, _)| *opcode)
+        .ok_or_else(|| format!("Invalid opcode '{text}'"))
+}
+
+const fn describe_toggle(set: bool) -> &'static str {
+    if set {
+        "set"
+    } else {
+        "removed"
+    }
+}
+
+/// Runs an interactive debugging session with commands read from stdin.
+#[allow(clippy::print_stdout)]
+#[allow(clippy::print_stderr)]
+pub fn run(program: Program, trace_path: Option<&str>) -> Result<(), String> {
+    let mut session = Session::new(program);
+    if let Some(trace_path) = trace_path {
+        session.set_trace_file(trace_path)?;
+    }
+
+    let stdin = std::io::stdin();
+    let mut stdout = std::io::stdout();
+    println!("Intcode debugger - type 'help' for a list of commands");
+    session.execute("list 0 1", &mut stdout)?;
+    loop {
+        print!("(intcode) ");
+        stdout
+            .flush()
+            .map_err(|error| format!("Unable to write output: {error}"))?;
+        let mut line = String::new();
+        if stdin
+            .lock()
+            .read_line(&mut line)
+            .map_err(|error| format!("Unable to read command: {error}"))?
+            == 0
+        {
+            println!();
+            return Ok(());
+        }
+        match session.execute(&line, &mut stdout) {
+            Ok(true) => {}
+            Ok(false) => return Ok(()),
+            Err(error) => eprintln!("Error: {error}"),
+        }
+    }
+}
+
+#[test]
+#[allow(clippy::unwrap_used)]
+fn test() {
+    // Echoes input until it is zero:
+    let program = Program::parse("3,11,1006,11,10,4,11,1105,1,0,99,0").unwrap();
+    let mut session = Session::new(program);
+    let mut execute = |line: &str| {
+        let mut out = Vec::new();
+        session
+            .execute(line, &mut out)
+            .map(|_| String::from_utf8(out).unwrap())
+    };
+
+    assert_eq!(
+        execute("c"),
+        Ok("Program needs input at 2\n=> 2: jf [11], #10\n".to_string())
+    );
+    assert_eq!(execute("i 72 105"), Ok(String::new()));
+    assert_eq!(execute("ascii a b\n"), Ok(String::new()));
+    assert_eq!(
+        execute("info").unwrap().lines().nth(4),
+        Some("Pending input: 105, 97, 32, 98, 10")
+    );
+    assert_eq!(execute("b 0"), Ok("Breakpoint at 0 set\n".to_string()));
+    assert_eq!(
+        execute("c"),
+        Ok("H\nBreakpoint at 0\n=> 0: in [11]\n".to_string())
+    );
+    assert_eq!(
+        execute("watch 11"),
+        Ok("Watchpoint at 11 set\n".to_string())
+    );
+    assert_eq!(
+        execute("s 3"),
+        Ok("Watchpoint at 11: 72 -> 105, before 2\n=> 2: jf [11], #10\n".to_string())
+    );
+    assert_eq!(execute("x 10 3"), Ok("10: 99 105 0\n".to_string()));
+    let state_path = std::env::temp_dir().join(format!("aoc-debug-test-{}", std::process::id()));
+    let state_path = state_path.to_str().unwrap();
+    assert_eq!(
+        execute(&format!("save {state_path}")),
+        Ok(format!("Saved state to {state_path}\n"))
+    );
+    assert_eq!(execute("set 11 0"), Ok(String::new()));
+    assert_eq!(
+        execute(&format!("diff {state_path}")),
+        Ok("memory[11]: 105 -> 0\n".to_string())
+    );
+    assert_eq!(
+        execute(&format!("load {state_path}")),
+        Ok(format!("Loaded state from {state_path}\n"))
+    );
+    assert_eq!(
+        execute(&format!("diff {state_path}")),
+        Ok("No differences\n".to_string())
+    );
+    std::fs::remove_file(state_path).unwrap();
+    assert_eq!(
+        execute("set 100000000000 1"),
+        Err("Address 100000000000 is above the memory limit of 1048576".to_string())
+    );
+    assert_eq!(
+        execute(&format!("x {} 2", usize::MAX - 1)),
+        Ok(format!("{}: 0\n", usize::MAX - 1))
+    );
+    assert_eq!(execute("set 11 0"), Ok(String::new()));
+    assert_eq!(execute("s 2"), Ok("Program halted\n".to_string()));
+    assert!(execute("bogus").is_err());
+    assert_eq!(execute("q"), Ok(String::new()));
+}
@@ -0,0 +1,159 @@
+#[cfg(feature = "online")]
+use std::path::Path;
+use std::path::PathBuf;
+
+/// The name used for an input when no explicit name is given.
+pub const DEFAULT_NAME: &str = "default";
+
+/// A directory of saved problem inputs, laid out as `<root>/<year>/<day>/<name>.txt`.
+///
+/// Several named inputs can be stored for the same day, e.g. one per account.
+pub struct InputStore {
+    root: PathBuf,
+}
+
+impl InputStore {
+    pub const fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    /// Opens the store at `$AOC_INPUT_STORE` if set, otherwise at
+    /// `$XDG_DATA_HOME/advent-of-code/inputs` falling back to `~/.local/share/advent-of-code/inputs`.
+    pub fn from_env() -> Result<Self, String> {
+        let non_empty_var = |name: &str| std::env::var_os(name).filter(|value| !value.is_empty());
+
+        if let Some(root) = non_empty_var("AOC_INPUT_STORE") {
+            return Ok(Self::new(root.into()));
+        }
+
+        let data_home = if let Some(data_home) = non_empty_var("XDG_DATA_HOME") {
+            PathBuf::from(data_home)
+        } else if let Some(home) = non_empty_var("HOME") {
+            PathBuf::from(home).join(".local").join("share")
+        } else {
+            return Err(
+                "Unable to locate input store - set AOC_INPUT_STORE, XDG_DATA_HOME or HOME"
+                    .to_string(),
+            );
+        };
+        Ok(Self::new(data_home.join("advent-of-code").join("inputs")))
+    }
+
+    #[cfg(feature = "online")]
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn path_of(&self, year: u16, day: u8, name: &str) -> Result<PathBuf, String> {
+        validate_name(name)?;
+        Ok(self.day_directory(year, day)?.join(format!("{name}.txt")))
+    }
+
+    pub fn save(&self, year: u16, day: u8, name: &str, input: &str) -> Result<PathBuf, String> {
+        let path = self.path_of(year, day, name)?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|error| format!("Unable to create {}: {error}", parent.display()))?;
+        }
+        std::fs::write(&path, input)
+            .map_err(|error| format!("Unable to write {}: {error}", path.display()))?;
+        Ok(path)
+    }
+
+    pub fn load(&self, year: u16, day: u8, name: &str) -> Result<String, String> {
+        let path = self.path_of(year, day, name)?;
+        match std::fs::read_to_string(&path) {
+            Ok(input) => Ok(input),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Err(format!(
+                "No input named '{name}' stored for year {year}, day {day} - expected at {} (use the import command to add it)",
+                path.display()
+            )),
+            Err(error) => Err(format!("Unable to read {}: {error}", path.display())),
+        }
+    }
+
+    pub fn contains(&self, year: u16, day: u8, name: &str) -> Result<bool, String> {
+        Ok(self.path_of(year, day, name)?.is_file())
+    }
+
+    /// Returns the sorted names of all inputs stored for the given day.
+    pub fn names(&self, year: u16, day: u8) -> Result<Vec<String>, String> {
+        let directory = self.day_directory(year, day)?;
+        let entries = match std::fs::read_dir(&directory) {
+            Ok(entries) => entries,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(Vec::new());
+            }
+            Err(error) => {
+                return Err(format!("Unable to read {}: {error}", directory.display()));
+            }
+        };
+
+        let mut names = Vec::new();
+        for entry in entries {
+            let entry =
+                entry.map_err(|error| format!("Unable to read directory entry: {error}"))?;
+            if let Some(name) = entry
+                .file_name()
+                .to_str()
+                .and_then(|file_name| file_name.strip_suffix(".txt"))
+            {
+                if validate_name(name).is_ok() {
+                    names.push(name.to_string());
+                }
+            }
+        }
+        names.sort_unstable();
+        Ok(names)
+    }
+
+    fn day_directory(&self, year: u16, day: u8) -> Result<PathBuf, String> {
+        if !matches!(day, 1..=25) {
+            return Err(format!("Invalid day {day} - must be 1-25"));
+        }
+        Ok(self.root.join(year.to_string()).join(format!("{day:02}")))
+    }
+}
+
+/// Input names are used as file names, so only allow a conservative set of characters.
+fn validate_name(name: &str) -> Result<(), String> {
+    if name.is_empty()
+        || name.starts_with('.')
+        || !name
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.'))
+    {
+        return Err(format!(
+            "Invalid input name '{name}' - only letters, digits, '-', '_' and '.' are allowed"
+        ));
+    }
+    Ok(())
+}
+
+#[allow(clippy::unwrap_used)]
+#[test]
+fn test() {
+    let root = std::env::temp_dir().join(format!("aoc-input-store-test-{}", std::process::id()));
+    let store = InputStore::new(root.clone());
+
+    assert_eq!(store.names(2019, 12).unwrap(), Vec::<String>::new());
+    assert!(store.load(2019, 12, DEFAULT_NAME).is_err());
+
+    store.save(2019, 12, DEFAULT_NAME, "1,2,3").unwrap();
+    store.save(2019, 12, "other", "4,5,6").unwrap();
+    assert_eq!(store.load(2019, 12, DEFAULT_NAME).unwrap(), "1,2,3");
+    assert_eq!(store.load(2019, 12, "other").unwrap(), "4,5,6");
+    assert!(store.contains(2019, 12, "other").unwrap());
+    assert!(!store.contains(2019, 13, "other").unwrap());
+    assert_eq!(store.names(2019, 12).unwrap(), vec!["default", "other"]);
+    assert_eq!(
+        store.path_of(2019, 12, "other").unwrap(),
+        root.join("2019").join("12").join("other.txt")
+    );
+
+    assert!(store.save(2019, 12, "../escape", "").is_err());
+    assert!(store.save(2019, 12, "", "").is_err());
+    assert!(store.save(2019, 26, DEFAULT_NAME, "").is_err());
+
+    std::fs::remove_dir_all(root).unwrap();
+}
@@ -9,9 +9,12 @@ pub mod id_assigner_copy;
 pub mod int_to_ascii;
 pub mod map_windows;
 pub mod md5;
+pub mod netlist;
 pub mod parser;
 pub mod permutation;
 pub mod priority_queueu;
+pub mod random;
+pub mod register_machine;
 pub mod triple_window_iterator;
 pub mod tuple_window_iterator;
 pub mod u256;
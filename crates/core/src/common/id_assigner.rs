@@ -17,6 +17,9 @@ impl<'a, const MAX_SIZE: usize, H: Ord + Eq + ?Sized> IdAssigner<'a, MAX_SIZE, H
         Ok(
             match self.id_map[0..(self.assigned_count as usize)].binary_search(&name) {
                 Ok(idx) => self.ids[idx],
+                Err(_) if self.len() == MAX_SIZE => {
+                    return Err(format!("Too many ids - at most {MAX_SIZE} are supported"));
+                }
                 Err(idx) => {
                     self.id_map
                         .copy_within(idx..self.assigned_count as usize, idx + 1);
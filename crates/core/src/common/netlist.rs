@@ -0,0 +1,371 @@
+//! Netlists of digital circuits, where each wire is driven by a gate computing its signal
+//! from other wires and constants.
+//!
+//! Both the formats of [2015 day 7](https://adventofcode.com/2015/day/7) and
+//! [2024 day 24](https://adventofcode.com/2024/day/24) are parsed:
+//!
+//! ```text
+//! x00: 1
+//! 123 -> x
+//! NOT x -> y
+//! x AND y -> z
+//! x LSHIFT 2 -> w
+//! x00 XOR y00 -> z00
+//! ```
+//!
+//! A circuit is evaluated in topological order, failing if it has a cycle, and can be
+//! exported as a graph in the [DOT] language of Graphviz.
+//!
+//! [DOT]: https://graphviz.org/doc/info/lang.html
+
+pub mod adder;
+
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter, Write};
+
+pub type WireId = usize;
+pub type Signal = u16;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Operand {
+    Wire(WireId),
+    Constant(Signal),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Operator {
+    And,
+    Or,
+    Xor,
+    LeftShift,
+    RightShift,
+}
+
+impl Operator {
+    fn parse(text: &str) -> Option<Self> {
+        Some(match text {
+            "AND" => Self::And,
+            "OR" => Self::Or,
+            "XOR" => Self::Xor,
+            "LSHIFT" => Self::LeftShift,
+            "RSHIFT" => Self::RightShift,
+            _ => return None,
+        })
+    }
+
+    fn apply(self, a: Signal, b: Signal) -> Signal {
+        match self {
+            Self::And => a & b,
+            Self::Or => a | b,
+            Self::Xor => a ^ b,
+            Self::LeftShift => a.checked_shl(u32::from(b)).unwrap_or(0),
+            Self::RightShift => a.checked_shr(u32::from(b)).unwrap_or(0),
+        }
+    }
+}
+
+impl Display for Operator {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::And => "AND",
+            Self::Or => "OR",
+            Self::Xor => "XOR",
+            Self::LeftShift => "LSHIFT",
+            Self::RightShift => "RSHIFT",
+        })
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Gate {
+    Assign(Operand),
+    Not(Operand),
+    Binary(Operator, Operand, Operand),
+}
+
+impl Gate {
+    pub fn inputs(self) -> impl Iterator<Item = Operand> {
+        match self {
+            Self::Assign(a) | Self::Not(a) => [Some(a), None],
+            Self::Binary(_, a, b) => [Some(a), Some(b)],
+        }
+        .into_iter()
+        .flatten()
+    }
+
+    pub fn input_wires(self) -> impl Iterator<Item = WireId> {
+        self.inputs().filter_map(|operand| match operand {
+            Operand::Wire(wire) => Some(wire),
+            Operand::Constant(_) => None,
+        })
+    }
+}
+
+pub struct Netlist<'a> {
+    /// The names of the wires, by id.
+    pub names: Vec<&'a str>,
+    ids: HashMap<&'a str, WireId>,
+    /// The gate driving each wire, by id.
+    pub gates: Vec<Option<Gate>>,
+}
+
+impl<'a> Netlist<'a> {
+    pub fn parse(text: &'a str) -> Result<Self, String> {
+        let mut netlist = Self {
+            names: Vec::new(),
+            ids: HashMap::new(),
+            gates: Vec::new(),
+        };
+        for (line_idx, line) in text.lines().enumerate() {
+            let on_error = || format!("Line {}: Invalid gate", line_idx + 1);
+            if line.is_empty() {
+                continue;
+            }
+            let (gate, wire) = if let Some((wire, value)) = line.split_once(": ") {
+                let value = value.parse().map_err(|_| on_error())?;
+                (Gate::Assign(Operand::Constant(value)), wire)
+            } else {
+                let (expression, wire) = line.split_once(" -> ").ok_or_else(on_error)?;
+                let words = expression.split(' ').collect::<Vec<_>>();
+                let gate = match words[..] {
+                    [a] => Gate::Assign(netlist.operand(a)),
+                    ["NOT", a] => Gate::Not(netlist.operand(a)),
+                    [a, operator, b] => {
+                        let operator = Operator::parse(operator).ok_or_else(on_error)?;
+                        Gate::Binary(operator, netlist.operand(a), netlist.operand(b))
+                    }
+                    _ => return Err(on_error()),
+                };
+                (gate, wire)
+            };
+
+            let wire = netlist.wire_or_insert(wire);
+            if netlist.gates[wire].replace(gate).is_some() {
+                return Err(format!(
+                    "Line {}: Wire '{}' is driven by more than one gate",
+                    line_idx + 1,
+                    netlist.names[wire]
+                ));
+            }
+        }
+        Ok(netlist)
+    }
+
+    fn wire_or_insert(&mut self, name: &'a str) -> WireId {
+        *self.ids.entry(name).or_insert_with(|| {
+            self.names.push(name);
+            self.gates.push(None);
+            self.names.len() - 1
+        })
+    }
+
+    fn operand(&mut self, text: &'a str) -> Operand {
+        text.parse().map_or_else(
+            |_| Operand::Wire(self.wire_or_insert(text)),
+            Operand::Constant,
+        )
+    }
+
+    pub fn wire(&self, name: &str) -> Option<WireId> {
+        self.ids.get(name).copied()
+    }
+
+    /// The wires ordered so that each wire comes after the wires its gate reads.
+    pub fn topological_order(&self) -> Result<Vec<WireId>, String> {
+        let mut readers = vec![Vec::new(); self.names.len()];
+        let mut unordered_inputs = vec![0; self.names.len()];
+        for (wire, gate) in self.gates.iter().enumerate() {
+            let gate = gate
+                .ok_or_else(|| format!("Wire '{}' is not driven by any gate", self.names[wire]))?;
+            for input in gate.input_wires() {
+                readers[input].push(wire);
+                unordered_inputs[wire] += 1;
+            }
+        }
+
+        let mut order = (0..self.names.len())
+            .filter(|&wire| unordered_inputs[wire] == 0)
+            .collect::<Vec<_>>();
+        let mut next = 0;
+        while let Some(&wire) = order.get(next) {
+            next += 1;
+            for &reader in &readers[wire] {
+                unordered_inputs[reader] -= 1;
+                if unordered_inputs[reader] == 0 {
+                    order.push(reader);
+                }
+            }
+        }
+
+        if let Some(start) = (0..self.names.len()).find(|&wire| unordered_inputs[wire] > 0) {
+            return Err(format!(
+                "The circuit has a cycle: {}",
+                self.cycle_from(start, &unordered_inputs)
+            ));
+        }
+        Ok(order)
+    }
+
+    /// Describes a cycle reached by following unordered inputs backwards from a wire.
+    fn cycle_from(&self, start: WireId, unordered_inputs: &[usize]) -> String {
+        let mut path = vec![start];
+        loop {
+            let wire = path[path.len() - 1];
+            let Some(input) = self.gates[wire].and_then(|gate| {
+                gate.input_wires()
+                    .find(|&input| unordered_inputs[input] > 0)
+            }) else {
+                // Every wire which is not ordered reads another one which is not.
+                return self.names[wire].to_string();
+            };
+            if let Some(position) = path.iter().position(|&w| w == input) {
+                let mut cycle = path[position..]
+                    .iter()
+                    .rev()
+                    .map(|&w| self.names[w])
+                    .collect::<Vec<_>>();
+                cycle.push(self.names[path[path.len() - 1]]);
+                return cycle.join(" -> ");
+            }
+            path.push(input);
+        }
+    }
+
+    /// Computes the signal of every wire, by id.
+    pub fn evaluate(&self) -> Result<Vec<Signal>, String> {
+        let mut signals = vec![0; self.names.len()];
+        for wire in self.topological_order()? {
+            let value_of = |operand| match operand {
+                Operand::Wire(input) => signals[input],
+                Operand::Constant(value) => value,
+            };
+            signals[wire] = match self.gates[wire] {
+                Some(Gate::Assign(a)) => value_of(a),
+                Some(Gate::Not(a)) => !value_of(a),
+                Some(Gate::Binary(operator, a, b)) => operator.apply(value_of(a), value_of(b)),
+                None => 0,
+            };
+        }
+        Ok(signals)
+    }
+
+    /// Exports the circuit as a Graphviz graph, with wires as ellipses and gates as boxes.
+    /// Wires set to constants are labelled with their values.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph netlist {\n");
+        for (wire, &name) in self.names.iter().enumerate() {
+            let gate = match self.gates[wire] {
+                Some(Gate::Assign(Operand::Constant(value))) => {
+                    let _ = writeln!(dot, "    \"{name}\" [label=\"{name} = {value}\"];");
+                    continue;
+                }
+                Some(gate) => gate,
+                None => {
+                    let _ = writeln!(dot, "    \"{name}\";");
+                    continue;
+                }
+            };
+            let label = match gate {
+                Gate::Assign(_) => "=".to_string(),
+                Gate::Not(_) => "NOT".to_string(),
+                Gate::Binary(operator, _, _) => operator.to_string(),
+            };
+            let _ = writeln!(dot, "    \"{name}\";");
+            let _ = writeln!(dot, "    \"{name} gate\" [shape=box, label=\"{label}\"];");
+            for (index, input) in gate.inputs().enumerate() {
+                match input {
+                    Operand::Wire(input) => {
+                        let _ = writeln!(dot, "    \"{}\" -> \"{name} gate\";", self.names[input]);
+                    }
+                    Operand::Constant(value) => {
+                        let _ = writeln!(
+                            dot,
+                            "    \"{name} gate {index}\" [shape=plaintext, label=\"{value}\"];"
+                        );
+                        let _ = writeln!(dot, "    \"{name} gate {index}\" -> \"{name} gate\";");
+                    }
+                }
+            }
+            let _ = writeln!(dot, "    \"{name} gate\" -> \"{name}\";");
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+#[test]
+#[allow(clippy::unwrap_used)]
+fn test() {
+    let netlist = Netlist::parse(
+        "123 -> x
+456 -> y
+x AND y -> d
+x OR y -> e
+x LSHIFT 2 -> f
+y RSHIFT 2 -> g
+NOT x -> h
+NOT y -> i",
+    )
+    .unwrap();
+    let signals = netlist.evaluate().unwrap();
+    let signal = |name| signals[netlist.wire(name).unwrap()];
+    assert_eq!(
+        ["d", "e", "f", "g", "h", "i", "x", "y"].map(signal),
+        [72, 507, 492, 114, 65412, 65079, 123, 456]
+    );
+
+    let netlist = Netlist::parse("x00: 1\ny00: 0\n\nx00 XOR y00 -> z00").unwrap();
+    assert_eq!(netlist.evaluate().unwrap(), [1, 0, 1]);
+    assert_eq!(
+        netlist.to_dot(),
+        r#"digraph netlist {
+    "x00" [label="x00 = 1"];
+    "y00" [label="y00 = 0"];
+    "z00";
+    "z00 gate" [shape=box, label="XOR"];
+    "x00" -> "z00 gate";
+    "y00" -> "z00 gate";
+    "z00 gate" -> "z00";
+}
+"#
+    );
+
+    let netlist = Netlist::parse("NOT b -> a\n1 AND a -> b\na -> c").unwrap();
+    assert_eq!(
+        netlist.to_dot(),
+        r#"digraph netlist {
+    "b";
+    "b gate" [shape=box, label="AND"];
+    "b gate 0" [shape=plaintext, label="1"];
+    "b gate 0" -> "b gate";
+    "a" -> "b gate";
+    "b gate" -> "b";
+    "a";
+    "a gate" [shape=box, label="NOT"];
+    "b" -> "a gate";
+    "a gate" -> "a";
+    "c";
+    "c gate" [shape=box, label="="];
+    "a" -> "c gate";
+    "c gate" -> "c";
+}
+"#
+    );
+    assert_eq!(
+        netlist.evaluate(),
+        Err("The circuit has a cycle: a -> b -> a".to_string())
+    );
+
+    assert_eq!(
+        Netlist::parse("a -> b").unwrap().evaluate(),
+        Err("Wire 'a' is not driven by any gate".to_string())
+    );
+    assert_eq!(
+        Netlist::parse("1 -> a\n2 -> a").err(),
+        Some("Line 2: Wire 'a' is driven by more than one gate".to_string())
+    );
+    assert_eq!(
+        Netlist::parse("a XNOR b -> c").err(),
+        Some("Line 1: Invalid gate".to_string())
+    );
+}
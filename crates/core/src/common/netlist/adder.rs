@@ -0,0 +1,189 @@
+//! Verification of circuits adding the numbers on the input wires `x00`, `x01`, … and `y00`,
+//! `y01`, … to the output wires `z00`, `z01`, …, expected to be ripple-carry adders:
+//!
+//! ```text
+//! z00 = x00 XOR y00
+//! c00 = x00 AND y00
+//! zNN = (xNN XOR yNN) XOR cMM         (where MM is NN - 1)
+//! cNN = (xNN AND yNN) OR ((xNN XOR yNN) AND cMM)
+//! zLL = cMM                           (where LL is the number of input bits)
+//! ```
+//!
+//! The names of wires other than inputs and outputs are arbitrary, so gates are checked by
+//! their operators and which gates read their outputs.
+
+use std::fmt::{Display, Formatter};
+
+use super::{Gate, Netlist, Operand, Operator, WireId};
+
+/// A gate not fitting into a ripple-carry adder.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Deviation<'a> {
+    /// The wire driven by the gate.
+    pub wire: &'a str,
+    pub reason: String,
+}
+
+impl Display for Deviation<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.wire, self.reason)
+    }
+}
+
+/// The bit of a wire named by a prefix and a number.
+fn bit_of(name: &str, prefix: char) -> Option<usize> {
+    name.strip_prefix(prefix)?.parse().ok()
+}
+
+impl<'a> Netlist<'a> {
+    /// Checks that the circuit is a ripple-carry adder, returning the gates which deviate
+    /// from its structure.
+    pub fn verify_adder(&self) -> Result<Vec<Deviation<'a>>, String> {
+        let count = |prefix| {
+            self.names
+                .iter()
+                .filter(|name| bit_of(name, prefix).is_some())
+                .count()
+        };
+        let bits = count('x');
+        if bits == 0 || count('y') != bits || count('z') != bits + 1 {
+            return Err(format!(
+                "An adder of {bits} bit numbers needs {bits} y inputs and {} z outputs",
+                bits + 1
+            ));
+        }
+
+        let mut readers = vec![Vec::new(); self.names.len()];
+        for gate in self.gates.iter().flatten() {
+            for input in gate.input_wires() {
+                readers[input].push(*gate);
+            }
+        }
+        let is_read_by = |wire: WireId, operator: Operator| {
+            readers[wire]
+                .iter()
+                .any(|gate| matches!(gate, Gate::Binary(o, _, _) if *o == operator))
+        };
+
+        let mut deviations = Vec::new();
+        for (wire, gate) in self.gates.iter().enumerate() {
+            let name = self.names[wire];
+            let output_bit = bit_of(name, 'z');
+            let (operator, a, b) = match gate {
+                Some(Gate::Binary(operator, Operand::Wire(a), Operand::Wire(b))) => {
+                    (*operator, self.names[*a], self.names[*b])
+                }
+                Some(Gate::Assign(Operand::Constant(_)))
+                    if bit_of(name, 'x').is_some() || bit_of(name, 'y').is_some() =>
+                {
+                    continue;
+                }
+                _ => {
+                    deviations.push(Deviation {
+                        wire: name,
+                        reason: "not an AND, OR or XOR gate of two wires".to_string(),
+                    });
+                    continue;
+                }
+            };
+            let input_bit = match (
+                bit_of(a, 'x'),
+                bit_of(a, 'y'),
+                bit_of(b, 'x'),
+                bit_of(b, 'y'),
+            ) {
+                (Some(x), None, None, Some(y)) | (None, Some(y), Some(x), None) if x == y => {
+                    Some(x)
+                }
+                _ => None,
+            };
+
+            let last_output = output_bit == Some(bits);
+            let read_as_carry = is_read_by(wire, Operator::And) && is_read_by(wire, Operator::Xor);
+            let reason = match (operator, output_bit, input_bit) {
+                (Operator::Xor, _, Some(0)) if output_bit != Some(0) => {
+                    Some(format!("{a} XOR {b} must compute z00"))
+                }
+                // The final carry of a one bit adder is the carry of its lowest bit:
+                (Operator::Or, _, _) | (Operator::And, _, Some(0)) if last_output => None,
+                _ if last_output => Some(format!(
+                    "the final carry must be computed by OR, not {operator}"
+                )),
+                (Operator::Xor, Some(bit), Some(_)) if bit > 0 => {
+                    Some("output bits must add a carry to the sum of input bits".to_string())
+                }
+                (Operator::Xor, Some(_), _) => None,
+                (_, Some(_), _) => Some(format!(
+                    "output bits must be computed by XOR, not {operator}"
+                )),
+                (Operator::Xor, None, Some(_)) if !is_read_by(wire, Operator::Xor) => {
+                    Some("a sum of input bits must be read by a XOR gate".to_string())
+                }
+                (Operator::Xor, None, None) => {
+                    Some("a XOR of a sum and a carry must compute an output bit".to_string())
+                }
+                (Operator::And, None, Some(0)) | (Operator::Or, None, _) if !read_as_carry => {
+                    Some("a carry must be read by both an AND and a XOR gate".to_string())
+                }
+                (Operator::And, None, _)
+                    if input_bit != Some(0) && !is_read_by(wire, Operator::Or) =>
+                {
+                    Some("an AND gate must be read by an OR gate".to_string())
+                }
+                (Operator::LeftShift | Operator::RightShift, _, _) => {
+                    Some(format!("{operator} gates are not part of an adder"))
+                }
+                _ => None,
+            };
+            if let Some(reason) = reason {
+                deviations.push(Deviation { wire: name, reason });
+            }
+        }
+        Ok(deviations)
+    }
+}
+
+#[test]
+#[allow(clippy::unwrap_used)]
+fn test() {
+    let adder = "x00: 1
+x01: 1
+y00: 0
+y01: 1
+
+x00 XOR y00 -> z00
+x00 AND y00 -> c00
+x01 XOR y01 -> s01
+s01 XOR c00 -> z01
+x01 AND y01 -> a01
+s01 AND c00 -> b01
+a01 OR b01 -> z02";
+    let netlist = Netlist::parse(adder).unwrap();
+    assert_eq!(netlist.verify_adder(), Ok(Vec::new()));
+
+    // Swapping the outputs of two gates:
+    let swapped = adder
+        .replace("s01 XOR c00 -> z01", "s01 XOR c00 -> a01")
+        .replace("x01 AND y01 -> a01", "x01 AND y01 -> z01");
+    let netlist = Netlist::parse(&swapped).unwrap();
+    assert_eq!(
+        netlist.verify_adder(),
+        Ok(vec![
+            Deviation {
+                wire: "a01",
+                reason: "a XOR of a sum and a carry must compute an output bit".to_string()
+            },
+            Deviation {
+                wire: "z01",
+                reason: "output bits must be computed by XOR, not AND".to_string()
+            },
+        ])
+    );
+
+    assert_eq!(
+        Netlist::parse("x00: 1\ny00: 1\nx00 XOR y00 -> z00")
+            .unwrap()
+            .verify_adder(),
+        Err("An adder of 1 bit numbers needs 1 y inputs and 2 z outputs".to_string())
+    );
+}
@@ -0,0 +1,63 @@
+/// A small and fast pseudo-random number generator based on [SplitMix64](https://prng.di.unimi.it/splitmix64.c).
+///
+/// Not suitable for cryptographic purposes, but the same seed always gives the same sequence.
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub const fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub const fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a value in the range `0..bound`, which must not be empty.
+    pub const fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+
+    /// Returns a value in the inclusive range `min..=max`.
+    pub const fn between(&mut self, min: usize, max: usize) -> usize {
+        min + self.below(max - min + 1)
+    }
+
+    /// Returns true with the probability `numerator / denominator`.
+    pub const fn chance(&mut self, numerator: usize, denominator: usize) -> bool {
+        self.below(denominator) < numerator
+    }
+
+    pub fn shuffle<T>(&mut self, slice: &mut [T]) {
+        for i in (1..slice.len()).rev() {
+            slice.swap(i, self.below(i + 1));
+        }
+    }
+}
+
+#[test]
+fn test() {
+    let mut random = Random::new(1234);
+    let mut other = Random::new(1234);
+    for _ in 0..100 {
+        assert_eq!(random.next_u64(), other.next_u64());
+    }
+    assert_ne!(random.next_u64(), Random::new(1235).next_u64());
+
+    for _ in 0..100 {
+        assert!(random.below(10) < 10);
+        assert!((3..=5).contains(&random.between(3, 5)));
+        assert!(random.chance(1, 1));
+        assert!(!random.chance(0, 1));
+    }
+
+    let mut values = [1, 2, 3, 4, 5, 6];
+    random.shuffle(&mut values);
+    values.sort_unstable();
+    assert_eq!(values, [1, 2, 3, 4, 5, 6]);
+}
@@ -0,0 +1,501 @@
+//! A framework for the small register machines of several problems, which execute a list
+//! of instructions operating on a few registers.
+//!
+//! Each instruction set implements [`InstructionSet`] by executing single instructions,
+//! while [`Machine`] holds the registers and program and implements what is common to
+//! all machines: stepping, running with an instruction budget, tracing, detecting loops
+//! and halting, and listing the program.
+//!
+//! Optimizers may replace loops of a program with native operations, kept in a
+//! [`NativeLoops`] table which instruction sets consult with [`Machine::run_native_loop`].
+
+use std::collections::VecDeque;
+use std::fmt::{Display, Write};
+use std::ops::IndexMut;
+
+/// A value stored in a register.
+pub trait Word: Copy + Default + Display {
+    fn from_i64(value: i64) -> Self;
+    fn to_i64(self) -> i64;
+}
+
+macro_rules! impl_word {
+    ($($word:ty),*) => {
+        $(impl Word for $word {
+            fn from_i64(value: i64) -> Self {
+                value as Self
+            }
+
+            fn to_i64(self) -> i64 {
+                self as i64
+            }
+        })*
+    };
+}
+
+impl_word!(i8, i32, u32, u64);
+
+impl Word for i64 {
+    fn from_i64(value: i64) -> Self {
+        value
+    }
+
+    fn to_i64(self) -> i64 {
+        self
+    }
+}
+
+/// How execution continues after an instruction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Effect<W> {
+    /// Continue with the following instruction.
+    Next,
+    /// Jump by an offset relative to the current instruction.
+    Jump(i64),
+    /// Jump to an absolute instruction index.
+    JumpTo(usize),
+    /// Output a value and continue with the following instruction.
+    Output(W),
+    /// The instruction needs an input value, and is executed again when resumed.
+    NeedsInput,
+    /// Stop execution at the current instruction.
+    Halt,
+}
+
+/// Why running a [`Machine`] stopped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stop<W> {
+    /// The instruction pointer left the program, or an instruction halted.
+    Halted,
+    Output(W),
+    NeedsInput,
+    /// The instruction at an index was about to be executed a second time.
+    Loop(usize),
+    /// The instruction at a breakpoint was about to be executed.
+    Breakpoint(usize),
+    /// The maximum number of instructions to execute were executed.
+    BudgetExhausted,
+}
+
+pub trait InstructionSet: Sized {
+    type Word: Word;
+    /// The register file, such as an array of words.
+    type Registers: Clone
+        + Default
+        + AsRef<[Self::Word]>
+        + AsMut<[Self::Word]>
+        + IndexMut<usize, Output = Self::Word>;
+    /// An instruction, displayed as its assembly source.
+    type Instruction: Copy + Display;
+    /// State of a machine besides its registers, such as counters and modes.
+    type State: Clone + Default;
+    /// The names of the registers, in order.
+    const REGISTER_NAMES: &'static [&'static str];
+
+    /// Executes an instruction, which may access and modify the whole machine.
+    fn execute(
+        instruction: Self::Instruction,
+        machine: &mut Machine<Self>,
+    ) -> Result<Effect<Self::Word>, String>;
+}
+
+/// A native operation replacing a loop of instructions, which sets the registers written by
+/// the loop to the values they have after its last iteration.
+pub trait NativeOperation<S: InstructionSet>: Copy {
+    /// Runs the operation, returning how execution continues after the loop, or `None` if
+    /// the loop should be run as instructions, such as when the operation would overflow.
+    fn execute(self, machine: &mut Machine<S>) -> Option<Effect<S::Word>>;
+}
+
+/// The native operations replacing loops of a program, by the index of the first
+/// instruction of each loop.
+#[derive(Clone)]
+pub struct NativeLoops<L>(Vec<Option<L>>);
+
+impl<L: Copy> NativeLoops<L> {
+    /// Finds the loop starting at each instruction, given its index and the instructions
+    /// from there.
+    pub fn find<I>(
+        instructions: &[I],
+        mut find_loop: impl FnMut(usize, &[I]) -> Option<L>,
+    ) -> Self {
+        Self(
+            (0..instructions.len())
+                .map(|start| find_loop(start, &instructions[start..]))
+                .collect(),
+        )
+    }
+
+    /// The loop starting at an instruction, if any.
+    pub fn get(&self, index: usize) -> Option<L> {
+        self.0.get(index).copied().flatten()
+    }
+}
+
+/// A machine executing a program of an [`InstructionSet`].
+#[derive(Clone)]
+pub struct Machine<S: InstructionSet> {
+    pub registers: S::Registers,
+    pub instructions: Vec<S::Instruction>,
+    /// The index of the next instruction to execute, with the machine halted if outside
+    /// of the program.
+    pub instruction_pointer: i64,
+    /// A register the instruction pointer is bound to, which is kept in sync with the
+    /// instruction pointer and may be written to by instructions to jump.
+    pub instruction_pointer_register: Option<usize>,
+    pub input: VecDeque<S::Word>,
+    pub state: S::State,
+    pub executed_instructions: u64,
+}
+
+impl<S: InstructionSet> Machine<S> {
+    pub fn new(instructions: Vec<S::Instruction>) -> Self {
+        Self {
+            registers: S::Registers::default(),
+            instructions,
+            instruction_pointer: 0,
+            instruction_pointer_register: None,
+            input: VecDeque::new(),
+            state: S::State::default(),
+            executed_instructions: 0,
+        }
+    }
+
+    pub fn current_instruction(&self) -> Option<S::Instruction> {
+        usize::try_from(self.instruction_pointer)
+            .ok()
+            .and_then(|index| self.instructions.get(index))
+            .copied()
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.current_instruction().is_none()
+    }
+
+    /// Resets the registers, instruction pointer, input and state while keeping the program.
+    pub fn reset(&mut self) {
+        self.registers = S::Registers::default();
+        self.instruction_pointer = 0;
+        self.input.clear();
+        self.state = S::State::default();
+        self.executed_instructions = 0;
+    }
+
+    /// Executes a single instruction, as [`Self::run`] with a budget of one instruction.
+    pub fn step(&mut self) -> Result<Stop<S::Word>, String> {
+        self.run(1)
+    }
+
+    /// Runs until halting, producing output or needing input, or until `max_instructions`
+    /// instructions have been executed.
+    pub fn run(&mut self, max_instructions: u64) -> Result<Stop<S::Word>, String> {
+        self.run_with(max_instructions, |_| None)
+    }
+
+    /// Runs as [`Self::run`], calling `trace` before each instruction is executed, such as
+    /// with [`Self::trace_line`].
+    pub fn run_traced(
+        &mut self,
+        max_instructions: u64,
+        trace: &mut dyn FnMut(&Self),
+    ) -> Result<Stop<S::Word>, String> {
+        self.run_with(max_instructions, |machine| {
+            trace(machine);
+            None
+        })
+    }
+
+    /// Runs as [`Self::run`], but also stops before an instruction is executed a second
+    /// time. This detects infinite loops in programs where the control flow only depends
+    /// on the instruction pointer.
+    ///
+    /// The instructions executed are marked in `executed`, which is cleared first so that
+    /// it can be reused between runs.
+    pub fn run_until_loop(
+        &mut self,
+        max_instructions: u64,
+        executed: &mut Vec<bool>,
+    ) -> Result<Stop<S::Word>, String> {
+        executed.clear();
+        executed.resize(self.instructions.len(), false);
+        self.run_with(max_instructions, |machine| {
+            let index = machine.instruction_pointer as usize;
+            if std::mem::replace(&mut executed[index], true) {
+                Some(Stop::Loop(index))
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Runs as [`Self::run`], but also stops before the instruction at `breakpoint` is
+    /// executed. The first instruction is always executed, so that a run stopped at the
+    /// breakpoint can be continued.
+    pub fn run_until_breakpoint(
+        &mut self,
+        max_instructions: u64,
+        breakpoint: usize,
+    ) -> Result<Stop<S::Word>, String> {
+        let mut first = true;
+        self.run_with(max_instructions, |machine| {
+            let at_breakpoint = !first && machine.instruction_pointer == breakpoint as i64;
+            first = false;
+            at_breakpoint.then_some(Stop::Breakpoint(breakpoint))
+        })
+    }
+
+    /// Runs until halting while discarding output, failing if the program needs input or
+    /// does not halt within `max_instructions` instructions.
+    pub fn run_until_halt(&mut self, max_instructions: u64) -> Result<(), String> {
+        let budget_end = self.executed_instructions + max_instructions;
+        loop {
+            let remaining = budget_end.saturating_sub(self.executed_instructions);
+            match self.run(remaining)? {
+                Stop::Halted => return Ok(()),
+                Stop::Output(_) => {}
+                Stop::NeedsInput => return Err("Program needs input".to_string()),
+                Stop::Loop(_) | Stop::Breakpoint(_) | Stop::BudgetExhausted => {
+                    return Err(format!("Aborted after {max_instructions} instructions"));
+                }
+            }
+        }
+    }
+
+    /// Runs with `before_step` called before each instruction, which is in the program,
+    /// is executed, stopping if it returns a reason.
+    fn run_with(
+        &mut self,
+        max_instructions: u64,
+        mut before_step: impl FnMut(&Self) -> Option<Stop<S::Word>>,
+    ) -> Result<Stop<S::Word>, String> {
+        // The instruction pointer is kept in a local while running, so that each instruction
+        // does not have to wait for the previous one to store it:
+        let mut instruction_pointer = self.instruction_pointer;
+        let bound_register = self.instruction_pointer_register;
+        let mut executed = 0;
+        let result = loop {
+            let Some(&instruction) = usize::try_from(instruction_pointer)
+                .ok()
+                .and_then(|index| self.instructions.get(index))
+            else {
+                break Ok(Stop::Halted);
+            };
+            if executed == max_instructions {
+                break Ok(Stop::BudgetExhausted);
+            }
+            self.instruction_pointer = instruction_pointer;
+            if let Some(stop) = before_step(self) {
+                break Ok(stop);
+            }
+
+            if let Some(register) = bound_register {
+                self.registers[register] = S::Word::from_i64(instruction_pointer);
+            }
+            let effect = match S::execute(instruction, self) {
+                Ok(effect) => effect,
+                Err(error) => break Err(error),
+            };
+            if matches!(effect, Effect::NeedsInput) {
+                break Ok(Stop::NeedsInput);
+            }
+            executed += 1;
+            if let Some(register) = bound_register {
+                instruction_pointer = self.registers[register].to_i64();
+            }
+            match effect {
+                Effect::Next => instruction_pointer += 1,
+                Effect::Jump(offset) => instruction_pointer += offset,
+                Effect::JumpTo(index) => instruction_pointer = index as i64,
+                Effect::Output(value) => {
+                    instruction_pointer += 1;
+                    break Ok(Stop::Output(value));
+                }
+                Effect::NeedsInput | Effect::Halt => break Ok(Stop::Halted),
+            }
+            if let Some(register) = bound_register {
+                self.registers[register] = S::Word::from_i64(instruction_pointer);
+            }
+        };
+        if let Some(register) = bound_register {
+            self.registers[register] = S::Word::from_i64(instruction_pointer);
+        }
+        self.instruction_pointer = instruction_pointer;
+        self.executed_instructions += executed;
+        result
+    }
+
+    /// Runs the native operation replacing the loop starting at the current instruction,
+    /// if `loops` gives a table of them for the machine and the operation is valid for the
+    /// current registers. Instruction sets call this before executing an instruction.
+    pub fn run_native_loop<L: NativeOperation<S>>(
+        &mut self,
+        loops: impl FnOnce(&Self) -> Option<&NativeLoops<L>>,
+    ) -> Option<Effect<S::Word>> {
+        let index = usize::try_from(self.instruction_pointer).ok()?;
+        loops(self)?.get(index)?.execute(self)
+    }
+
+    /// The current instruction and the register values, as a line of an execution trace.
+    pub fn trace_line(&self) -> String {
+        let mut line = format!("{:>4}: ", self.instruction_pointer);
+        let _ = match self.current_instruction() {
+            Some(instruction) => write!(line, "{:<24}", instruction.to_string()),
+            None => write!(line, "{:<24}", "(halted)"),
+        };
+        for (name, value) in S::REGISTER_NAMES.iter().zip(self.registers.as_ref()) {
+            let _ = write!(line, " {name}={value}");
+        }
+        line
+    }
+
+    /// The program with one instruction per line, prefixed with its index.
+    pub fn listing(&self) -> String {
+        let mut result = String::new();
+        for (index, instruction) in self.instructions.iter().enumerate() {
+            let _ = writeln!(result, "{index:>4}: {instruction}");
+        }
+        result
+    }
+}
+
+#[test]
+#[allow(clippy::unwrap_used)]
+fn test() {
+    // A counter with a bound instruction pointer, and an instruction outputting it:
+    #[derive(Clone)]
+    struct Counter;
+
+    #[derive(Clone, Copy)]
+    enum Instruction {
+        Increment,
+        JumpIfBelow(u64, usize),
+        Output,
+        Input,
+    }
+
+    impl Display for Instruction {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match self {
+                Self::Increment => write!(f, "inc"),
+                Self::JumpIfBelow(limit, target) => write!(f, "jlt {limit} {target}"),
+                Self::Output => write!(f, "out"),
+                Self::Input => write!(f, "in"),
+            }
+        }
+    }
+
+    impl InstructionSet for Counter {
+        type Word = u64;
+        type Registers = [u64; 2];
+        type Instruction = Instruction;
+        type State = Option<NativeLoops<CountTo>>;
+        const REGISTER_NAMES: &'static [&'static str] = &["ip", "n"];
+
+        fn execute(
+            instruction: Instruction,
+            machine: &mut Machine<Self>,
+        ) -> Result<Effect<u64>, String> {
+            Ok(match instruction {
+                Instruction::Increment => {
+                    machine.registers[1] += 1;
+                    Effect::Next
+                }
+                Instruction::JumpIfBelow(limit, target) if machine.registers[1] < limit => {
+                    Effect::JumpTo(target)
+                }
+                Instruction::JumpIfBelow(..) => Effect::Next,
+                Instruction::Output => Effect::Output(machine.registers[1]),
+                Instruction::Input => match machine.input.pop_front() {
+                    Some(value) => {
+                        machine.registers[1] = value;
+                        Effect::Next
+                    }
+                    None => Effect::NeedsInput,
+                },
+            })
+        }
+    }
+
+    let mut machine = Machine::<Counter>::new(vec![
+        Instruction::Increment,
+        Instruction::JumpIfBelow(3, 0),
+        Instruction::Output,
+        Instruction::Input,
+        Instruction::Output,
+    ]);
+    machine.instruction_pointer_register = Some(0);
+    assert_eq!(
+        machine.listing(),
+        "   0: inc\n   1: jlt 3 0\n   2: out\n   3: in\n   4: out\n"
+    );
+    assert_eq!(
+        machine.trace_line(),
+        "   0: inc                      ip=0 n=0"
+    );
+
+    let mut trace = Vec::new();
+    assert_eq!(
+        machine.run_traced(100, &mut |machine| trace.push(machine.trace_line())),
+        Ok(Stop::Output(3))
+    );
+    assert_eq!(trace.len(), 7);
+    assert_eq!(trace[2], "   0: inc                      ip=0 n=1");
+    assert_eq!(machine.registers, [3, 3]);
+    assert_eq!(machine.run(100), Ok(Stop::NeedsInput));
+    assert_eq!(machine.run(100), Ok(Stop::NeedsInput));
+    machine.input.push_back(42);
+    assert_eq!(machine.run(100), Ok(Stop::Output(42)));
+    assert_eq!(machine.run(100), Ok(Stop::Halted));
+    assert_eq!(machine.executed_instructions, 9);
+
+    machine.reset();
+    assert_eq!(machine.run(2), Ok(Stop::BudgetExhausted));
+    let mut executed = Vec::new();
+    assert_eq!(
+        machine.run_until_loop(100, &mut executed),
+        Ok(Stop::Loop(0))
+    );
+    assert_eq!(executed, vec![true, true, false, false, false]);
+    assert_eq!(
+        machine.run_until_breakpoint(100, 1),
+        Ok(Stop::Breakpoint(1))
+    );
+    assert_eq!(machine.registers, [1, 3]);
+    assert_eq!(
+        machine.run_until_halt(100),
+        Err("Program needs input".to_string())
+    );
+
+    // The loop of the first two instructions, replaced by setting the counter to its limit:
+    #[derive(Clone, Copy)]
+    struct CountTo(u64);
+
+    impl NativeOperation<Counter> for CountTo {
+        fn execute(self, machine: &mut Machine<Counter>) -> Option<Effect<u64>> {
+            (machine.registers[1] < self.0).then(|| {
+                machine.registers[1] = self.0;
+                Effect::JumpTo(2)
+            })
+        }
+    }
+
+    let loops = NativeLoops::find(
+        &machine.instructions,
+        |start, instructions| match instructions {
+            [Instruction::Increment, Instruction::JumpIfBelow(limit, target), ..]
+                if *target == start =>
+            {
+                Some(CountTo(*limit))
+            }
+            _ => None,
+        },
+    );
+    assert!(loops.get(0).is_some() && loops.get(1).is_none());
+    machine.reset();
+    machine.state = Some(loops);
+    let run_native_loop =
+        |machine: &mut Machine<Counter>| machine.run_native_loop(|machine| machine.state.as_ref());
+    assert_eq!(run_native_loop(&mut machine), Some(Effect::JumpTo(2)));
+    assert_eq!(machine.registers[1], 3);
+    assert_eq!(run_native_loop(&mut machine), None);
+}
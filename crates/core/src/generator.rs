@@ -0,0 +1,504 @@
+//! Generation of random but valid problem inputs, for load testing and fuzzing.
+//!
+//! Each generated input is checked with the parser of the day it is generated for.
+//!
+//! # Example
+//! ```
+//! use advent_of_code::generator::{generate, GeneratorOptions};
+//! let options = GeneratorOptions { seed: 1, size: Some(10), height: Some(5) };
+//! let input = generate(2021, 15, &options).unwrap();
+//! assert_eq!(input.lines().count(), 5);
+//! assert!(advent_of_code::solve(2021, 15, 1, &input).is_ok());
+//! ```
+
+use std::collections::HashSet;
+use std::fmt::Write;
+
+use crate::common::random::Random;
+
+/// The year and day of problems for which inputs can be generated.
+pub const SUPPORTED_DAYS: [(u16, u8); 7] = [
+    (2016, 12),
+    (2017, 18),
+    (2018, 19),
+    (2021, 9),
+    (2021, 15),
+    (2023, 25),
+    (2024, 23),
+];
+
+/// Options controlling the generated input.
+#[derive(Clone, Default)]
+pub struct GeneratorOptions {
+    /// The same seed together with the same options always generates the same input.
+    pub seed: u64,
+    /// The size of the input, with a day specific default. It is the width of grids, the
+    /// number of nodes in graphs and the loop count of programs.
+    pub size: Option<usize>,
+    /// The height of grids, defaulting to the size.
+    pub height: Option<usize>,
+}
+
+impl GeneratorOptions {
+    fn size(&self, default: usize, min: usize, max: usize) -> Result<usize, String> {
+        if self.height.is_some() {
+            return Err("Height is only supported for grids".to_string());
+        }
+        let size = self.size.unwrap_or(default);
+        if !(min..=max).contains(&size) {
+            return Err(format!("Invalid size {size} - must be {min}-{max}"));
+        }
+        Ok(size)
+    }
+
+    fn dimensions(&self, default: usize, min: usize, max: usize) -> Result<(usize, usize), String> {
+        let width = self.size.unwrap_or(default);
+        let height = self.height.unwrap_or(width);
+        for (name, value) in [("width", width), ("height", height)] {
+            if !(min..=max).contains(&value) {
+                return Err(format!("Invalid {name} {value} - must be {min}-{max}"));
+            }
+        }
+        Ok((width, height))
+    }
+
+    fn no_size(&self) -> Result<(), String> {
+        if self.size.is_some() || self.height.is_some() {
+            return Err("Size is not supported for this day".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Parses a generated input with the parser of the day.
+type InputCheck = fn(&str) -> Result<(), String>;
+
+/// Generates an input for the specified problem.
+///
+/// # Arguments
+///
+/// * `year` - The year of the problem, one of those in [`SUPPORTED_DAYS`].
+/// * `day` - The day of the problem, one of those in [`SUPPORTED_DAYS`].
+/// * `options` - The seed and size of the input to generate.
+pub fn generate(year: u16, day: u8, options: &GeneratorOptions) -> Result<String, String> {
+    let mut random = Random::new(options.seed);
+
+    let (input, check): (String, InputCheck) = match (year, day) {
+        (2016, 12) => (assembunny_fibonacci(&mut random, options)?, |text| {
+            crate::year2016::assembunny::Computer::parse(text).map(|_| ())
+        }),
+        (2017, 18) => (duet_sort(&mut random, options)?, |text| {
+            crate::year2017::assembly::Computer::parse(text).map(|_| ())
+        }),
+        (2018, 19) => (elfcode_divisor_sum(&mut random, options)?, |text| {
+            crate::year2018::elfcode::Computer::parse(text).map(|_| ())
+        }),
+        (2021, 9) => (
+            digit_grid(&mut random, options, 255, |random| {
+                // Walls of nines separate the basins.
+                if random.chance(1, 4) {
+                    9
+                } else {
+                    random.below(9) as u8
+                }
+            })?,
+            |text| crate::year2021::day09::HeightMap::parse(text).map(|_| ()),
+        ),
+        (2021, 15) => (
+            // Limited by the maximum input length accepted by `solve`, rather than by the parser.
+            digit_grid(&mut random, options, 400, |random| {
+                random.between(1, 9) as u8
+            })?,
+            |text| crate::year2021::day15::Graph::parse(text, 1).map(|_| ()),
+        ),
+        (2023, 25) => (three_cut_graph(&mut random, options)?, |text| {
+            crate::year2023::day25::InputStruct::parse(text).map(|_| ())
+        }),
+        (2024, 23) => (lan_party(&mut random, options)?, |text| {
+            crate::year2024::day23::Network::parse(text).map(|_| ())
+        }),
+        _ => {
+            return Err(format!(
+                "Generating input is not supported for year={year}, day={day}"
+            ));
+        }
+    };
+
+    check(&input).map_err(|error| format!("Internal error - generated invalid input: {error}"))?;
+    Ok(input)
+}
+
+/// A grid of digits of the given dimensions, where each digit is produced by `digit`.
+fn digit_grid(
+    random: &mut Random,
+    options: &GeneratorOptions,
+    max_dimension: usize,
+    digit: fn(&mut Random) -> u8,
+) -> Result<String, String> {
+    let (width, height) = options.dimensions(100, 4, max_dimension)?;
+    let mut result = String::with_capacity((width + 1) * height);
+    for _ in 0..height {
+        for _ in 0..width {
+            result.push(char::from(b'0' + digit(random)));
+        }
+        result.push('\n');
+    }
+    Ok(result)
+}
+
+/// The structure of the 2016 day 12 program, computing a Fibonacci number with the size as index,
+/// then adding a product of two random numbers. Part two increases the index further.
+fn assembunny_fibonacci(random: &mut Random, options: &GeneratorOptions) -> Result<String, String> {
+    let index = options.size(26, 1, 28)?;
+    Ok(format!(
+        "cpy 1 a
+cpy 1 b
+cpy {index} d
+jnz c 2
+jnz 1 5
+cpy {} c
+inc d
+dec c
+jnz c -2
+cpy a c
+inc a
+dec b
+jnz b -2
+cpy c b
+dec d
+jnz d -6
+cpy {} c
+cpy {} d
+inc a
+dec d
+jnz d -2
+dec c
+jnz c -5
+",
+        random.between(5, 8),
+        random.between(10, 20),
+        random.between(10, 20),
+    ))
+}
+
+/// The structure of the 2017 day 18 program, where program zero generates a list of
+/// pseudo-random numbers and the two programs then cooperate to sort it.
+fn duet_sort(random: &mut Random, options: &GeneratorOptions) -> Result<String, String> {
+    let count = options.size(127, 2, 127)?;
+    Ok(format!(
+        "set i 31
+set a 1
+mul p 17
+jgz p p
+mul a 2
+add i -1
+jgz i -2
+add a -1
+set i {count}
+set p {}
+mul p 8505
+mod p a
+mul p 129749
+add p 12345
+mod p a
+set b p
+mod b 10000
+snd b
+add i -1
+jgz i -9
+jgz a 3
+rcv b
+jgz b -1
+set f 0
+set i {}
+rcv a
+rcv b
+set p a
+mul p -1
+add p b
+jgz p 4
+snd a
+set a b
+jgz 1 3
+snd b
+set f 1
+add i -1
+jgz i -11
+snd a
+jgz f -16
+jgz a -19
+",
+        random.between(1, 999),
+        count - 1
+    ))
+}
+
+/// The structure of the 2018 day 19 program, summing the divisors of a number computed
+/// from random constants, with randomly assigned registers.
+fn elfcode_divisor_sum(random: &mut Random, options: &GeneratorOptions) -> Result<String, String> {
+    options.no_size()?;
+
+    // Register 0 holds the result, the other roles are assigned to the remaining registers:
+    let mut registers = [1, 2, 3, 4, 5];
+    random.shuffle(&mut registers);
+    let [ip, outer, inner, temp, target] = registers;
+    let (first_addend, second_addend) = (random.between(1, 7), random.between(1, 19));
+    let mut ignored = || random.below(10);
+
+    let mut program = format!("#ip {ip}\n");
+    for (opcode, a, b, c) in [
+        ("addi", ip, 16, ip),
+        ("seti", 1, ignored(), outer),
+        ("seti", 1, ignored(), inner),
+        ("mulr", outer, inner, temp),
+        ("eqrr", temp, target, temp),
+        ("addr", temp, ip, ip),
+        ("addi", ip, 1, ip),
+        ("addr", outer, 0, 0),
+        ("addi", inner, 1, inner),
+        ("gtrr", inner, target, temp),
+        ("addr", ip, temp, ip),
+        ("seti", 2, ignored(), ip),
+        ("addi", outer, 1, outer),
+        ("gtrr", outer, target, temp),
+        ("addr", temp, ip, ip),
+        ("seti", 1, ignored(), ip),
+        ("mulr", ip, ip, ip),
+        ("addi", target, 2, target),
+        ("mulr", target, target, target),
+        ("mulr", ip, target, target),
+        ("muli", target, 11, target),
+        ("addi", temp, first_addend, temp),
+        ("mulr", temp, ip, temp),
+        ("addi", temp, second_addend, temp),
+        ("addr", target, temp, target),
+        ("addr", ip, 0, ip),
+        ("seti", 0, ignored(), ip),
+        ("setr", ip, ignored(), temp),
+        ("mulr", temp, ip, temp),
+        ("addr", ip, temp, temp),
+        ("mulr", ip, temp, temp),
+        ("muli", temp, 14, temp),
+        ("mulr", temp, ip, temp),
+        ("addr", target, temp, target),
+        ("seti", 0, ignored(), 0),
+        ("seti", 0, ignored(), ip),
+    ] {
+        let _ = writeln!(program, "{opcode} {a} {b} {c}");
+    }
+    Ok(program)
+}
+
+/// Returns `count` distinct names of `length` lowercase letters.
+fn random_names(random: &mut Random, count: usize, length: u32) -> Vec<String> {
+    let mut ids = (0..26_usize.pow(length)).collect::<Vec<_>>();
+    random.shuffle(&mut ids);
+    ids.into_iter()
+        .take(count)
+        .map(|id| {
+            (0..length)
+                .rev()
+                .map(|position| char::from(b'a' + (id / 26_usize.pow(position) % 26) as u8))
+                .collect()
+        })
+        .collect()
+}
+
+/// Adds the undirected edge to `edges` unless already present, returning whether it was added.
+fn add_edge(edges: &mut HashSet<(usize, usize)>, a: usize, b: usize) -> bool {
+    a != b && edges.insert((a.min(b), a.max(b)))
+}
+
+/// Two well connected components joined by exactly three edges, as in 2023 day 25.
+fn three_cut_graph(random: &mut Random, options: &GeneratorOptions) -> Result<String, String> {
+    let node_count = options.size(1500, 10, 10_000)?;
+    let component_sizes = [node_count / 2, node_count - node_count / 2];
+    let mut edges = HashSet::new();
+
+    let mut offset = 0;
+    for size in component_sizes {
+        // A ring where each node is also connected to the one two steps away cannot be
+        // split by removing three edges. Random chords shorten the distances in it.
+        for i in 0..size {
+            add_edge(&mut edges, offset + i, offset + (i + 1) % size);
+            add_edge(&mut edges, offset + i, offset + (i + 2) % size);
+        }
+        let mut chords = 0;
+        while chords < size * 3 / 2 {
+            if add_edge(
+                &mut edges,
+                offset + random.below(size),
+                offset + random.below(size),
+            ) {
+                chords += 1;
+            }
+        }
+        offset += size;
+    }
+
+    let mut first_side = (0..component_sizes[0]).collect::<Vec<_>>();
+    let mut second_side = (component_sizes[0]..node_count).collect::<Vec<_>>();
+    random.shuffle(&mut first_side);
+    random.shuffle(&mut second_side);
+    for (&a, &b) in first_side.iter().zip(second_side.iter()).take(3) {
+        add_edge(&mut edges, a, b);
+    }
+
+    // Each edge is listed once, on the line of one of its nodes:
+    let names = random_names(random, node_count, 3);
+    let mut connections = vec![Vec::new(); node_count];
+    let mut edges = edges.into_iter().collect::<Vec<_>>();
+    edges.sort_unstable();
+    for (a, b) in edges {
+        let (from, to) = if random.chance(1, 2) { (a, b) } else { (b, a) };
+        connections[from].push(to);
+    }
+    let mut lines = connections
+        .iter()
+        .enumerate()
+        .filter(|(_, to)| !to.is_empty())
+        .map(|(from, to)| {
+            let to = to.iter().map(|&to| names[to].as_str()).collect::<Vec<_>>();
+            format!("{}: {}\n", names[from], to.join(" "))
+        })
+        .collect::<Vec<_>>();
+    random.shuffle(&mut lines);
+    Ok(lines.concat())
+}
+
+/// A network of computers as in 2024 day 23, consisting of groups of fully connected
+/// computers, with one group larger than the others, and sparse links between groups.
+fn lan_party(random: &mut Random, options: &GeneratorOptions) -> Result<String, String> {
+    const LARGEST_GROUP_SIZE: usize = 13;
+    let node_count = options.size(520, 2 * LARGEST_GROUP_SIZE, 676)?;
+    let mut edges = HashSet::new();
+
+    let mut group_start = 0;
+    while group_start < node_count {
+        let group_size = if group_start == 0 {
+            LARGEST_GROUP_SIZE
+        } else {
+            LARGEST_GROUP_SIZE - 1
+        };
+        let group_end = (group_start + group_size).min(node_count);
+        for a in group_start..group_end {
+            for b in (a + 1)..group_end {
+                add_edge(&mut edges, a, b);
+            }
+        }
+        group_start = group_end;
+    }
+
+    // Link random pairs of computers outside the largest group. Each computer is linked at
+    // most once, so that no links form a group larger than two.
+    let mut linkable = (LARGEST_GROUP_SIZE..node_count).collect::<Vec<_>>();
+    random.shuffle(&mut linkable);
+    for pair in linkable.chunks_exact(2).take(node_count / 8) {
+        let same_group = (pair[0] - LARGEST_GROUP_SIZE) / (LARGEST_GROUP_SIZE - 1)
+            == (pair[1] - LARGEST_GROUP_SIZE) / (LARGEST_GROUP_SIZE - 1);
+        if !same_group {
+            add_edge(&mut edges, pair[0], pair[1]);
+        }
+    }
+
+    let names = random_names(random, node_count, 2);
+    let mut edges = edges.into_iter().collect::<Vec<_>>();
+    edges.sort_unstable();
+    let mut lines = edges
+        .into_iter()
+        .map(|(a, b)| {
+            let (from, to) = if random.chance(1, 2) { (a, b) } else { (b, a) };
+            format!("{}-{}\n", names[from], names[to])
+        })
+        .collect::<Vec<_>>();
+    random.shuffle(&mut lines);
+    Ok(lines.concat())
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+fn test_generate(year: u16, day: u8, seed: u64, size: Option<usize>) -> String {
+    let options = GeneratorOptions {
+        seed,
+        size,
+        height: None,
+    };
+    let input = generate(year, day, &options).unwrap();
+    assert_eq!(input, generate(year, day, &options).unwrap());
+    input
+}
+
+#[test]
+#[allow(clippy::unwrap_used)]
+fn tests() {
+    use crate::input::Input;
+
+    for seed in 0..3 {
+        let input = test_generate(2016, 12, seed, Some(10));
+        assert!(crate::year2016::day12::solve(&Input::part_one(&input)).is_ok());
+
+        let input = test_generate(2017, 18, seed, Some(20));
+        assert!(crate::year2017::day18::solve(&Input::part_one(&input)).is_ok());
+        // Program one sends the list back once per sorting round:
+        assert_eq!(
+            crate::year2017::day18::solve(&Input::part_two(&input)).unwrap() % 20,
+            0
+        );
+
+        let input = test_generate(2018, 19, seed, None);
+        assert!(crate::year2018::day19::solve(&Input::part_one(&input)).unwrap() > 836);
+        assert!(crate::year2018::day19::solve(&Input::part_two(&input)).is_ok());
+
+        let input = test_generate(2021, 9, seed, None);
+        assert_eq!(input.lines().count(), 100);
+        assert!(crate::year2021::day09::solve(&Input::part_two(&input)).is_ok());
+
+        let input = test_generate(2021, 15, seed, Some(20));
+        assert!(crate::year2021::day15::solve(&Input::part_two(&input)).is_ok());
+
+        let input = test_generate(2023, 25, seed, Some(200));
+        assert_eq!(
+            crate::year2023::day25::solve(&Input::part_one(&input)),
+            Ok(100 * 100)
+        );
+
+        let input = test_generate(2024, 23, seed, None);
+        assert!(crate::year2024::day23::solve(&Input::part_one(&input)).is_ok());
+        assert_eq!(
+            crate::year2024::day23::solve(&Input::part_two(&input))
+                .unwrap()
+                .len(),
+            13 * 3 - 1
+        );
+    }
+
+    assert_ne!(
+        test_generate(2021, 15, 1, None),
+        test_generate(2021, 15, 2, None)
+    );
+    assert_eq!(
+        generate(
+            2021,
+            15,
+            &GeneratorOptions {
+                seed: 1,
+                size: Some(3),
+                height: None
+            }
+        ),
+        Err("Invalid width 3 - must be 4-400".to_string())
+    );
+    assert_eq!(
+        generate(
+            2018,
+            19,
+            &GeneratorOptions {
+                seed: 1,
+                size: Some(3),
+                height: None
+            }
+        ),
+        Err("Size is not supported for this day".to_string())
+    );
+    assert!(generate(2019, 1, &GeneratorOptions::default()).is_err());
+}
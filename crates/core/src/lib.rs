@@ -26,6 +26,7 @@ assert_eq!(solution, Ok("2".to_string()));
 #![crate_name = "advent_of_code"]
 
 mod common;
+pub mod generator;
 #[cfg_attr(test, macro_use)]
 mod input;
 mod mod_exp;
@@ -40,6 +41,13 @@ mod year2022;
 mod year2023;
 mod year2024;
 
+pub use common::netlist;
+pub use common::register_machine;
+pub use year2018::elfcode;
+pub use year2019::day13::arcade;
+pub use year2019::int_code;
+pub use year2020::day08 as handheld;
+
 #[cfg(feature = "visualization")]
 pub type ResultType = String;
 
@@ -1,25 +1,187 @@
 #![forbid(unsafe_code)]
 
+mod cli;
+
 use std::env;
 use std::io::Read;
+use std::time::{Duration, Instant, SystemTime};
 
+use advent_of_code::generator::{generate, GeneratorOptions};
 use advent_of_code::solve_raw;
+use cli::store::{InputStore, DEFAULT_NAME};
+
+/// How often watched input files are checked for modification.
+const WATCH_POLL_INTERVAL: Duration = Duration::from_millis(300);
 
 #[allow(clippy::print_stdout)]
 #[allow(clippy::print_stderr)]
 fn main() -> Result<(), String> {
     let usage = || -> ! {
         eprintln!("usage: advent-of-code [year] [day] [part] < [input-file]");
+        eprintln!("       advent-of-code --watch [year] [day] [input-file]...");
+        eprintln!("       advent-of-code --svg [output-file] [year] [day] [part] < [input-file]");
+        eprintln!("       advent-of-code import [year] [day] [--name name] [--force] [input-file]");
+        eprintln!("       advent-of-code solve [year] [day] [part] [--name name]");
+        eprintln!("       advent-of-code inputs [year] [day]");
+        eprintln!("       advent-of-code fetch [year] [day] [--name name] [--force]");
+        eprintln!("       advent-of-code submit [year] [day] [part] [--name name] [answer]");
+        eprintln!("       advent-of-code intcode assemble [source-file]");
+        eprintln!("       advent-of-code intcode disassemble [program-file]");
+        eprintln!("       advent-of-code intcode cfg [program-file]");
+        eprintln!(
+            "       advent-of-code intcode coverage [--ascii] [--dot] [program-file] < [input-file]"
+        );
+        eprintln!("       advent-of-code intcode run [--script script-file] [program-file]");
+        eprintln!("       advent-of-code intcode debug [--trace trace-file] [program-file]");
+        eprintln!("       advent-of-code intcode diff [program-file] [program-file]");
+        eprintln!("       advent-of-code elfcode decompile [program-file]");
+        eprintln!("       advent-of-code arcade [--replay] [--fps fps] [program-file]");
+        eprintln!(
+            "       advent-of-code generate [year] [day] [--seed seed] [--size size] [--height height]"
+        );
         std::process::exit(1);
     };
 
-    let args: Vec<String> = env::args().collect();
+    let mut args: Vec<String> = env::args().collect();
 
     if args.iter().any(|s| s == "-v" || s == "--version") {
         println!(env!("CARGO_PKG_VERSION"));
         return Ok(());
     }
 
+    if args.get(1).is_some_and(|arg| arg == "--watch") {
+        if args.len() < 5 {
+            usage();
+        }
+        return watch(&args[2], &args[3], &args[4..]);
+    }
+
+    if args.get(1).is_some_and(|arg| arg == "--svg") {
+        if args.len() != 6 {
+            usage();
+        }
+        if let Err(error) = write_svg(&args[2], &args[3], &args[4], &args[5]) {
+            eprintln!("Error: {error}");
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if args.get(1).is_some_and(|arg| arg == "arcade") {
+        let replay = take_flag(&mut args, "--replay");
+        let frames_per_second = take_option(&mut args, "--fps")
+            .and_then(|fps| {
+                fps.map_or(Ok(30), |fps| {
+                    fps.parse::<u32>()
+                        .ok()
+                        .filter(|&fps| fps > 0)
+                        .ok_or_else(|| format!("Invalid value for --fps: {fps}"))
+                })
+            })
+            .unwrap_or_else(|error| {
+                eprintln!("Error: {error}");
+                usage();
+            });
+        if args.len() != 3 {
+            usage();
+        }
+        let result = std::fs::read_to_string(&args[2])
+            .map_err(|error| format!("Unable to read {}: {error}", args[2]))
+            .and_then(|text| advent_of_code::int_code::Program::parse(&text))
+            .and_then(|program| cli::arcade::play(program, replay, frames_per_second));
+        if let Err(error) = result {
+            eprintln!("Error: {error}");
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if args.get(1).is_some_and(|arg| arg == "intcode") {
+        let trace = take_option(&mut args, "--trace").unwrap_or_else(|error| {
+            eprintln!("Error: {error}");
+            usage();
+        });
+        let script = take_option(&mut args, "--script").unwrap_or_else(|error| {
+            eprintln!("Error: {error}");
+            usage();
+        });
+        let ascii_input = take_flag(&mut args, "--ascii");
+        let dot = take_flag(&mut args, "--dot");
+        let result = match (args.get(2).map(String::as_str), args.len()) {
+            (Some("assemble"), 3) => cli::intcode::assemble(None),
+            (Some("assemble"), 4) => cli::intcode::assemble(Some(&args[3])),
+            (Some("disassemble"), 3) => cli::intcode::disassemble(None),
+            (Some("disassemble"), 4) => cli::intcode::disassemble(Some(&args[3])),
+            (Some("cfg"), 3) => cli::intcode::control_flow_graph(None),
+            (Some("cfg"), 4) => cli::intcode::control_flow_graph(Some(&args[3])),
+            (Some("coverage"), 4) => cli::intcode::coverage(&args[3], ascii_input, dot),
+            (Some("run"), 4) => cli::intcode::run(&args[3], script.as_deref()),
+            (Some("debug"), 4) => cli::intcode::debug(&args[3], trace.as_deref()),
+            (Some("diff"), 5) => cli::intcode::diff(&args[3], &args[4]),
+            _ => usage(),
+        };
+        if let Err(error) = result {
+            eprintln!("Error: {error}");
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if args.get(1).is_some_and(|arg| arg == "elfcode") {
+        let result = match (args.get(2).map(String::as_str), args.len()) {
+            (Some("decompile"), 3) => cli::elfcode::decompile(None),
+            (Some("decompile"), 4) => cli::elfcode::decompile(Some(&args[3])),
+            _ => usage(),
+        };
+        if let Err(error) = result {
+            eprintln!("Error: {error}");
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if args.get(1).is_some_and(|arg| arg == "generate") {
+        let options = take_generator_options(&mut args).unwrap_or_else(|error| {
+            eprintln!("Error: {error}");
+            usage();
+        });
+        if args.len() != 4 {
+            usage();
+        }
+        if let Err(error) = generate_input(&args[2], &args[3], &options) {
+            eprintln!("Error: {error}");
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if matches!(
+        args.get(1).map(String::as_str),
+        Some("import" | "solve" | "inputs" | "fetch" | "submit")
+    ) {
+        let force = take_flag(&mut args, "--force");
+        let name = take_option(&mut args, "--name").unwrap_or_else(|error| {
+            eprintln!("Error: {error}");
+            usage();
+        });
+        let name = name.as_deref().unwrap_or(DEFAULT_NAME);
+        let result = match (args[1].as_str(), args.len()) {
+            ("import", 4) => import_input(&args[2], &args[3], name, None, force),
+            ("import", 5) => import_input(&args[2], &args[3], name, Some(&args[4]), force),
+            ("solve", 5) => solve_stored(&args[2], &args[3], &args[4], name),
+            ("inputs", 4) => list_inputs(&args[2], &args[3]),
+            ("fetch", 4) => fetch_input(&args[2], &args[3], name, force),
+            ("submit", 5) => submit_answer(&args[2], &args[3], &args[4], name, None),
+            ("submit", 6) => submit_answer(&args[2], &args[3], &args[4], name, Some(&args[5])),
+            _ => usage(),
+        };
+        if let Err(error) = result {
+            eprintln!("Error: {error}");
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
     let repeat = if let Ok(value) = env::var("AOC_REPEAT") {
         value
             .parse::<usize>()
@@ -32,10 +194,7 @@ fn main() -> Result<(), String> {
         let year = &args[1];
         let day = &args[2];
         let part = &args[3];
-        let mut input = String::new();
-        std::io::stdin()
-            .read_to_string(&mut input)
-            .map_err(|error| format!("Error reading input: {error}"))?;
+        let input = read_stdin()?;
 
         for _ in 0..repeat {
             match solve_raw(year, day, part, input.as_ref()) {
@@ -55,3 +214,257 @@ fn main() -> Result<(), String> {
     }
     Ok(())
 }
+
+/// Removes `option` and its following value from `args`, returning the value if present.
+fn take_option(args: &mut Vec<String>, option: &str) -> Result<Option<String>, String> {
+    let Some(idx) = args.iter().position(|arg| arg == option) else {
+        return Ok(None);
+    };
+    if idx + 1 >= args.len() {
+        return Err(format!("Missing value for {option}"));
+    }
+    let value = args.remove(idx + 1);
+    args.remove(idx);
+    Ok(Some(value))
+}
+
+/// Removes `flag` from `args`, returning whether it was present.
+fn take_flag(args: &mut Vec<String>, flag: &str) -> bool {
+    let len_before = args.len();
+    args.retain(|arg| arg != flag);
+    args.len() != len_before
+}
+
+/// Removes the `--seed`, `--size` and `--height` options from `args`.
+fn take_generator_options(args: &mut Vec<String>) -> Result<GeneratorOptions, String> {
+    let mut take_number = |option: &str| -> Result<Option<u64>, String> {
+        take_option(args, option)?
+            .map(|value| {
+                value
+                    .parse::<u64>()
+                    .map_err(|_| format!("Invalid value for {option}: {value}"))
+            })
+            .transpose()
+    };
+    Ok(GeneratorOptions {
+        seed: take_number("--seed")?.unwrap_or_default(),
+        size: take_number("--size")?.map(|size| size as usize),
+        height: take_number("--height")?.map(|height| height as usize),
+    })
+}
+
+fn parse_year_and_day(year: &str, day: &str) -> Result<(u16, u8), String> {
+    let year = year.parse::<u16>().map_err(|_| "Invalid year")?;
+    let day = day.parse::<u8>().map_err(|_| "Invalid day")?;
+    Ok((year, day))
+}
+
+/// Saves an input, read from the given file or else stdin, to the input store.
+///
+/// An already stored input is only replaced if `force` is set.
+#[allow(clippy::print_stderr)]
+fn import_input(
+    year: &str,
+    day: &str,
+    name: &str,
+    path: Option<&str>,
+    force: bool,
+) -> Result<(), String> {
+    let (year, day) = parse_year_and_day(year, day)?;
+    let store = InputStore::from_env()?;
+    if !force && store.contains(year, day, name)? {
+        return Err(format!(
+            "Input already stored at {} - use --force to replace it",
+            store.path_of(year, day, name)?.display()
+        ));
+    }
+    let input = match path {
+        Some(path) => std::fs::read_to_string(path)
+            .map_err(|error| format!("Unable to read {path}: {error}"))?,
+        None => read_stdin()?,
+    };
+    if input.trim().is_empty() {
+        return Err("Empty input".to_string());
+    }
+    let saved_to = store.save(year, day, name, &input)?;
+    eprintln!("Saved input to {}", saved_to.display());
+    Ok(())
+}
+
+#[allow(clippy::print_stdout)]
+fn solve_stored(year: &str, day: &str, part: &str, name: &str) -> Result<(), String> {
+    let (parsed_year, parsed_day) = parse_year_and_day(year, day)?;
+    let input = InputStore::from_env()?.load(parsed_year, parsed_day, name)?;
+    println!("{}", solve_raw(year, day, part, &input)?);
+    Ok(())
+}
+
+#[allow(clippy::print_stdout)]
+fn list_inputs(year: &str, day: &str) -> Result<(), String> {
+    let (year, day) = parse_year_and_day(year, day)?;
+    for name in InputStore::from_env()?.names(year, day)? {
+        println!("{name}");
+    }
+    Ok(())
+}
+
+/// Downloads an input into the input store, unless already stored there and `force` is not set.
+#[cfg(feature = "online")]
+#[allow(clippy::print_stderr)]
+fn fetch_input(year: &str, day: &str, name: &str, force: bool) -> Result<(), String> {
+    let (year, day) = parse_year_and_day(year, day)?;
+    let store = InputStore::from_env()?;
+    let client = cli::client::Client::from_env(&store)?;
+    client.fetch_input(&store, year, day, name, force)?;
+    eprintln!(
+        "Input stored at {}",
+        store.path_of(year, day, name)?.display()
+    );
+    Ok(())
+}
+
+/// Submits an answer, computing it from the (possibly downloaded) stored input if not given.
+#[cfg(feature = "online")]
+#[allow(clippy::print_stdout)]
+fn submit_answer(
+    year: &str,
+    day: &str,
+    part: &str,
+    name: &str,
+    answer: Option<&str>,
+) -> Result<(), String> {
+    use cli::client::{Client, SubmissionOutcome};
+
+    let (parsed_year, parsed_day) = parse_year_and_day(year, day)?;
+    let parsed_part = part.parse::<u8>().map_err(|_| "Invalid part")?;
+    let store = InputStore::from_env()?;
+    let client = Client::from_env(&store)?;
+
+    let answer = match answer {
+        Some(answer) => answer.to_string(),
+        None => {
+            let input = client.fetch_input(&store, parsed_year, parsed_day, name, false)?;
+            solve_raw(year, day, part, &input)?
+        }
+    };
+
+    match client.submit_answer(parsed_year, parsed_day, parsed_part, &answer)? {
+        SubmissionOutcome::Correct => println!("Correct answer: {answer}"),
+        SubmissionOutcome::AlreadySolved => {
+            println!("Part {part} already solved - answer {answer} not checked");
+        }
+        SubmissionOutcome::Incorrect(message)
+        | SubmissionOutcome::TooRecent(message)
+        | SubmissionOutcome::Unknown(message) => {
+            return Err(format!("Answer {answer} not accepted: {message}"));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "online"))]
+fn fetch_input(_year: &str, _day: &str, _name: &str, _force: bool) -> Result<(), String> {
+    Err("Fetching inputs requires building with '--features online'".to_string())
+}
+
+#[cfg(not(feature = "online"))]
+fn submit_answer(
+    _year: &str,
+    _day: &str,
+    _part: &str,
+    _name: &str,
+    _answer: Option<&str>,
+) -> Result<(), String> {
+    Err("Submitting answers requires building with '--features online'".to_string())
+}
+
+#[allow(clippy::print_stdout)]
+fn generate_input(year: &str, day: &str, options: &GeneratorOptions) -> Result<(), String> {
+    let (year, day) = parse_year_and_day(year, day)?;
+    print!("{}", generate(year, day, options)?);
+    Ok(())
+}
+
+fn read_stdin() -> Result<String, String> {
+    let mut input = String::new();
+    std::io::stdin()
+        .read_to_string(&mut input)
+        .map_err(|error| format!("Error reading input: {error}"))?;
+    Ok(input)
+}
+
+/// Solves the problem with input from stdin and writes the produced SVG visualization to `path`.
+///
+/// With the `visualization` feature enabled, [solve_raw] returns the visualization
+/// instead of the answer, which is empty for days without one.
+#[cfg(feature = "visualization")]
+fn write_svg(path: &str, year: &str, day: &str, part: &str) -> Result<(), String> {
+    let input = read_stdin()?;
+    let svg = solve_raw(year, day, part, &input)?;
+    if svg.is_empty() {
+        return Err(format!(
+            "No visualization available for year {year}, day {day}, part {part}"
+        ));
+    }
+    std::fs::write(path, svg).map_err(|error| format!("Unable to write {path}: {error}"))
+}
+
+#[cfg(not(feature = "visualization"))]
+fn write_svg(_path: &str, _year: &str, _day: &str, _part: &str) -> Result<(), String> {
+    Err("Rendering SVG requires building with '--features visualization'".to_string())
+}
+
+/// Polls the input files for modification, re-solving both parts whenever one changes.
+///
+/// Only the standard library is used, so a changed modification time or file size is
+/// what triggers a new run. Files that are temporarily missing, as when an editor
+/// replaces a file on save, are reported and picked up again once they reappear.
+#[allow(clippy::print_stdout)]
+#[allow(clippy::print_stderr)]
+fn watch(year: &str, day: &str, paths: &[String]) -> Result<(), String> {
+    let mut last_seen: Vec<Option<(SystemTime, u64)>> = vec![None; paths.len()];
+    let mut missing = vec![false; paths.len()];
+
+    loop {
+        for (idx, path) in paths.iter().enumerate() {
+            let current = match std::fs::metadata(path)
+                .and_then(|metadata| Ok((metadata.modified()?, metadata.len())))
+            {
+                Ok(current) => current,
+                Err(error) => {
+                    if !missing[idx] {
+                        eprintln!("Unable to read {path}: {error}");
+                        missing[idx] = true;
+                    }
+                    continue;
+                }
+            };
+            missing[idx] = false;
+
+            if last_seen[idx] == Some(current) {
+                continue;
+            }
+            last_seen[idx] = Some(current);
+
+            match std::fs::read_to_string(path) {
+                Ok(input) => {
+                    println!("== {path} ({year} day {day})");
+                    for part in ["1", "2"] {
+                        let start = Instant::now();
+                        let result = solve_raw(year, day, part, &input);
+                        let elapsed = start.elapsed();
+                        match result {
+                            Ok(answer) => println!("Part {part}: {answer} ({elapsed:.2?})"),
+                            Err(error) => println!("Part {part}: Error: {error} ({elapsed:.2?})"),
+                        }
+                    }
+                }
+                Err(error) => {
+                    eprintln!("Unable to read {path}: {error}");
+                }
+            }
+        }
+
+        std::thread::sleep(WATCH_POLL_INTERVAL);
+    }
+}
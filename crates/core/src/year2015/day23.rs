@@ -1,5 +1,10 @@
+use std::fmt::{Display, Formatter};
+
+use crate::common::register_machine::{Effect, InstructionSet, Machine};
 use crate::input::Input;
 
+const MAX_INSTRUCTIONS: u64 = 10_000_000;
+
 #[derive(Copy, Clone)]
 enum Instruction {
     Half(u8),
@@ -10,9 +15,20 @@ enum Instruction {
     JumpIfOne(u8, i16),
 }
 
-struct Computer {
-    registers: [u32; 2],
-    instructions: Vec<Instruction>,
+struct Computer;
+
+impl Display for Instruction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = |register| Computer::REGISTER_NAMES[register as usize];
+        match *self {
+            Self::Half(register) => write!(f, "hlf {}", name(register)),
+            Self::Triple(register) => write!(f, "tpl {}", name(register)),
+            Self::Increment(register) => write!(f, "inc {}", name(register)),
+            Self::Jump(offset) => write!(f, "jmp {offset:+}"),
+            Self::JumpIfEven(register, offset) => write!(f, "jie {}, {offset:+}", name(register)),
+            Self::JumpIfOne(register, offset) => write!(f, "jio {}, {offset:+}", name(register)),
+        }
+    }
 }
 
 impl Computer {
@@ -26,7 +42,7 @@ impl Computer {
         })
     }
 
-    fn parse(input: &str) -> Result<Self, String> {
+    fn parse(input: &str) -> Result<Machine<Self>, String> {
         let mut instructions = Vec::new();
 
         for line in input.lines() {
@@ -58,60 +74,52 @@ impl Computer {
             });
         }
 
-        Ok(Self {
-            registers: [0, 0],
-            instructions,
-        })
+        Ok(Machine::new(instructions))
     }
+}
+
+impl InstructionSet for Computer {
+    type Word = u32;
+    type Registers = [u32; 2];
+    type Instruction = Instruction;
+    type State = ();
+    const REGISTER_NAMES: &'static [&'static str] = &["a", "b"];
 
-    fn run(&mut self) {
-        let mut instruction_pointer = 0_i16;
-        loop {
-            if instruction_pointer < 0 {
-                break;
+    #[inline]
+    fn execute(
+        instruction: Instruction,
+        machine: &mut Machine<Self>,
+    ) -> Result<Effect<u32>, String> {
+        let registers = &mut machine.registers;
+        Ok(match instruction {
+            Instruction::Increment(register) => {
+                registers[register as usize] += 1;
+                Effect::Next
             }
-            match self.instructions.get(instruction_pointer as usize) {
-                Some(&Instruction::Increment(register)) => {
-                    self.registers[register as usize] += 1;
-                    instruction_pointer += 1;
-                }
-                Some(&Instruction::Half(register)) => {
-                    self.registers[register as usize] /= 2;
-                    instruction_pointer += 1;
-                }
-                Some(&Instruction::Triple(register)) => {
-                    self.registers[register as usize] *= 3;
-                    instruction_pointer += 1;
-                }
-                Some(&Instruction::Jump(offset)) => {
-                    instruction_pointer += offset;
-                }
-                Some(&Instruction::JumpIfEven(register, offset)) => {
-                    instruction_pointer += if self.registers[register as usize] % 2 == 0 {
-                        offset
-                    } else {
-                        1
-                    }
-                }
-                Some(&Instruction::JumpIfOne(register, offset)) => {
-                    instruction_pointer += if self.registers[register as usize] == 1 {
-                        offset
-                    } else {
-                        1
-                    }
-                }
-                None => {
-                    break;
-                }
+            Instruction::Half(register) => {
+                registers[register as usize] /= 2;
+                Effect::Next
             }
-        }
+            Instruction::Triple(register) => {
+                registers[register as usize] *= 3;
+                Effect::Next
+            }
+            Instruction::Jump(offset) => Effect::Jump(offset.into()),
+            Instruction::JumpIfEven(register, offset) if registers[register as usize] % 2 == 0 => {
+                Effect::Jump(offset.into())
+            }
+            Instruction::JumpIfOne(register, offset) if registers[register as usize] == 1 => {
+                Effect::Jump(offset.into())
+            }
+            Instruction::JumpIfEven(..) | Instruction::JumpIfOne(..) => Effect::Next,
+        })
     }
 }
 
 pub fn solve(input: &Input) -> Result<u32, String> {
     let mut computer = Computer::parse(input.text)?;
     computer.registers[0] = input.part_values(0, 1);
-    computer.run();
+    computer.run_until_halt(MAX_INSTRUCTIONS)?;
     Ok(computer.registers[1])
 }
 
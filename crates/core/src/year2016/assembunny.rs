@@ -1,7 +1,17 @@
+pub mod optimizer;
+
+use std::fmt::{Display, Formatter};
+
+use crate::common::register_machine::{Effect, InstructionSet, Machine, NativeLoops};
+use optimizer::NativeLoop;
+
 pub type Word = i32;
+
+/// The maximum number of instructions to execute before giving up on a program.
+pub const MAX_INSTRUCTIONS: u64 = 1_000_000_000;
 type Register = u8;
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
 pub enum ValueOrRegister {
     Value(Word),
     Register(Register),
@@ -17,6 +27,19 @@ impl ValueOrRegister {
     }
 }
 
+impl Display for ValueOrRegister {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Self::Value(value) => write!(f, "{value}"),
+            Self::Register(register) => write!(f, "{}", register_name(register)),
+        }
+    }
+}
+
+const fn register_name(register: Register) -> &'static str {
+    Computer::REGISTER_NAMES[register as usize]
+}
+
 fn parse_register(input: &str) -> Result<Register, String> {
     if ["a", "b", "c", "d"].contains(&input) {
         Ok(input.as_bytes()[0] - b'a')
@@ -25,7 +48,7 @@ fn parse_register(input: &str) -> Result<Register, String> {
     }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
 pub enum Instruction {
     // cpy x y copies x (either an integer or the value of a register) into register y.
     Copy(ValueOrRegister, Register),
@@ -43,6 +66,20 @@ pub enum Instruction {
     Out(ValueOrRegister),
 }
 
+impl Display for Instruction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Self::Copy(a, b) => write!(f, "cpy {a} {}", register_name(b)),
+            Self::Increase(a) => write!(f, "inc {}", register_name(a)),
+            Self::Decrease(a) => write!(f, "dec {}", register_name(a)),
+            Self::Jump(a, b) => write!(f, "jnz {a} {b}"),
+            Self::Toggle(a) => write!(f, "tgl {}", register_name(a)),
+            Self::Nop => write!(f, "nop"),
+            Self::Out(a) => write!(f, "out {a}"),
+        }
+    }
+}
+
 impl Instruction {
     fn parse(input: &str) -> Result<Self, String> {
         let words = input.split(' ').collect::<Vec<_>>();
@@ -121,68 +158,84 @@ impl Instruction {
     }
 }
 
-pub struct Computer {
-    // The assembunny code you've extracted operates on four registers (a, b, c, and d) that start at 0 and can hold any integer
-    pub(crate) registers: [Word; 4],
-    pub(crate) instructions: Vec<Instruction>,
+/// The assembunny instruction set, operating on four registers (a, b, c, and d) that start
+/// at 0 and can hold any integer.
+#[derive(Clone)]
+pub struct Computer;
+
+#[derive(Clone, Default)]
+pub struct State {
+    /// Set by [`Computer::optimize`].
+    loops: Option<NativeLoops<NativeLoop>>,
 }
 
 impl Computer {
-    pub(crate) fn parse(input: &str) -> Result<Self, String> {
+    pub(crate) fn parse(input: &str) -> Result<Machine<Self>, String> {
         let mut instructions = Vec::new();
         for line in input.lines() {
             instructions.push(Instruction::parse(line)?);
         }
-        Ok(Self {
-            registers: [0, 0, 0, 0],
-            instructions,
-        })
+        Ok(Machine::new(instructions))
     }
 
-    pub(crate) fn execute(&mut self) -> Word {
-        let mut current_instruction = 0;
-        'outer: while let Some(&instruction) = self.instructions.get(current_instruction) {
-            match instruction {
-                Instruction::Copy(value_or_register, register) => {
-                    let value = self.value_of(value_or_register);
-                    self.registers[register as usize] = value;
-                }
-                Instruction::Increase(register) => {
-                    self.registers[register as usize] += 1;
-                }
-                Instruction::Decrease(register) => {
-                    self.registers[register as usize] -= 1;
-                }
-                Instruction::Jump(first, second) => {
-                    if self.value_of(first) != 0 {
-                        current_instruction =
-                            (current_instruction as Word + self.value_of(second)) as usize;
-                        continue 'outer;
-                    }
+    const fn value_of(registers: &[Word; 4], value_or_register: ValueOrRegister) -> Word {
+        match value_or_register {
+            ValueOrRegister::Value(word) => word,
+            ValueOrRegister::Register(register_idx) => registers[register_idx as usize],
+        }
+    }
+}
+
+impl InstructionSet for Computer {
+    type Word = Word;
+    type Registers = [Word; 4];
+    type Instruction = Instruction;
+    type State = State;
+    const REGISTER_NAMES: &'static [&'static str] = &["a", "b", "c", "d"];
+
+    #[inline]
+    fn execute(
+        instruction: Instruction,
+        machine: &mut Machine<Self>,
+    ) -> Result<Effect<Word>, String> {
+        if let Some(effect) = machine.run_native_loop(|machine| machine.state.loops.as_ref()) {
+            return Ok(effect);
+        }
+
+        let registers = &mut machine.registers;
+        match instruction {
+            Instruction::Copy(value_or_register, register) => {
+                registers[register as usize] = Self::value_of(registers, value_or_register);
+            }
+            Instruction::Increase(register) => {
+                registers[register as usize] += 1;
+            }
+            Instruction::Decrease(register) => {
+                registers[register as usize] -= 1;
+            }
+            Instruction::Jump(first, second) => {
+                if Self::value_of(registers, first) != 0 {
+                    return Ok(Effect::Jump(Self::value_of(registers, second).into()));
                 }
-                Instruction::Toggle(register) => {
-                    let value = self.registers[register as usize];
-                    let ptr = current_instruction as Word + value;
-                    if ptr < 0 || ptr >= self.instructions.len() as Word {
-                        // If an attempt is made to toggle an instruction outside the program, nothing happens.
-                    } else {
-                        self.instructions[ptr as usize] = self.instructions[ptr as usize].toggle();
+            }
+            Instruction::Toggle(register) => {
+                let target = machine.instruction_pointer + i64::from(registers[register as usize]);
+                // If an attempt is made to toggle an instruction outside the program, nothing happens.
+                if let Some(instruction) = usize::try_from(target)
+                    .ok()
+                    .and_then(|target| machine.instructions.get_mut(target))
+                {
+                    *instruction = instruction.toggle();
+                    if machine.state.loops.is_some() {
+                        Self::optimize(machine);
                     }
                 }
-                Instruction::Nop => {}
-                Instruction::Out(_a) => {}
             }
-
-            current_instruction += 1;
-        }
-
-        self.registers[0]
-    }
-
-    const fn value_of(&self, value_or_register: ValueOrRegister) -> Word {
-        match value_or_register {
-            ValueOrRegister::Value(word) => word,
-            ValueOrRegister::Register(register_idx) => self.registers[register_idx as usize],
+            Instruction::Nop => {}
+            Instruction::Out(value_or_register) => {
+                return Ok(Effect::Output(Self::value_of(registers, value_or_register)));
+            }
         }
+        Ok(Effect::Next)
     }
 }
@@ -0,0 +1,344 @@
+//! A peephole optimizer replacing add, multiply and division loops of assembunny programs
+//! with native operations.
+//!
+//! An add loop changes a target register by one while counting a counter register down
+//! (or up) to zero:
+//!
+//! ```text
+//! inc a
+//! dec c
+//! jnz c -2
+//! ```
+//!
+//! A multiply loop repeats an add loop, copying a factor into its counter each time:
+//!
+//! ```text
+//! cpy b c
+//! inc a
+//! dec c
+//! jnz c -2
+//! dec d
+//! jnz d -5
+//! ```
+//!
+//! A division loop adds the quotient of a division by a constant to a target register,
+//! leaving the divisor minus the remainder in its counter:
+//!
+//! ```text
+//! cpy 2 c
+//! jnz b 2
+//! jnz 1 6
+//! dec b
+//! dec c
+//! jnz c -4
+//! inc a
+//! jnz 1 -7
+//! ```
+//!
+//! Since `tgl` can change instructions, the loops are found again whenever the program is
+//! changed. An add loop whose counter moves away from zero, such as one with `inc c` while
+//! `c` is positive, never ends and is left to the interpreter.
+
+use super::{Computer, Instruction, Register, State, ValueOrRegister, Word};
+use crate::common::register_machine::{Effect, Machine, NativeLoops, NativeOperation};
+
+#[derive(Copy, Clone)]
+pub struct Counter {
+    register: Register,
+    step: Word,
+}
+
+impl Counter {
+    /// The number of steps for the counter to reach zero, if it will.
+    const fn iterations(self, registers: &[Word; 4]) -> Option<Word> {
+        let value = registers[self.register as usize];
+        match self.step {
+            -1 if value > 0 => Some(value),
+            1 if value < 0 => value.checked_neg(),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+pub enum NativeLoop {
+    Add {
+        target: Register,
+        step: Word,
+        counter: Counter,
+    },
+    Multiply {
+        target: Register,
+        step: Word,
+        factor: ValueOrRegister,
+        inner_counter: Counter,
+        counter: Counter,
+    },
+    Divide {
+        target: Register,
+        dividend: Register,
+        divisor: Word,
+        counter: Register,
+    },
+}
+
+impl NativeLoop {
+    const fn length(self) -> i64 {
+        match self {
+            Self::Add { .. } => 3,
+            Self::Multiply { .. } => 6,
+            Self::Divide { .. } => 8,
+        }
+    }
+}
+
+impl NativeOperation<Computer> for NativeLoop {
+    fn execute(self, machine: &mut Machine<Computer>) -> Option<Effect<Word>> {
+        let registers = &mut machine.registers;
+        // Registers are only written once the loop is known to run natively, since the
+        // interpreter runs it from the unchanged registers otherwise:
+        match self {
+            Self::Add {
+                target,
+                step,
+                counter,
+            } => {
+                let change = step * counter.iterations(registers)?;
+                let target_value = registers[target as usize].checked_add(change)?;
+                registers[counter.register as usize] = 0;
+                registers[target as usize] = target_value;
+            }
+            Self::Multiply {
+                target,
+                step,
+                factor,
+                inner_counter,
+                counter,
+            } => {
+                let mut factor_registers = *registers;
+                factor_registers[inner_counter.register as usize] =
+                    Computer::value_of(registers, factor);
+                let inner_iterations = inner_counter.iterations(&factor_registers)?;
+                let iterations = counter.iterations(registers)?;
+                let change = step * inner_iterations.checked_mul(iterations)?;
+                let target_value = registers[target as usize].checked_add(change)?;
+                registers[inner_counter.register as usize] = 0;
+                registers[counter.register as usize] = 0;
+                registers[target as usize] = target_value;
+            }
+            Self::Divide {
+                target,
+                dividend,
+                divisor,
+                counter,
+            } => {
+                let dividend_value = registers[dividend as usize];
+                if dividend_value < 0 {
+                    return None;
+                }
+                let target_value =
+                    registers[target as usize].checked_add(dividend_value / divisor)?;
+                registers[dividend as usize] = 0;
+                registers[counter as usize] = divisor - dividend_value % divisor;
+                registers[target as usize] = target_value;
+            }
+        }
+        Some(Effect::Jump(self.length()))
+    }
+}
+
+impl Computer {
+    /// Makes the machine run add, multiply and division loops as native operations, which
+    /// `tgl` keeps up to date by optimizing the toggled program again.
+    pub fn optimize(machine: &mut Machine<Self>) {
+        machine.state = State {
+            loops: Some(NativeLoops::find(
+                &machine.instructions,
+                |_, instructions| {
+                    divide_loop(instructions)
+                        .or_else(|| multiply_loop(instructions))
+                        .or_else(|| add_loop(instructions))
+                },
+            )),
+        };
+    }
+}
+
+const fn change(instruction: Instruction) -> Option<(Register, Word)> {
+    match instruction {
+        Instruction::Increase(register) => Some((register, 1)),
+        Instruction::Decrease(register) => Some((register, -1)),
+        _ => None,
+    }
+}
+
+/// If the instruction jumps back by `offset` instructions if the register is not zero.
+const fn is_jump_back(instruction: Instruction, register: Register, offset: Word) -> bool {
+    matches!(
+        instruction,
+        Instruction::Jump(ValueOrRegister::Register(r), ValueOrRegister::Value(o))
+            if r == register && o == -offset
+    )
+}
+
+/// If the instruction always jumps by `offset` instructions.
+const fn is_unconditional_jump(instruction: Instruction, offset: Word) -> bool {
+    matches!(
+        instruction,
+        Instruction::Jump(ValueOrRegister::Value(condition), ValueOrRegister::Value(o))
+            if condition != 0 && o == offset
+    )
+}
+
+fn add_loop(instructions: &[Instruction]) -> Option<NativeLoop> {
+    let [first, second, jump, ..] = *instructions else {
+        return None;
+    };
+    let (first, second) = (change(first)?, change(second)?);
+    [(first, second), (second, first)]
+        .into_iter()
+        .find(|&((target, _), (counter, _))| target != counter && is_jump_back(jump, counter, 2))
+        .map(
+            |((target, step), (register, counter_step))| NativeLoop::Add {
+                target,
+                step,
+                counter: Counter {
+                    register,
+                    step: counter_step,
+                },
+            },
+        )
+}
+
+fn multiply_loop(instructions: &[Instruction]) -> Option<NativeLoop> {
+    let [Instruction::Copy(factor, inner_register), _, _, _, outer_change, outer_jump, ..] =
+        *instructions
+    else {
+        return None;
+    };
+    let Some(NativeLoop::Add {
+        target,
+        step,
+        counter: inner_counter,
+    }) = add_loop(&instructions[1..])
+    else {
+        return None;
+    };
+    let (register, counter_step) = change(outer_change)?;
+    let registers = [target, inner_register, register];
+    let distinct = target != register && inner_register != register;
+    let factor_is_constant =
+        !matches!(factor, ValueOrRegister::Register(r) if registers.contains(&r));
+    (inner_register == inner_counter.register
+        && distinct
+        && factor_is_constant
+        && is_jump_back(outer_jump, register, 5))
+    .then_some(NativeLoop::Multiply {
+        target,
+        step,
+        factor,
+        inner_counter,
+        counter: Counter {
+            register,
+            step: counter_step,
+        },
+    })
+}
+
+fn divide_loop(instructions: &[Instruction]) -> Option<NativeLoop> {
+    use Instruction::{Copy, Jump};
+    use ValueOrRegister::{Register as R, Value as V};
+
+    let [Copy(V(divisor), counter), Jump(R(dividend), V(2)), exit_jump, ..] = *instructions else {
+        return None;
+    };
+    let [dividend_change, counter_change, counter_jump, target_change, loop_jump, ..] =
+        instructions[3..]
+    else {
+        return None;
+    };
+    let Some((target, 1)) = change(target_change) else {
+        return None;
+    };
+    (divisor > 0
+        && is_unconditional_jump(exit_jump, 6)
+        && change(dividend_change) == Some((dividend, -1))
+        && change(counter_change) == Some((counter, -1))
+        && is_jump_back(counter_jump, counter, 4)
+        && is_unconditional_jump(loop_jump, -7)
+        && target != dividend
+        && target != counter
+        && dividend != counter)
+        .then_some(NativeLoop::Divide {
+            target,
+            dividend,
+            divisor,
+            counter,
+        })
+}
+
+#[test]
+#[allow(clippy::unwrap_used)]
+fn test() {
+    use crate::common::register_machine::Stop;
+
+    // Subtracting 4 * 3 from a, with the add loop counting c up from -3 to 0:
+    let mut computer = Computer::parse(
+        "cpy 4 d
+cpy -3 b
+cpy b c
+dec a
+inc c
+jnz c -2
+dec d
+jnz d -5
+inc a",
+    )
+    .unwrap();
+    Computer::optimize(&mut computer);
+    computer.run_until_halt(100).unwrap();
+    assert_eq!(computer.executed_instructions, 4);
+    assert_eq!(computer.registers, [-11, -3, 0, 0]);
+
+    // Dividing 17 by 5 into a, leaving c as 5 - 17 % 5:
+    let mut computer = Computer::parse(
+        "cpy 17 b
+cpy 5 c
+jnz b 2
+jnz 1 6
+dec b
+dec c
+jnz c -4
+inc a
+jnz 1 -7",
+    )
+    .unwrap();
+    Computer::optimize(&mut computer);
+    computer.run_until_halt(100).unwrap();
+    assert_eq!(computer.executed_instructions, 2);
+    assert_eq!(computer.registers, [3, 0, 3, 0]);
+
+    // Toggling `inc c` into `dec c` turns a loop which never ends into an add loop, which
+    // is only run natively since the program is optimized again after the toggle:
+    let mut computer = Computer::parse("cpy 3 c\ncpy 2 d\ntgl d\ninc a\ninc c\njnz c -2").unwrap();
+    Computer::optimize(&mut computer);
+    computer.run_until_halt(100).unwrap();
+    assert_eq!(computer.executed_instructions, 4);
+    assert_eq!(computer.registers, [3, 0, 0, 2]);
+
+    // An add loop overflowing its target is run as instructions, from unchanged registers:
+    let mut computer =
+        Computer::parse("cpy 2147483642 a\ncpy 10 c\ninc a\ndec c\njnz c -2").unwrap();
+    let mut unoptimized = computer.clone();
+    Computer::optimize(&mut computer);
+    assert_eq!(computer.run(11), Ok(Stop::BudgetExhausted));
+    assert_eq!(unoptimized.run(11), Ok(Stop::BudgetExhausted));
+    assert_eq!(computer.registers, unoptimized.registers);
+    assert_eq!(computer.registers, [i32::MAX - 2, 0, 7, 0]);
+
+    // An add loop with a counter which would not reach zero is run as instructions:
+    let mut computer = Computer::parse("inc a\ninc b\njnz b -2").unwrap();
+    Computer::optimize(&mut computer);
+    assert_eq!(computer.run(4), Ok(Stop::BudgetExhausted));
+    assert_eq!(computer.registers, [2, 1, 0, 0]);
+}
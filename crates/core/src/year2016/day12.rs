@@ -1,10 +1,12 @@
-use super::assembunny::{Computer, Word};
+use super::assembunny::{Computer, Word, MAX_INSTRUCTIONS};
 use crate::input::Input;
 
 pub fn solve(input: &Input) -> Result<Word, String> {
     let mut computer = Computer::parse(input.text)?;
     computer.registers[2] = input.part_values(0, 1);
-    Ok(computer.execute())
+    Computer::optimize(&mut computer);
+    computer.run_until_halt(MAX_INSTRUCTIONS)?;
+    Ok(computer.registers[0])
 }
 
 #[test]
@@ -1,26 +1,14 @@
-use super::assembunny::{Computer, Instruction, ValueOrRegister, Word};
+use super::assembunny::{Computer, Word, MAX_INSTRUCTIONS};
 use crate::input::Input;
 
-fn factorial(num: Word) -> Word {
-    match num {
-        0 | 1 => 1,
-        _ => factorial(num - 1) * num,
-    }
-}
-
 pub fn solve(input: &Input) -> Result<Word, String> {
     let mut computer = Computer::parse(input.text)?;
-    let register_a_value = input.part_values(7, 12);
-    if computer.instructions.len() > 20 {
-        if let Instruction::Copy(ValueOrRegister::Value(a), _) = computer.instructions[19] {
-            if let Instruction::Jump(ValueOrRegister::Value(b), _) = computer.instructions[20] {
-                return Ok(a * b + factorial(register_a_value));
-            }
-        }
-    }
-
-    computer.registers[0] = register_a_value;
-    Ok(computer.execute())
+    computer.registers[0] = input.part_values(7, 12);
+    // The program computes a factorial by multiplication loops, which are run natively and
+    // found again whenever toggling modifies the program.
+    Computer::optimize(&mut computer);
+    computer.run_until_halt(MAX_INSTRUCTIONS)?;
+    Ok(computer.registers[0])
 }
 
 #[test]
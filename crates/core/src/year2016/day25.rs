@@ -1,21 +1,51 @@
-use super::assembunny::{Computer, Instruction, ValueOrRegister};
+use super::assembunny::{Computer, Word, MAX_INSTRUCTIONS};
+use crate::common::register_machine::{Machine, Stop};
 use crate::input::Input;
+use std::collections::HashSet;
+
+/// The largest initial value of register a to try.
+const MAX_INITIAL_VALUE: Word = 100_000;
 
 pub fn solve(input: &Input) -> Result<u32, String> {
-    let computer = Computer::parse(input.text)?;
-    if let Instruction::Copy(ValueOrRegister::Value(a), _register) = computer.instructions[1] {
-        if let Instruction::Copy(ValueOrRegister::Value(b), _register) = computer.instructions[2] {
-            let start_value = a * b;
-            for initial_value in 1..1000 {
-                let value = start_value + initial_value;
-                if format!("{value:b}").replace("10", "").is_empty() {
-                    return Ok(initial_value as u32);
+    let program = Computer::parse(input.text)?;
+    for initial_value in 1..=MAX_INITIAL_VALUE {
+        let mut computer = program.clone();
+        computer.registers[0] = initial_value;
+        Computer::optimize(&mut computer);
+        if produces_clock_signal(&mut computer)? {
+            return Ok(initial_value as u32);
+        }
+    }
+    Err("No initial value produces a clock signal".to_string())
+}
+
+/// If the program outputs 0, 1, 0, 1, ... forever, which is the case if it has output
+/// that so far and gets back to a state it has been in after outputting the same value.
+fn produces_clock_signal(computer: &mut Machine<Computer>) -> Result<bool, String> {
+    let mut seen_states = HashSet::new();
+    let mut expected = 0;
+    loop {
+        match computer.run(MAX_INSTRUCTIONS)? {
+            Stop::Output(value) if value == expected => {
+                let state = (
+                    value,
+                    computer.registers,
+                    computer.instruction_pointer,
+                    computer.instructions.clone(),
+                );
+                if !seen_states.insert(state) {
+                    return Ok(true);
                 }
+                expected = 1 - expected;
+            }
+            Stop::Output(_) | Stop::Halted => {
+                return Ok(false);
+            }
+            _ => {
+                return Err(format!("Aborted after {MAX_INSTRUCTIONS} instructions"));
             }
         }
     }
-
-    Err("Input does not match expectations".to_string())
 }
 
 #[test]
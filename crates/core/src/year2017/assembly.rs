@@ -1,9 +1,15 @@
-use std::collections::VecDeque;
+pub mod duet;
+pub mod optimizer;
+
+use std::fmt::{Display, Formatter};
+
+use crate::common::register_machine::{Effect, InstructionSet, Machine, NativeLoops};
+use optimizer::NativeLoop;
 
 pub type RegisterSpecifier = u8;
 pub type NumberValue = i64;
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq, Eq)]
 pub enum Value {
     Register(RegisterSpecifier),
     Number(NumberValue),
@@ -29,7 +35,16 @@ impl Value {
     }
 }
 
-#[derive(Copy, Clone)]
+impl Display for Value {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Self::Register(register) => write!(f, "{}", (b'a' + register) as char),
+            Self::Number(number) => write!(f, "{number}"),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
 pub enum Instruction {
     Snd(Value),
     Set(RegisterSpecifier, Value),
@@ -42,6 +57,23 @@ pub enum Instruction {
     Jnz(Value, Value),
 }
 
+impl Display for Instruction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let register = |x: RegisterSpecifier| Value::Register(x);
+        match *self {
+            Self::Snd(x) => write!(f, "snd {x}"),
+            Self::Set(x, y) => write!(f, "set {} {y}", register(x)),
+            Self::Add(x, y) => write!(f, "add {} {y}", register(x)),
+            Self::Sub(x, y) => write!(f, "sub {} {y}", register(x)),
+            Self::Mul(x, y) => write!(f, "mul {} {y}", register(x)),
+            Self::Mod(x, y) => write!(f, "mod {} {y}", register(x)),
+            Self::Rcv(x) => write!(f, "rcv {x}"),
+            Self::Jgz(x, y) => write!(f, "jgz {x} {y}"),
+            Self::Jnz(x, y) => write!(f, "jnz {x} {y}"),
+        }
+    }
+}
+
 impl Instruction {
     fn parse(input: &str) -> Option<Self> {
         let mut parts = input.split(' ');
@@ -88,23 +120,25 @@ impl Instruction {
     }
 }
 
-#[derive(Clone)]
-pub struct Program {
-    pub(crate) registers: Vec<NumberValue>,
-    instruction_pointer: u8,
-    pub(crate) terminated: bool,
-    pub(crate) last_played_frequency: NumberValue,
-    pub(crate) instructions: Vec<Instruction>,
-    executed_instructions_count: u32,
-    pub(crate) input_queue: VecDeque<NumberValue>,
-    pub(crate) sent_value_count: NumberValue,
-    pub(crate) mul_count: u32,
+/// The maximum number of instructions to execute before giving up on a program.
+pub const MAX_INSTRUCTIONS: u64 = 10_000_000;
+
+#[derive(Clone, Default)]
+pub struct State {
+    /// If `snd` and `rcv` send and receive values between programs, instead of playing and
+    /// recovering sounds.
+    pub duet: bool,
+    pub last_played_frequency: NumberValue,
+    pub mul_count: u64,
+    /// Set by [`Computer::optimize`].
+    loops: Option<NativeLoops<NativeLoop>>,
 }
 
-impl Program {
-    const MAX_INSTRUCTIONS: u32 = 100_000;
+#[derive(Clone)]
+pub struct Computer;
 
-    pub(crate) fn parse(input: &str) -> Result<Self, String> {
+impl Computer {
+    pub(crate) fn parse(input: &str) -> Result<Machine<Self>, String> {
         let instructions = input
             .lines()
             .enumerate()
@@ -113,96 +147,90 @@ impl Program {
                     .ok_or_else(|| format!("Line {}: Invalid instruction", line_idx + 1))
             })
             .collect::<Result<_, _>>()?;
-        Ok(Self {
-            registers: vec![0; 28],
-            instruction_pointer: 0,
-            terminated: false,
-            last_played_frequency: 0,
-            instructions,
-            executed_instructions_count: 0,
-            input_queue: VecDeque::new(),
-            sent_value_count: 0,
-            mul_count: 0,
-        })
+        Ok(Machine::new(instructions))
     }
 
-    fn value_of(&self, value: Value) -> NumberValue {
+    const fn value_of(registers: &[NumberValue; 26], value: Value) -> NumberValue {
         match value {
-            Value::Register(register_specifier) => self.registers[register_specifier as usize],
+            Value::Register(register_specifier) => registers[register_specifier as usize],
             Value::Number(number_value) => number_value,
         }
     }
+}
 
-    pub(crate) fn run_until_recover(
-        &mut self,
-        mut output_queue: Option<&mut VecDeque<NumberValue>>,
-    ) {
-        loop {
-            if self.executed_instructions_count >= Self::MAX_INSTRUCTIONS
-                || self.instruction_pointer as usize >= self.instructions.len()
-            {
-                self.terminated = true;
-                return;
-            }
+impl InstructionSet for Computer {
+    type Word = NumberValue;
+    type Registers = [NumberValue; 26];
+    type Instruction = Instruction;
+    type State = State;
+    const REGISTER_NAMES: &'static [&'static str] = &[
+        "a", "b", "c", "d", "e", "f", "g", "h", "i", "j", "k", "l", "m", "n", "o", "p", "q", "r",
+        "s", "t", "u", "v", "w", "x", "y", "z",
+    ];
 
-            self.executed_instructions_count += 1;
+    #[inline]
+    fn execute(
+        instruction: Instruction,
+        machine: &mut Machine<Self>,
+    ) -> Result<Effect<NumberValue>, String> {
+        if let Some(effect) = machine.run_native_loop(|machine| machine.state.loops.as_ref()) {
+            return Ok(effect);
+        }
 
-            match self.instructions[self.instruction_pointer as usize] {
-                Instruction::Snd(x) => {
-                    let x_value = self.value_of(x);
-                    if let Some(ref mut queue) = output_queue {
-                        self.sent_value_count += 1;
-                        queue.push_back(x_value);
-                    } else {
-                        self.last_played_frequency = x_value;
-                    }
-                }
-                Instruction::Set(x, y) => {
-                    self.registers[x as usize] = self.value_of(y);
-                }
-                Instruction::Add(x, y) => {
-                    self.registers[x as usize] += self.value_of(y);
-                }
-                Instruction::Sub(x, y) => {
-                    self.registers[x as usize] -= self.value_of(y);
-                }
-                Instruction::Mul(x, y) => {
-                    self.mul_count += 1;
-                    self.registers[x as usize] *= self.value_of(y);
+        let registers = &mut machine.registers;
+        match instruction {
+            Instruction::Snd(x) => {
+                let x_value = Self::value_of(registers, x);
+                if machine.state.duet {
+                    return Ok(Effect::Output(x_value));
                 }
-                Instruction::Mod(x, y) => {
-                    self.registers[x as usize] %= self.value_of(y);
+                machine.state.last_played_frequency = x_value;
+            }
+            Instruction::Set(x, y) => {
+                registers[x as usize] = Self::value_of(registers, y);
+            }
+            Instruction::Add(x, y) => {
+                registers[x as usize] += Self::value_of(registers, y);
+            }
+            Instruction::Sub(x, y) => {
+                registers[x as usize] -= Self::value_of(registers, y);
+            }
+            Instruction::Mul(x, y) => {
+                machine.state.mul_count += 1;
+                registers[x as usize] *= Self::value_of(registers, y);
+            }
+            Instruction::Mod(x, y) => {
+                let divisor = Self::value_of(registers, y);
+                if divisor == 0 {
+                    return Err("Modulo by zero".to_string());
                 }
-                Instruction::Rcv(x) => {
-                    if output_queue.is_none() {
-                        if self.value_of(x) != 0 {
-                            return;
-                        }
-                    } else if let Some(value) = self.input_queue.pop_front() {
-                        if let Value::Register(specifier) = x {
-                            self.registers[specifier as usize] = value;
-                        }
-                    } else {
-                        return;
+                registers[x as usize] %= divisor;
+            }
+            Instruction::Rcv(x) => {
+                if !machine.state.duet {
+                    // Recovering the last played sound stops the program:
+                    if Self::value_of(registers, x) != 0 {
+                        return Ok(Effect::Halt);
                     }
-                }
-                Instruction::Jgz(x, y) => {
-                    if self.value_of(x) > 0 {
-                        self.instruction_pointer =
-                            (NumberValue::from(self.instruction_pointer) + self.value_of(y)) as u8;
-                        continue;
+                } else if let Some(value) = machine.input.pop_front() {
+                    if let Value::Register(specifier) = x {
+                        registers[specifier as usize] = value;
                     }
+                } else {
+                    return Ok(Effect::NeedsInput);
                 }
-                Instruction::Jnz(x, y) => {
-                    if self.value_of(x) != 0 {
-                        self.instruction_pointer =
-                            (NumberValue::from(self.instruction_pointer) + self.value_of(y)) as u8;
-                        continue;
-                    }
+            }
+            Instruction::Jgz(x, y) => {
+                if Self::value_of(registers, x) > 0 {
+                    return Ok(Effect::Jump(Self::value_of(registers, y)));
+                }
+            }
+            Instruction::Jnz(x, y) => {
+                if Self::value_of(registers, x) != 0 {
+                    return Ok(Effect::Jump(Self::value_of(registers, y)));
                 }
             }
-
-            self.instruction_pointer += 1;
         }
+        Ok(Effect::Next)
     }
 }
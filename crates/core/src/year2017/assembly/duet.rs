@@ -0,0 +1,127 @@
+//! A scheduler running any number of duet programs, with each program sending values to
+//! the input queue of another.
+
+use super::{Computer, NumberValue};
+use crate::common::register_machine::{Machine, Stop};
+
+/// The register holding the ID of a program.
+const PROGRAM_ID_REGISTER: usize = (b'p' - b'a') as usize;
+
+/// How a duet ended.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Termination {
+    /// All programs halted.
+    Halted,
+    /// The programs, by index, waiting for values that will never be sent since all other
+    /// programs are waiting too or have halted.
+    Deadlock(Vec<usize>),
+}
+
+pub struct Scheduler {
+    pub programs: Vec<Machine<Computer>>,
+    /// The index of the program each program sends values to.
+    pub destinations: Vec<usize>,
+    /// The number of values sent by each program.
+    pub sent: Vec<u64>,
+    /// The number of values received by each program.
+    pub received: Vec<u64>,
+    /// The largest number of values waiting in the input queue of each program.
+    pub max_queued: Vec<usize>,
+}
+
+impl Scheduler {
+    /// Creates a scheduler for copies of a program connected in a ring, where each program
+    /// sends values to the next one and has its ID, counting from 0, in register p.
+    pub fn ring(program: &Machine<Computer>, count: usize) -> Self {
+        let programs = (0..count)
+            .map(|id| {
+                let mut program = program.clone();
+                program.state.duet = true;
+                program.registers[PROGRAM_ID_REGISTER] = id as NumberValue;
+                program
+            })
+            .collect();
+        Self {
+            programs,
+            destinations: (0..count).map(|id| (id + 1) % count).collect(),
+            sent: vec![0; count],
+            received: vec![0; count],
+            max_queued: vec![0; count],
+        }
+    }
+
+    /// Runs the programs in turn, each until it halts or waits for a value, until none of
+    /// them can continue. Fails if more than `max_instructions` instructions are executed
+    /// in total.
+    pub fn run(&mut self, max_instructions: u64) -> Result<Termination, String> {
+        let mut executed = 0;
+        loop {
+            let mut progressed = false;
+            for index in 0..self.programs.len() {
+                loop {
+                    let program = &mut self.programs[index];
+                    let (executed_before, queued_before) =
+                        (program.executed_instructions, program.input.len());
+                    let stop = program.run(max_instructions - executed)?;
+                    executed += program.executed_instructions - executed_before;
+                    progressed |= program.executed_instructions > executed_before;
+                    self.received[index] += (queued_before - program.input.len()) as u64;
+
+                    match stop {
+                        Stop::Output(value) => {
+                            self.sent[index] += 1;
+                            let destination = self.destinations[index];
+                            let queue = &mut self.programs[destination].input;
+                            queue.push_back(value);
+                            self.max_queued[destination] =
+                                self.max_queued[destination].max(queue.len());
+                        }
+                        Stop::Halted | Stop::NeedsInput => break,
+                        Stop::Loop(_) | Stop::Breakpoint(_) | Stop::BudgetExhausted => {
+                            return Err(format!("Aborted after {max_instructions} instructions"));
+                        }
+                    }
+                }
+            }
+
+            if !progressed {
+                let waiting = (0..self.programs.len())
+                    .filter(|&index| !self.programs[index].is_halted())
+                    .collect::<Vec<_>>();
+                return Ok(if waiting.is_empty() {
+                    Termination::Halted
+                } else {
+                    Termination::Deadlock(waiting)
+                });
+            }
+        }
+    }
+}
+
+#[test]
+#[allow(clippy::unwrap_used)]
+fn test() {
+    // Three programs passing their IDs on, and then waiting for another value:
+    let program = Computer::parse("snd p\nrcv a\nrcv b").unwrap();
+    let mut scheduler = Scheduler::ring(&program, 3);
+    assert_eq!(scheduler.run(100), Ok(Termination::Deadlock(vec![0, 1, 2])));
+    assert_eq!(scheduler.sent, [1, 1, 1]);
+    assert_eq!(scheduler.received, [1, 1, 1]);
+    assert_eq!(scheduler.max_queued, [1, 1, 1]);
+    assert_eq!(
+        scheduler
+            .programs
+            .iter()
+            .map(|p| p.registers[0])
+            .collect::<Vec<_>>(),
+        [2, 0, 1]
+    );
+
+    // The first program sending two values to the second, which halts after receiving them:
+    let program = Computer::parse("jgz p 4\nsnd 1\nsnd 2\njgz 1 3\nrcv a\nrcv a").unwrap();
+    let mut scheduler = Scheduler::ring(&program, 2);
+    assert_eq!(scheduler.run(100), Ok(Termination::Halted));
+    assert_eq!(scheduler.sent, [2, 0]);
+    assert_eq!(scheduler.received, [0, 2]);
+    assert_eq!(scheduler.max_queued, [0, 2]);
+}
@@ -0,0 +1,253 @@
+//! An optimizer replacing loops of programs searching for factorizations with native
+//! operations.
+//!
+//! A factor check loop counts a register `e` up to a number `b`, setting a flag `f` if
+//! `d * e` is the number, using `g` as scratch register:
+//!
+//! ```text
+//! set g d
+//! mul g e
+//! sub g b
+//! jnz g 2
+//! set f 0
+//! sub e -1
+//! set g e
+//! sub g b
+//! jnz g -8
+//! ```
+//!
+//! A factorization loop runs a factor check loop, starting `e` from a constant, for each
+//! `d` counting up to the number:
+//!
+//! ```text
+//! set e 2
+//! <factor check loop>
+//! sub d -1
+//! set g d
+//! sub g b
+//! jnz g -13
+//! ```
+//!
+//! A loop is only run natively while its counter is below the number, as it would
+//! otherwise count past the number without ever reaching it.
+
+use super::{Computer, Instruction, NumberValue, RegisterSpecifier, Value};
+use crate::common::register_machine::{Effect, Machine, NativeLoops, NativeOperation};
+
+/// The registers and constants of a factor check loop.
+#[derive(Copy, Clone)]
+pub struct FactorCheck {
+    factor: RegisterSpecifier,
+    counter: RegisterSpecifier,
+    number: RegisterSpecifier,
+    flag: RegisterSpecifier,
+    flag_value: NumberValue,
+    scratch: RegisterSpecifier,
+}
+
+#[derive(Copy, Clone)]
+pub enum NativeLoop {
+    FactorCheck(FactorCheck),
+    Factorization {
+        check: FactorCheck,
+        counter_start: NumberValue,
+    },
+}
+
+impl NativeLoop {
+    const fn length(self) -> i64 {
+        match self {
+            Self::FactorCheck(_) => 9,
+            Self::Factorization { .. } => 14,
+        }
+    }
+}
+
+impl NativeOperation<Computer> for NativeLoop {
+    fn execute(self, machine: &mut Machine<Computer>) -> Option<Effect<NumberValue>> {
+        let (registers, state) = (&mut machine.registers, &mut machine.state);
+        let (check, found, multiplications) = match self {
+            Self::FactorCheck(check) => {
+                let factor = registers[check.factor as usize];
+                let counter = registers[check.counter as usize];
+                let number = registers[check.number as usize];
+                if counter >= number {
+                    return None;
+                }
+                let found = if factor == 0 {
+                    number == 0
+                } else {
+                    number % factor == 0 && (counter..number).contains(&(number / factor))
+                };
+                (check, found, number - counter)
+            }
+            Self::Factorization {
+                check,
+                counter_start,
+            } => {
+                let factor_start = registers[check.factor as usize];
+                let number = registers[check.number as usize];
+                if factor_start <= 0
+                    || counter_start <= 0
+                    || number <= factor_start.max(counter_start)
+                {
+                    return None;
+                }
+                let found = (1..)
+                    .take_while(|&i| i <= number / i)
+                    .filter(|&i| number % i == 0)
+                    .flat_map(|i| [(i, number / i), (number / i, i)])
+                    .any(|(factor, counter)| {
+                        (factor_start..number).contains(&factor)
+                            && (counter_start..number).contains(&counter)
+                    });
+                let iterations = number - factor_start;
+                (
+                    check,
+                    found,
+                    iterations.checked_mul(number - counter_start)?,
+                )
+            }
+        };
+        let multiplications = u64::try_from(multiplications).ok()?;
+        state.mul_count = state.mul_count.checked_add(multiplications)?;
+
+        let number = registers[check.number as usize];
+        if found {
+            registers[check.flag as usize] = check.flag_value;
+        }
+        if matches!(self, Self::Factorization { .. }) {
+            registers[check.factor as usize] = number;
+        }
+        registers[check.counter as usize] = number;
+        registers[check.scratch as usize] = 0;
+        Some(Effect::Jump(self.length()))
+    }
+}
+
+impl Computer {
+    /// Makes the machine run factor check and factorization loops as native operations,
+    /// which add the multiplications they replace to `mul_count`.
+    pub fn optimize(machine: &mut Machine<Self>) {
+        machine.state.loops = Some(NativeLoops::find(
+            &machine.instructions,
+            |_, instructions| {
+                factorization_loop(instructions)
+                    .or_else(|| factor_check_loop(instructions).map(NativeLoop::FactorCheck))
+            },
+        ));
+    }
+}
+
+fn factor_check_loop(instructions: &[Instruction]) -> Option<FactorCheck> {
+    use Instruction::{Jnz, Mul, Set, Sub};
+    use Value::{Number, Register};
+
+    let [Set(scratch, Register(factor)), Mul(_, Register(counter)), Sub(_, Register(number)), ..] =
+        *instructions
+    else {
+        return None;
+    };
+    let Some(&Set(flag, Number(flag_value))) = instructions.get(4) else {
+        return None;
+    };
+    let expected = [
+        Set(scratch, Register(factor)),
+        Mul(scratch, Register(counter)),
+        Sub(scratch, Register(number)),
+        Jnz(Register(scratch), Number(2)),
+        Set(flag, Number(flag_value)),
+        Sub(counter, Number(-1)),
+        Set(scratch, Register(counter)),
+        Sub(scratch, Register(number)),
+        Jnz(Register(scratch), Number(-8)),
+    ];
+    let registers = [scratch, factor, counter, number, flag];
+    let distinct = (1..registers.len()).all(|i| !registers[..i].contains(&registers[i]));
+    (distinct && instructions.starts_with(&expected)).then_some(FactorCheck {
+        factor,
+        counter,
+        number,
+        flag,
+        flag_value,
+        scratch,
+    })
+}
+
+fn factorization_loop(instructions: &[Instruction]) -> Option<NativeLoop> {
+    use Instruction::{Jnz, Set, Sub};
+    use Value::{Number, Register};
+
+    let [Set(counter, Number(counter_start)), ..] = *instructions else {
+        return None;
+    };
+    let check = factor_check_loop(&instructions[1..])?;
+    let expected = [
+        Sub(check.factor, Number(-1)),
+        Set(check.scratch, Register(check.factor)),
+        Sub(check.scratch, Register(check.number)),
+        Jnz(Register(check.scratch), Number(-13)),
+    ];
+    (counter == check.counter && instructions[10..].starts_with(&expected)).then_some(
+        NativeLoop::Factorization {
+            check,
+            counter_start,
+        },
+    )
+}
+
+#[test]
+#[allow(clippy::unwrap_used)]
+fn test() {
+    // Clearing f if b is not prime, after (b - 2)² multiplications:
+    for (number, prime, mul_count) in [(15, false, 169), (13, true, 121)] {
+        let mut computer = Computer::parse(&format!(
+            "set b {number}
+set f 1
+set d 2
+set e 2
+set g d
+mul g e
+sub g b
+jnz g 2
+set f 0
+sub e -1
+set g e
+sub g b
+jnz g -8
+sub d -1
+set g d
+sub g b
+jnz g -13"
+        ))
+        .unwrap();
+        Computer::optimize(&mut computer);
+        computer.run_until_halt(100).unwrap();
+        assert_eq!(computer.executed_instructions, 4);
+        assert_eq!(computer.state.mul_count, mul_count);
+        assert_eq!(computer.registers[5], NumberValue::from(prime));
+    }
+
+    // A factor check loop alone, finding 3 * 5 = 15 with e counting from 2 to 15:
+    let mut computer = Computer::parse(
+        "set b 15
+set d 3
+set e 2
+set f 1
+set g d
+mul g e
+sub g b
+jnz g 2
+set f 0
+sub e -1
+set g e
+sub g b
+jnz g -8",
+    )
+    .unwrap();
+    Computer::optimize(&mut computer);
+    computer.run_until_halt(100).unwrap();
+    assert_eq!(computer.executed_instructions, 5);
+    assert_eq!(computer.state.mul_count, 13);
+    assert_eq!(computer.registers[..7], [0, 15, 0, 3, 15, 0, 0]);
+}
@@ -1,36 +1,21 @@
-use super::assembly::{NumberValue, Program};
+use super::assembly::duet::Scheduler;
+use super::assembly::{Computer, NumberValue, MAX_INSTRUCTIONS};
+use crate::common::register_machine::Stop;
 use crate::input::Input;
 
 pub fn solve(input: &Input) -> Result<NumberValue, String> {
-    let mut program_zero = Program::parse(input.text)?;
+    let mut program = Computer::parse(input.text)?;
     if input.is_part_one() {
-        program_zero.run_until_recover(None);
-        Ok(program_zero.last_played_frequency)
-    } else {
-        let mut program_one = program_zero.clone();
-
-        // "Each program also has its own program ID (one 0 and the other 1);
-        // the register p should begin with this value."
-        program_zero.registers[(b'p' - b'a') as usize] = 0;
-        program_one.registers[(b'p' - b'a') as usize] = 1;
-
-        loop {
-            if !program_zero.terminated {
-                program_zero.run_until_recover(Some(&mut program_one.input_queue));
-            }
-            if !program_one.terminated {
-                program_one.run_until_recover(Some(&mut program_zero.input_queue));
-            }
-
-            if (program_zero.terminated && program_one.terminated)
-                || (program_zero.input_queue.is_empty() && program_one.input_queue.is_empty())
-            {
-                // Both programs terminated, or both programs deadlocking.
-                break;
-            }
+        match program.run(MAX_INSTRUCTIONS)? {
+            Stop::BudgetExhausted => Err(format!("Aborted after {MAX_INSTRUCTIONS} instructions")),
+            _ => Ok(program.state.last_played_frequency),
         }
-
-        Ok(program_one.sent_value_count)
+    } else {
+        // The duet ends when both programs have terminated, or are deadlocked waiting for
+        // values from each other:
+        let mut scheduler = Scheduler::ring(&program, 2);
+        scheduler.run(MAX_INSTRUCTIONS)?;
+        Ok(scheduler.sent[1] as NumberValue)
     }
 }
 
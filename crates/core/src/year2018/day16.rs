@@ -1,12 +1,6 @@
-use super::elfcode::{Opcode, Registers};
+use super::elfcode::inference::{self, Sample};
+use super::elfcode::Registers;
 use crate::input::Input;
-use std::collections::HashSet;
-
-struct Sample {
-    registers_before: Registers,
-    instruction: [u16; 4],
-    registers_after: Registers,
-}
 
 struct ProblemInput {
     pub samples: Vec<Sample>,
@@ -66,15 +60,13 @@ impl ProblemInput {
                     for (i, &value) in parts.iter().enumerate() {
                         registers_after.values[i] = u64::from(value);
                     }
+                    let &[number, a, b, c] = &instruction[..] else {
+                        return Err(format!("Invalid input at line {}", line_index + 1));
+                    };
                     samples.push(Sample {
-                        registers_before,
-                        instruction: [
-                            instruction[0],
-                            instruction[1],
-                            instruction[2],
-                            instruction[3],
-                        ],
-                        registers_after,
+                        before: registers_before,
+                        instruction: [number, a, b, c].map(u64::from),
+                        after: registers_after,
                     });
                 }
             } else {
@@ -95,115 +87,24 @@ impl ProblemInput {
 pub fn solve(input: &Input) -> Result<u64, String> {
     let problem_input = ProblemInput::parse(input.text)?;
 
-    let all_opcodes = [
-        Opcode::Addr,
-        Opcode::Addi,
-        Opcode::Mulr,
-        Opcode::Muli,
-        Opcode::Banr,
-        Opcode::Bani,
-        Opcode::Borr,
-        Opcode::Bori,
-        Opcode::Setr,
-        Opcode::Seti,
-        Opcode::Gtir,
-        Opcode::Gtri,
-        Opcode::Gtrr,
-        Opcode::Eqir,
-        Opcode::Eqri,
-        Opcode::Eqrr,
-    ];
-
     if input.is_part_one() {
-        let mut result = 0;
-
-        for sample in problem_input.samples {
-            let mut sum = 0;
-            for opcode in all_opcodes.iter() {
-                let mut registers_applied = sample.registers_before;
-                registers_applied.apply(
-                    *opcode,
-                    u64::from(sample.instruction[1]),
-                    u64::from(sample.instruction[2]),
-                    u64::from(sample.instruction[3]),
-                );
-                if registers_applied == sample.registers_after {
-                    sum += 1;
-                }
-            }
-            if sum >= 3 {
-                result += 1;
-            }
-        }
-        Ok(result)
-    } else {
-        let mut possible_meanings: Vec<HashSet<Opcode>> = Vec::new();
-        for _ in 0..16 {
-            let s: HashSet<Opcode> = all_opcodes.iter().cloned().collect();
-            possible_meanings.push(s);
-        }
-
-        for sample in problem_input.samples {
-            let s: &mut HashSet<Opcode> = &mut possible_meanings[sample.instruction[0] as usize];
-
-            s.retain(|opcode| {
-                let mut registers_applied = sample.registers_before;
-                registers_applied.apply(
-                    *opcode,
-                    u64::from(sample.instruction[1]),
-                    u64::from(sample.instruction[2]),
-                    u64::from(sample.instruction[3]),
-                );
-                registers_applied == sample.registers_after
-            });
-        }
-
-        let mut assigned_opcodes = HashSet::new();
-        for s in possible_meanings.iter_mut() {
-            if s.len() == 1 {
-                assigned_opcodes
-                    .insert(*s.iter().next().ok_or("Internal error - no elements in s")?);
-            }
-        }
-
-        let mut new_assign = Vec::new();
-        while assigned_opcodes.len() != 16 {
-            for new in assigned_opcodes.iter() {
-                for s in possible_meanings.iter_mut() {
-                    if s.len() > 1 && s.remove(new) && s.len() == 1 {
-                        new_assign.push(
-                            *s.iter()
-                                .next()
-                                .ok_or("Internal error - no elements in s for new_assign")?,
-                        );
-                    }
-                }
-            }
-
-            for new in new_assign.iter() {
-                assigned_opcodes.insert(*new);
-            }
-        }
-
-        let meanings: Vec<Opcode> = possible_meanings
+        Ok(problem_input
+            .samples
             .iter()
-            .map(|s| {
-                s.iter()
-                    .next()
-                    .copied()
-                    .ok_or("Internal error - no elements in s for meanings")
-            })
-            .collect::<Result<_, _>>()?;
+            .filter(|sample| sample.matching_opcodes().nth(2).is_some())
+            .count() as u64)
+    } else {
+        let opcodes = inference::infer_opcodes(&problem_input.samples)?;
 
         let mut regs = Registers::new();
         for instruction in problem_input.program {
-            let opcode = meanings[instruction[0] as usize];
-            regs.apply(
-                opcode,
-                u64::from(instruction[1]),
-                u64::from(instruction[2]),
-                u64::from(instruction[3]),
-            );
+            let &[number, a, b, c] = &instruction[..] else {
+                return Err("Invalid input - program instructions must have four numbers".into());
+            };
+            let opcode = *opcodes
+                .get(usize::from(number))
+                .ok_or_else(|| format!("Invalid input - no opcode number {number}"))?;
+            regs.apply(opcode, u64::from(a), u64::from(b), u64::from(c));
         }
 
         Ok(regs.values[0])
@@ -1,42 +1,17 @@
-use super::elfcode::Program;
+use super::elfcode::Computer;
 use crate::input::Input;
 
-pub fn solve(input: &Input) -> Result<u64, String> {
-    let mut program = Program::parse(input.text)?;
-
-    if input.is_part_one() {
-        return program.execute_until_halt(10_000_000);
-    }
-
-    program.registers.values[0] = 1;
+const MAX_INSTRUCTIONS: u64 = 10_000_000;
 
-    program.optimize();
-
-    if program.instructions.len() < 3 {
-        return Err("Too few instructions".to_string());
-    }
-    let register = program.instructions[2].c as usize;
-    if register > 5 {
-        return Err("Register outside bounds".to_string());
-    }
-    while program.registers.values[register] == 0 {
-        program.execute_one_instruction()?;
-    }
-
-    let mut sum = 0;
-    // Assuming number to be factored is highest register value:
-    let &seed = program
-        .registers
-        .values
-        .iter()
-        .max()
-        .ok_or("Internal error: No registers")?;
-    for i in 1..=seed {
-        if seed % i == 0 {
-            sum += i;
-        }
-    }
-    Ok(sum)
+pub fn solve(input: &Input) -> Result<u64, String> {
+    let mut program = Computer::parse(input.text)?;
+    program.registers[0] = input.part_values(0, 1);
+
+    // The program sums the divisors of a number by trying every pair of factors, and the
+    // number is above ten million in part two, so the nested loops are run natively:
+    Computer::optimize(&mut program);
+    program.run_until_halt(MAX_INSTRUCTIONS)?;
+    Ok(program.registers[0])
 }
 
 #[test]
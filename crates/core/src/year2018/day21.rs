@@ -1,66 +1,82 @@
-use super::elfcode::Program;
+use super::elfcode::{Computer, Opcode};
+use crate::common::register_machine::{Machine, Stop};
 use crate::input::Input;
 use std::collections::HashSet;
 
+/// A parsed program, with the index of the instruction comparing register 0 with another
+/// register, and the index of that other register.
+struct Program {
+    machine: Machine<Computer>,
+    check_index: usize,
+    compared_register: usize,
+}
+
 fn parse(input_string: &str) -> Result<Program, String> {
-    let program = Program::parse(input_string)?;
-    if program.instructions.len() != 31 {
-        return Err("Expected 31 instructions in program".to_string());
+    let mut machine = Computer::parse(input_string)?;
+    if machine.instruction_pointer_register == Some(0) {
+        return Err("Register 0 is bound to the instruction pointer".to_string());
     }
-    Ok(program)
+
+    let mut reading_instructions = machine
+        .instructions
+        .iter()
+        .enumerate()
+        .filter(|(_, instruction)| instruction.reads_register(0));
+    let (check_index, check) = match (reading_instructions.next(), reading_instructions.next()) {
+        (Some(reading_instruction), None) => reading_instruction,
+        _ => return Err("Register 0 is not read by exactly one instruction".to_string()),
+    };
+    if check.opcode() != Opcode::Eqrr {
+        return Err("Register 0 is not compared for equality with a register".to_string());
+    }
+    let compared_register = if check.a == 0 { check.b } else { check.a } as usize;
+
+    // Replace the loop dividing by 256 with a native division:
+    Computer::optimize(&mut machine);
+    Ok(Program {
+        machine,
+        check_index,
+        compared_register,
+    })
 }
 
 const MAX_INSTRUCTIONS: u64 = 1_000_000;
 
 pub fn solve(input: &Input) -> Result<u64, String> {
-    let mut program = parse(input.text)?;
-    if input.is_part_one() {
-        // The last three instructions are (as seen with program.pretty_print()):
-        //
-        // 28: r4 = (r3 == r0) ? 1 : 0
-        // 29: goto r4 + 30
-        // 30: goto 6
-        //
-        // which exits on instruction 29 only if r4 is non-zero, which means r0 must equal r3.
-        //
-        // Since this is the only place in the program where register 0 is referenced, we can
-        // set register 0 to the value it's first compared with here to exit as soon as possible.
+    let Program {
+        machine: mut program,
+        check_index,
+        compared_register,
+    } = parse(input.text)?;
 
-        let mut loop_count = 0;
-        while program.instruction_pointer()? != 29 {
-            program.execute_one_instruction()?;
-
-            loop_count += 1;
-            if loop_count > MAX_INSTRUCTIONS {
-                return Err(format!("Aborted after {loop_count} instructions"));
+    // The program loops until the value it computes equals register 0, which is only read
+    // in that comparison (as seen with `advent-of-code elfcode decompile`):
+    //
+    //     r4 = r3 == r0 ? 1 : 0               // 28
+    // } while r3 != r0                        // 29
+    //
+    // In part one we can set register 0 to the first value it is compared with to exit as
+    // soon as possible. In part two the last value before the values start repeating
+    // makes the program run the longest before exiting.
+    let mut seen = HashSet::new();
+    let mut last_value = 0;
+    loop {
+        // The budget is shared by all values compared with, as they may never repeat:
+        let budget = MAX_INSTRUCTIONS.saturating_sub(program.executed_instructions);
+        match program.run_until_breakpoint(budget, check_index)? {
+            Stop::Breakpoint(_) => {}
+            Stop::Halted => {
+                return Err("Program halted without comparing with register 0".to_string());
             }
+            _ => return Err(format!("Aborted after {MAX_INSTRUCTIONS} instructions")),
         }
-        Ok(program.registers.values[program.instructions[28].a as usize])
-    } else {
-        let mut seen = HashSet::new();
-        let mut last_value = 0;
-        let mut loop_count = 0;
-        loop {
-            let ip = program.instruction_pointer()?;
-            if ip == 14 {
-                if program.registers.values[program.instructions[13].c as usize] == 0 {
-                    program.registers.values[program.instructions[6].c as usize] /= 256;
-                    program.registers.values[program.instruction_pointer_index as usize] = 8;
-                }
-            } else if ip == 29 {
-                let value = program.registers.values[program.instructions[28].a as usize];
-                if seen.insert(value) {
-                    last_value = value;
-                } else {
-                    return Ok(last_value);
-                }
-            }
-            program.execute_one_instruction()?;
-
-            loop_count += 1;
-            if loop_count > MAX_INSTRUCTIONS {
-                return Err(format!("Aborted after {loop_count} instructions"));
-            }
+        let value = program.registers[compared_register];
+        if input.is_part_one() {
+            return Ok(value);
+        } else if seen.insert(value) {
+            last_value = value;
+        } else {
+            return Ok(last_value);
         }
     }
 }
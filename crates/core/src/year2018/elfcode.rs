@@ -1,4 +1,14 @@
-#[derive(Copy, Clone, PartialEq, Eq)]
+pub mod decompiler;
+pub mod inference;
+pub mod optimizer;
+
+use std::fmt::{Display, Formatter};
+use std::ops::{Index, IndexMut};
+
+use crate::common::register_machine::{Effect, InstructionSet, Machine, NativeLoops};
+use optimizer::NativeLoop;
+
+#[derive(Copy, Clone, Default, PartialEq, Eq)]
 pub struct Registers {
     pub values: [u64; 6],
 }
@@ -11,49 +21,37 @@ pub struct Instruction {
     pub c: u64,
 }
 
-pub struct Program {
-    pub instruction_pointer_index: u8,
-    pub instructions: Vec<Instruction>,
-    pub registers: Registers,
-}
-
-impl Program {
-    pub fn instruction_pointer(&self) -> Result<u64, String> {
-        self.registers
-            .values
-            .get(self.instruction_pointer_index as usize)
-            .copied()
-            .ok_or_else(|| "Invalid instruction pointer".to_string())
+impl Instruction {
+    pub const fn opcode(&self) -> Opcode {
+        self.opcode
     }
 
-    pub fn execute_one_instruction(&mut self) -> Result<bool, String> {
-        let ip = self.instruction_pointer()?;
-        if ip as usize >= self.instructions.len() {
-            return Ok(false);
-        }
-        let instruction = self.instructions[ip as usize];
-        self.registers.apply(
-            instruction.opcode,
-            instruction.a,
-            instruction.b,
-            instruction.c,
-        );
-        self.registers.values[self.instruction_pointer_index as usize] += 1;
-        Ok(true)
+    /// If the instruction reads the given register.
+    pub const fn reads_register(&self, register: u64) -> bool {
+        let (a_is_register, b_is_register) = self.opcode.register_operands();
+        (a_is_register && self.a == register) || (b_is_register && self.b == register)
     }
+}
 
-    pub fn execute_until_halt(&mut self, max_instructions: u32) -> Result<u64, String> {
-        let mut loop_count = 0;
-        while self.execute_one_instruction()? {
-            loop_count += 1;
-            if loop_count > max_instructions {
-                return Err(format!("Aborting after {max_instructions} instructions"));
-            }
-        }
-        Ok(self.registers.values[0])
+impl Display for Instruction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} {} {}", self.opcode.name(), self.a, self.b, self.c)
     }
+}
 
-    pub fn parse(input_string: &str) -> Result<Self, String> {
+/// The elfcode instruction set, with six registers and an instruction pointer bound to one
+/// of them.
+#[derive(Clone)]
+pub struct Computer;
+
+#[derive(Clone, Default)]
+pub struct State {
+    /// Set by [`Computer::optimize`].
+    loops: Option<NativeLoops<NativeLoop>>,
+}
+
+impl Computer {
+    pub fn parse(input_string: &str) -> Result<Machine<Self>, String> {
         let mut lines = input_string.lines();
         let first_line = lines.next().ok_or("Empty input")?;
 
@@ -61,7 +59,10 @@ impl Program {
             return Err("Invalid first line of elfcode".to_string());
         }
         let error = |_| "Invalid elfcode instruction";
-        let instruction_pointer_index = (first_line[4..]).parse::<u8>().map_err(error)?;
+        let instruction_pointer_index = (first_line[4..]).parse::<usize>().map_err(error)?;
+        if instruction_pointer_index >= Self::REGISTER_NAMES.len() {
+            return Err("Invalid instruction pointer".to_string());
+        }
 
         let mut instructions = Vec::new();
         for line in lines {
@@ -73,45 +74,40 @@ impl Program {
             let a = parts[1].parse::<u64>().map_err(error)?;
             let b = parts[2].parse::<u64>().map_err(error)?;
             let c = parts[3].parse::<u64>().map_err(error)?;
+            if c >= 6 {
+                return Err("Invalid elfcode - writing to non-existing register".into());
+            }
             instructions.push(Instruction { opcode, a, b, c });
         }
 
-        Ok(Self {
-            instruction_pointer_index,
-            instructions,
-            registers: Registers::new(),
-        })
+        let mut machine = Machine::new(instructions);
+        machine.instruction_pointer_register = Some(instruction_pointer_index);
+        Ok(machine)
     }
+}
 
-    pub fn optimize(&mut self) {
-        for (line, instruction) in self.instructions.iter_mut().enumerate() {
-            match instruction.opcode {
-                Opcode::Addi => {
-                    if instruction.a as u8 == self.instruction_pointer_index {
-                        instruction.opcode = Opcode::Seti;
-                        instruction.a = line as u64 + instruction.b;
-                        instruction.b = 0; // ignored
-                    }
-                }
-                Opcode::Mulr => {
-                    if instruction.a as u8 == self.instruction_pointer_index
-                        && instruction.b as u8 == self.instruction_pointer_index
-                    {
-                        instruction.opcode = Opcode::Seti;
-                        instruction.a = line as u64 * line as u64;
-                        instruction.b = 0; // ignored
-                    }
-                }
-                Opcode::Muli => {
-                    if instruction.a as u8 == self.instruction_pointer_index {
-                        instruction.opcode = Opcode::Seti;
-                        instruction.a = line as u64 * instruction.b;
-                        instruction.b = 0; // ignored
-                    }
-                }
-                _ => {}
-            }
+impl InstructionSet for Computer {
+    type Word = u64;
+    type Registers = Registers;
+    type Instruction = Instruction;
+    type State = State;
+    const REGISTER_NAMES: &'static [&'static str] = &["r0", "r1", "r2", "r3", "r4", "r5"];
+
+    #[inline]
+    fn execute(
+        instruction: Instruction,
+        machine: &mut Machine<Self>,
+    ) -> Result<Effect<u64>, String> {
+        if let Some(effect) = machine.run_native_loop(|machine| machine.state.loops.as_ref()) {
+            return Ok(effect);
         }
+        machine.registers.apply(
+            instruction.opcode,
+            instruction.a,
+            instruction.b,
+            instruction.c,
+        );
+        Ok(Effect::Next)
     }
 }
 
@@ -135,6 +131,63 @@ pub enum Opcode {
     Eqrr, // (equal register/register) sets register C to 1 if register A is equal to register B. Otherwise, register C is set to 0.
 }
 
+impl Opcode {
+    pub const ALL: [Self; 16] = [
+        Self::Addr,
+        Self::Addi,
+        Self::Mulr,
+        Self::Muli,
+        Self::Banr,
+        Self::Bani,
+        Self::Borr,
+        Self::Bori,
+        Self::Setr,
+        Self::Seti,
+        Self::Gtir,
+        Self::Gtri,
+        Self::Gtrr,
+        Self::Eqir,
+        Self::Eqri,
+        Self::Eqrr,
+    ];
+
+    /// If the A and B operands are registers rather than values.
+    const fn register_operands(self) -> (bool, bool) {
+        match self {
+            Self::Addr | Self::Mulr | Self::Banr | Self::Borr | Self::Gtrr | Self::Eqrr => {
+                (true, true)
+            }
+            Self::Addi | Self::Muli | Self::Bani | Self::Bori | Self::Gtri | Self::Eqri => {
+                (true, false)
+            }
+            Self::Setr => (true, false),
+            Self::Seti => (false, false),
+            Self::Gtir | Self::Eqir => (false, true),
+        }
+    }
+
+    const fn name(self) -> &'static str {
+        match self {
+            Self::Addr => "addr",
+            Self::Addi => "addi",
+            Self::Mulr => "mulr",
+            Self::Muli => "muli",
+            Self::Banr => "banr",
+            Self::Bani => "bani",
+            Self::Borr => "borr",
+            Self::Bori => "bori",
+            Self::Setr => "setr",
+            Self::Seti => "seti",
+            Self::Gtir => "gtir",
+            Self::Gtri => "gtri",
+            Self::Gtrr => "gtrr",
+            Self::Eqir => "eqir",
+            Self::Eqri => "eqri",
+            Self::Eqrr => "eqrr",
+        }
+    }
+}
+
 fn opcode_from_str(name: &str) -> Result<Opcode, String> {
     Ok(match name {
         "addr" => Opcode::Addr,
@@ -192,3 +245,29 @@ impl Registers {
         }
     }
 }
+
+impl Index<usize> for Registers {
+    type Output = u64;
+
+    fn index(&self, index: usize) -> &u64 {
+        &self.values[index]
+    }
+}
+
+impl IndexMut<usize> for Registers {
+    fn index_mut(&mut self, index: usize) -> &mut u64 {
+        &mut self.values[index]
+    }
+}
+
+impl AsRef<[u64]> for Registers {
+    fn as_ref(&self) -> &[u64] {
+        &self.values
+    }
+}
+
+impl AsMut<[u64]> for Registers {
+    fn as_mut(&mut self) -> &mut [u64] {
+        &mut self.values
+    }
+}
@@ -0,0 +1,615 @@
+//! A decompiler turning elfcode programs into structured pseudo-code.
+//!
+//! Reads of the register bound to the instruction pointer are replaced by the index of the
+//! instruction, and writes to it become jumps. A comparison followed by a jump over the
+//! next instruction, which is how elfcode branches, becomes a conditional jump, with the
+//! comparison left out if its register is only used for branching.
+//!
+//! Jumps are then turned into `if`, `if`-`else`, `loop` and `do`-`while` blocks when the
+//! code they span is only entered from its start, while other jumps are kept as `goto`.
+
+use std::collections::BTreeSet;
+use std::fmt::{Display, Formatter, Write};
+
+use super::{Computer, Instruction, Opcode};
+use crate::common::register_machine::Machine;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Operand {
+    Register(u64),
+    Value(u64),
+}
+
+impl Display for Operand {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Register(register) => write!(f, "r{register}"),
+            Self::Value(value) => write!(f, "{value}"),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Expression {
+    left: Operand,
+    operation: Option<(&'static str, Operand)>,
+}
+
+impl Expression {
+    fn is_comparison(&self) -> bool {
+        matches!(self.operation, Some((">" | "==", _)))
+    }
+
+    fn constant(&self) -> Option<u64> {
+        let Operand::Value(left) = self.left else {
+            return None;
+        };
+        Some(match self.operation {
+            None => left,
+            Some((operator, Operand::Value(right))) => match operator {
+                "+" => left.wrapping_add(right),
+                "*" => left.wrapping_mul(right),
+                "&" => left & right,
+                "|" => left | right,
+                ">" => u64::from(left > right),
+                _ => u64::from(left == right),
+            },
+            Some((_, Operand::Register(_))) => return None,
+        })
+    }
+
+    fn reads(&self, register: u64) -> bool {
+        self.left == Operand::Register(register)
+            || matches!(self.operation, Some((_, right)) if right == Operand::Register(register))
+    }
+}
+
+impl Display for Expression {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if let Some(constant) = self.constant() {
+            return write!(f, "{constant}");
+        }
+        match self.operation {
+            None => write!(f, "{}", self.left),
+            Some((operator, right)) => write!(f, "{} {operator} {right}", self.left),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Condition {
+    left: Operand,
+    operator: &'static str,
+    right: Operand,
+}
+
+impl Condition {
+    fn negate(self) -> Self {
+        let operator = match self.operator {
+            ">" => "<=",
+            "<=" => ">",
+            "==" => "!=",
+            _ => "==",
+        };
+        Self { operator, ..self }
+    }
+}
+
+impl Display for Condition {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} {}", self.left, self.operator, self.right)
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Node {
+    Assign {
+        register: u64,
+        expression: Expression,
+    },
+    /// A jump to an instruction, or a halt if outside of the program.
+    Goto(usize),
+    ConditionalGoto {
+        condition: Condition,
+        target: usize,
+    },
+    /// A jump to the instruction following the value of an expression.
+    IndirectGoto(Expression),
+    /// A jump over the next instruction if a register is one, as done by `addr x ip ip`.
+    SkipIf(u64),
+}
+
+/// A structured block of code, referring to nodes by their instruction index.
+enum Block {
+    Node(usize),
+    /// A loop ending with an unconditional or conditional jump back to its start.
+    Loop {
+        back_edge: usize,
+        body: Vec<Block>,
+    },
+    /// An if statement, entered if the condition of the branch is false.
+    If {
+        branch: usize,
+        then: Vec<Block>,
+        otherwise: Option<Vec<Block>>,
+    },
+}
+
+struct Decompiler {
+    /// The nodes by instruction index, with `None` for instructions merged into others.
+    nodes: Vec<Option<Node>>,
+    /// Jumps inside the program, as source and target instruction indices.
+    jumps: Vec<(usize, usize)>,
+    /// Jumps made into blocks, by their source instruction index.
+    structured_jumps: BTreeSet<usize>,
+}
+
+/// Decompiles an elfcode program into pseudo-code, with each statement commented with the
+/// index of the instruction it starts at.
+pub fn decompile(program: &Machine<Computer>) -> String {
+    let mut decompiler = Decompiler::new(program);
+    let blocks = decompiler.structure(0, decompiler.nodes.len());
+    let labels = decompiler
+        .jumps
+        .iter()
+        .filter(|(from, _)| !decompiler.structured_jumps.contains(from))
+        .map(|&(_, to)| to)
+        .collect::<BTreeSet<_>>();
+
+    let mut result = String::new();
+    decompiler.write_blocks(&blocks, &labels, 0, &mut result);
+    result
+}
+
+impl Decompiler {
+    fn new(program: &Machine<Computer>) -> Self {
+        let len = program.instructions.len();
+        let instruction_pointer = program.instruction_pointer_register.map(|r| r as u64);
+        let mut nodes = program
+            .instructions
+            .iter()
+            .enumerate()
+            .map(|(index, instruction)| Some(lift(index, instruction, instruction_pointer, len)))
+            .collect::<Vec<_>>();
+
+        // A comparison directly followed by a skip using its result, which is only reached
+        // through the comparison, gives the condition of the skip:
+        let jump_targets = nodes
+            .iter()
+            .filter_map(|node| match node {
+                Some(Node::Goto(target)) => Some(*target),
+                _ => None,
+            })
+            .collect::<BTreeSet<_>>();
+        let skip_condition = |nodes: &[Option<Node>], index: usize| {
+            let Some(Node::SkipIf(flag)) = nodes[index] else {
+                return None;
+            };
+            match nodes[index.checked_sub(1)?] {
+                Some(Node::Assign {
+                    register,
+                    expression:
+                        Expression {
+                            left,
+                            operation: Some((operator, right)),
+                        },
+                }) if register == flag
+                    && matches!(operator, ">" | "==")
+                    && !jump_targets.contains(&index) =>
+                {
+                    // The comparison may overwrite one of its operands, leaving its result:
+                    let flag = Operand::Register(flag);
+                    Some(if left == flag || right == flag {
+                        Condition {
+                            left: flag,
+                            operator: "==",
+                            right: Operand::Value(1),
+                        }
+                    } else {
+                        Condition {
+                            left,
+                            operator,
+                            right,
+                        }
+                    })
+                }
+                _ => None,
+            }
+        };
+
+        // Registers only read by skips with a known condition hold no other values, so the
+        // comparisons writing them can be left out:
+        let flag_registers = (0..6)
+            .filter(|&register| {
+                nodes.iter().enumerate().all(|(index, node)| match node {
+                    Some(Node::Assign { expression, .. } | Node::IndirectGoto(expression)) => {
+                        !expression.reads(register)
+                    }
+                    Some(Node::SkipIf(flag)) if *flag == register => {
+                        skip_condition(&nodes, index).is_some()
+                    }
+                    _ => true,
+                })
+            })
+            .collect::<BTreeSet<_>>();
+
+        for index in 0..len {
+            let Some(Node::SkipIf(flag)) = nodes[index] else {
+                continue;
+            };
+            let Some(condition) = skip_condition(&nodes, index) else {
+                nodes[index] = Some(Node::IndirectGoto(Expression {
+                    left: Operand::Value(index as u64),
+                    operation: Some(("+", Operand::Register(flag))),
+                }));
+                continue;
+            };
+            // Skipping over a jump is a jump if the condition is false:
+            let (condition, target) = match nodes.get(index + 1) {
+                Some(&Some(Node::Goto(target))) if !jump_targets.contains(&(index + 1)) => {
+                    nodes[index + 1] = None;
+                    (condition.negate(), target)
+                }
+                _ => (condition, (index + 2).min(len)),
+            };
+            let start = if flag_registers.contains(&flag) {
+                nodes[index] = None;
+                index - 1
+            } else {
+                index
+            };
+            nodes[start] = Some(Node::ConditionalGoto { condition, target });
+        }
+
+        let jumps = nodes
+            .iter()
+            .enumerate()
+            .filter_map(|(index, node)| match node {
+                Some(Node::Goto(target) | Node::ConditionalGoto { target, .. })
+                    if *target < len =>
+                {
+                    Some((index, *target))
+                }
+                _ => None,
+            })
+            .collect();
+        Self {
+            nodes,
+            jumps,
+            structured_jumps: BTreeSet::new(),
+        }
+    }
+
+    /// If code in `start..end` is entered by a jump from outside of `region`.
+    fn is_entered_from_outside(
+        &self,
+        start: usize,
+        end: usize,
+        region: std::ops::Range<usize>,
+    ) -> bool {
+        self.jumps
+            .iter()
+            .any(|(from, to)| (start..end).contains(to) && !region.contains(from))
+    }
+
+    fn structure(&mut self, start: usize, end: usize) -> Vec<Block> {
+        let mut blocks = Vec::new();
+        let mut index = start;
+        while index < end {
+            let Some(node) = self.nodes[index] else {
+                index += 1;
+                continue;
+            };
+
+            // The furthest jump back to this instruction closes a loop:
+            let back_edge = self
+                .jumps
+                .iter()
+                .filter(|&&(from, to)| to == index && (index..end).contains(&from))
+                .map(|&(from, _)| from)
+                .filter(|from| !self.structured_jumps.contains(from))
+                .max();
+            if let Some(back_edge) = back_edge {
+                if !self.is_entered_from_outside(index + 1, back_edge + 1, index..back_edge + 1) {
+                    self.structured_jumps.insert(back_edge);
+                    let body = self.structure(index, back_edge);
+                    blocks.push(Block::Loop { back_edge, body });
+                    index = back_edge + 1;
+                    continue;
+                }
+            }
+
+            if let Node::ConditionalGoto { target, .. } = node {
+                if index < target
+                    && target <= end
+                    && target < self.nodes.len()
+                    && !self.is_entered_from_outside(index + 1, target, index..target)
+                {
+                    self.structured_jumps.insert(index);
+                    // A jump at the end of the then block over following code makes that
+                    // code an else block, if only entered by the branch:
+                    let last = (index + 1..target).rev().find(|&i| self.nodes[i].is_some());
+                    if let Some((last, else_end)) = last.and_then(|last| match self.nodes[last] {
+                        Some(Node::Goto(else_end))
+                            if target < else_end
+                                && else_end <= end
+                                && else_end < self.nodes.len()
+                                && self.jumps.iter().all(|&(from, to)| {
+                                    !(target..else_end).contains(&to)
+                                        || (target..else_end).contains(&from)
+                                        || (from, to) == (index, target)
+                                }) =>
+                        {
+                            Some((last, else_end))
+                        }
+                        _ => None,
+                    }) {
+                        self.structured_jumps.insert(last);
+                        let then = self.structure(index + 1, last);
+                        let otherwise = self.structure(target, else_end);
+                        blocks.push(Block::If {
+                            branch: index,
+                            then,
+                            otherwise: Some(otherwise),
+                        });
+                        index = else_end;
+                    } else {
+                        let then = self.structure(index + 1, target);
+                        blocks.push(Block::If {
+                            branch: index,
+                            then,
+                            otherwise: None,
+                        });
+                        index = target;
+                    }
+                    continue;
+                }
+            }
+
+            blocks.push(Block::Node(index));
+            index += 1;
+        }
+        blocks
+    }
+
+    fn write_blocks(
+        &self,
+        blocks: &[Block],
+        labels: &BTreeSet<usize>,
+        depth: usize,
+        result: &mut String,
+    ) {
+        let indent = "    ".repeat(depth);
+        for block in blocks {
+            match block {
+                Block::Node(index) => {
+                    if labels.contains(index) {
+                        write_line(result, &format!("{indent}L{index}:"), None);
+                    }
+                    let statement = format!("{indent}{}", self.statement(*index));
+                    write_line(result, &statement, Some(*index));
+                }
+                Block::Loop { back_edge, body } => {
+                    let condition = match self.nodes[*back_edge] {
+                        Some(Node::ConditionalGoto { condition, .. }) => Some(condition),
+                        _ => None,
+                    };
+                    let start = if condition.is_some() {
+                        "do {"
+                    } else {
+                        "loop {"
+                    };
+                    write_line(result, &format!("{indent}{start}"), None);
+                    self.write_blocks(body, labels, depth + 1, result);
+                    let end = condition.map_or_else(String::new, |c| format!(" while {c}"));
+                    write_line(result, &format!("{indent}}}{end}"), Some(*back_edge));
+                }
+                Block::If {
+                    branch,
+                    then,
+                    otherwise,
+                } => {
+                    if labels.contains(branch) {
+                        write_line(result, &format!("{indent}L{branch}:"), None);
+                    }
+                    if let Some(Node::ConditionalGoto { condition, .. }) = self.nodes[*branch] {
+                        let line = format!("{indent}if {} {{", condition.negate());
+                        write_line(result, &line, Some(*branch));
+                    }
+                    self.write_blocks(then, labels, depth + 1, result);
+                    if let Some(otherwise) = otherwise {
+                        write_line(result, &format!("{indent}}} else {{"), None);
+                        self.write_blocks(otherwise, labels, depth + 1, result);
+                    }
+                    write_line(result, &format!("{indent}}}"), None);
+                }
+            }
+        }
+    }
+
+    fn statement(&self, index: usize) -> String {
+        let len = self.nodes.len();
+        let goto = |target: usize| {
+            if target >= len {
+                "halt".to_string()
+            } else {
+                format!("goto L{target}")
+            }
+        };
+        match self.nodes[index] {
+            Some(Node::Assign {
+                register,
+                expression,
+            }) => assignment(register, expression),
+            Some(Node::Goto(target)) => goto(target),
+            Some(Node::ConditionalGoto { condition, target }) => {
+                format!("if {condition} {}", goto(target))
+            }
+            Some(Node::IndirectGoto(expression)) => match expression.operation {
+                // Fold the increment of the instruction pointer into a constant:
+                Some(("+", right)) if expression.constant().is_none() => match expression.left {
+                    Operand::Value(value) => format!("goto {right} + {}", value + 1),
+                    left @ Operand::Register(_) => match right {
+                        Operand::Value(value) => format!("goto {left} + {}", value + 1),
+                        Operand::Register(_) => format!("goto {expression} + 1"),
+                    },
+                },
+                _ => format!("goto {expression} + 1"),
+            },
+            Some(Node::SkipIf(_)) | None => String::new(),
+        }
+    }
+}
+
+/// Writes a line, commented with the index of the instruction it is for if any.
+fn write_line(result: &mut String, line: &str, index: Option<usize>) {
+    let _ = match index {
+        Some(index) => writeln!(result, "{line:<40}// {index}"),
+        None => writeln!(result, "{line}"),
+    };
+}
+
+fn assignment(register: u64, expression: Expression) -> String {
+    let target = Operand::Register(register);
+    if expression.constant().is_none() {
+        if let Some((operator, right)) = expression.operation {
+            if expression.is_comparison() {
+                return format!("{target} = {expression} ? 1 : 0");
+            } else if expression.left == target {
+                return format!("{target} {operator}= {right}");
+            } else if right == target {
+                return format!("{target} {operator}= {}", expression.left);
+            }
+        }
+    }
+    format!("{target} = {expression}")
+}
+
+/// Lifts an instruction into a node, with reads of the instruction pointer register
+/// replaced by the instruction index and writes to it turned into jumps.
+fn lift(
+    index: usize,
+    instruction: &Instruction,
+    instruction_pointer: Option<u64>,
+    len: usize,
+) -> Node {
+    let register = |register: u64| {
+        if Some(register) == instruction_pointer {
+            Operand::Value(index as u64)
+        } else {
+            Operand::Register(register)
+        }
+    };
+    let (a, b) = (instruction.a, instruction.b);
+    let binary = |left, operator, right| Expression {
+        left,
+        operation: Some((operator, right)),
+    };
+    let expression = match instruction.opcode {
+        Opcode::Addr => binary(register(a), "+", register(b)),
+        Opcode::Addi => binary(register(a), "+", Operand::Value(b)),
+        Opcode::Mulr => binary(register(a), "*", register(b)),
+        Opcode::Muli => binary(register(a), "*", Operand::Value(b)),
+        Opcode::Banr => binary(register(a), "&", register(b)),
+        Opcode::Bani => binary(register(a), "&", Operand::Value(b)),
+        Opcode::Borr => binary(register(a), "|", register(b)),
+        Opcode::Bori => binary(register(a), "|", Operand::Value(b)),
+        Opcode::Setr => Expression {
+            left: register(a),
+            operation: None,
+        },
+        Opcode::Seti => Expression {
+            left: Operand::Value(a),
+            operation: None,
+        },
+        Opcode::Gtir => binary(Operand::Value(a), ">", register(b)),
+        Opcode::Gtri => binary(register(a), ">", Operand::Value(b)),
+        Opcode::Gtrr => binary(register(a), ">", register(b)),
+        Opcode::Eqir => binary(Operand::Value(a), "==", register(b)),
+        Opcode::Eqri => binary(register(a), "==", Operand::Value(b)),
+        Opcode::Eqrr => binary(register(a), "==", register(b)),
+    };
+
+    if Some(instruction.c) != instruction_pointer {
+        return Node::Assign {
+            register: instruction.c,
+            expression,
+        };
+    }
+    if let Some(value) = expression.constant() {
+        return Node::Goto(value.saturating_add(1).min(len as u64) as usize);
+    }
+    match (instruction.opcode, expression.left, expression.operation) {
+        (Opcode::Addr, Operand::Value(_), Some(("+", Operand::Register(flag))))
+            if Some(a) == instruction_pointer =>
+        {
+            Node::SkipIf(flag)
+        }
+        (Opcode::Addr, Operand::Register(flag), Some(("+", Operand::Value(_))))
+            if Some(b) == instruction_pointer =>
+        {
+            Node::SkipIf(flag)
+        }
+        _ => Node::IndirectGoto(expression),
+    }
+}
+
+#[test]
+#[allow(clippy::unwrap_used)]
+fn test() {
+    // A do-while loop counting to 10, an if statement and a halt:
+    let program = Computer::parse(
+        "#ip 5
+seti 0 0 1
+addi 1 1 1
+gtri 1 9 2
+addr 2 5 5
+seti 0 0 5
+eqri 1 10 3
+addr 3 5 5
+addi 4 1 4
+seti 99 0 5",
+    )
+    .unwrap();
+    assert_eq!(
+        decompile(&program),
+        "r1 = 0                                  // 0
+do {
+    r1 += 1                             // 1
+} while r1 <= 9                         // 2
+if r1 != 10 {                           // 5
+    r4 += 1                             // 7
+}
+halt                                    // 8
+"
+    );
+
+    // An infinite loop with an if-else statement and an indirect jump:
+    let program = Computer::parse(
+        "#ip 4
+addr 4 0 4
+gtrr 1 2 3
+addr 4 3 4
+seti 5 0 4
+addi 1 1 1
+seti 6 0 4
+addi 2 1 2
+mulr 3 3 0
+seti 0 0 4",
+    )
+    .unwrap();
+    assert_eq!(
+        decompile(&program),
+        "goto r0 + 1                             // 0
+loop {
+    r3 = r1 > r2 ? 1 : 0                // 1
+    if r1 > r2 {                        // 2
+        r1 += 1                         // 4
+    } else {
+        r2 += 1                         // 6
+    }
+    r0 = r3 * r3                        // 7
+}                                       // 8
+"
+    );
+}
@@ -0,0 +1,395 @@
+//! Inference of which opcode each opcode number means, from samples of instructions with the
+//! registers before and after executing them.
+//!
+//! This is a constraint problem: each of the 16 opcode numbers means a distinct opcode, which
+//! must behave like every sample with that number. The candidate opcodes of each number are
+//! first narrowed down by the samples, then by propagation - a number with a single candidate
+//! left rules it out for the others, and an opcode left as the candidate of a single number
+//! is assigned to it. The remaining assignments are enumerated by backtracking over the
+//! matchings between opcode numbers and opcodes.
+//!
+//! When no assignment is consistent with the samples, the samples in conflict are found from
+//! a maximum matching: the numbers it leaves unmatched, and those reachable from them through
+//! alternating paths, have fewer candidate opcodes between them than there are numbers.
+
+use super::{Opcode, Registers};
+
+pub const OPCODE_NUMBERS: usize = Opcode::ALL.len();
+
+/// A set of opcodes, as a bit set indexed like [`Opcode::ALL`].
+type Candidates = u16;
+
+const ALL_CANDIDATES: Candidates = Candidates::MAX;
+
+/// An instruction executed on the registers before it, leaving them as the registers after.
+pub struct Sample {
+    pub before: Registers,
+    /// The opcode number, followed by the A, B and C operands.
+    pub instruction: [u64; 4],
+    pub after: Registers,
+}
+
+impl Sample {
+    fn behaves_like(&self, opcode: Opcode) -> bool {
+        let [_, a, b, c] = self.instruction;
+        let registers = self.before.values.len() as u64;
+        let (a_is_register, b_is_register) = opcode.register_operands();
+        if (a_is_register && a >= registers) || (b_is_register && b >= registers) || c >= registers
+        {
+            return false;
+        }
+        let mut registers = self.before;
+        registers.apply(opcode, a, b, c);
+        registers == self.after
+    }
+
+    /// The opcodes which behave like the sample.
+    pub fn matching_opcodes(&self) -> impl Iterator<Item = Opcode> + '_ {
+        Opcode::ALL
+            .into_iter()
+            .filter(|&opcode| self.behaves_like(opcode))
+    }
+
+    fn candidates(&self) -> Candidates {
+        Opcode::ALL
+            .iter()
+            .enumerate()
+            .filter(|&(_, &opcode)| self.behaves_like(opcode))
+            .fold(0, |candidates, (index, _)| candidates | (1 << index))
+    }
+}
+
+fn names(candidates: Candidates) -> String {
+    Opcode::ALL
+        .iter()
+        .enumerate()
+        .filter(|&(index, _)| candidates & (1 << index) != 0)
+        .map(|(_, opcode)| opcode.name())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn list(numbers: impl IntoIterator<Item = usize>) -> String {
+    numbers
+        .into_iter()
+        .map(|number| number.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// The candidate opcodes of an opcode number, narrowed down by its samples, along with the
+/// samples (numbered from 1) and the candidates each of them leaves.
+struct Number {
+    candidates: Candidates,
+    samples: Vec<(usize, Candidates)>,
+}
+
+impl Number {
+    /// Some of the samples which together leave no candidates outside of `allowed`, picking
+    /// those narrowing down the candidates left by the ones picked before.
+    fn samples_narrowing_to(&self, allowed: Candidates) -> Vec<usize> {
+        let mut candidates = ALL_CANDIDATES;
+        let mut picked = Vec::new();
+        for &(sample, sample_candidates) in self.samples.iter().rev() {
+            if candidates & !allowed == 0 {
+                break;
+            }
+            if candidates & sample_candidates != candidates {
+                candidates &= sample_candidates;
+                picked.push(sample);
+            }
+        }
+        picked.sort_unstable();
+        picked
+    }
+}
+
+fn narrow_down(samples: &[Sample]) -> Result<Vec<Number>, String> {
+    let mut numbers = (0..OPCODE_NUMBERS)
+        .map(|_| Number {
+            candidates: ALL_CANDIDATES,
+            samples: Vec::new(),
+        })
+        .collect::<Vec<_>>();
+    for (index, sample) in samples.iter().enumerate() {
+        let opcode_number = sample.instruction[0];
+        let number = usize::try_from(opcode_number)
+            .ok()
+            .and_then(|number| numbers.get_mut(number))
+            .ok_or_else(|| {
+                format!(
+                    "Sample {}: Opcode number {opcode_number} is not below {OPCODE_NUMBERS}",
+                    index + 1
+                )
+            })?;
+        let candidates = sample.candidates();
+        if candidates == 0 {
+            return Err(format!(
+                "Sample {} does not behave like any opcode",
+                index + 1
+            ));
+        }
+        number.candidates &= candidates;
+        number.samples.push((index + 1, candidates));
+        if number.candidates == 0 {
+            return Err(format!(
+                "No opcode behaves like all of samples {}, with opcode number {opcode_number}",
+                list(number.samples_narrowing_to(0))
+            ));
+        }
+    }
+    Ok(numbers)
+}
+
+/// Narrows down candidates of opcode numbers from those already assigned, returning false
+/// if this leaves a number or an opcode without any possible assignment.
+fn propagate(candidates: &mut [Candidates; OPCODE_NUMBERS]) -> bool {
+    loop {
+        let mut changed = false;
+        for number in 0..OPCODE_NUMBERS {
+            if candidates[number].count_ones() != 1 {
+                continue;
+            }
+            for other in 0..OPCODE_NUMBERS {
+                if other != number && candidates[other] & candidates[number] != 0 {
+                    candidates[other] &= !candidates[number];
+                    if candidates[other] == 0 {
+                        return false;
+                    }
+                    changed = true;
+                }
+            }
+        }
+        for opcode in 0..Opcode::ALL.len() {
+            let bit = 1 << opcode;
+            let mut numbers = (0..OPCODE_NUMBERS).filter(|&number| candidates[number] & bit != 0);
+            match (numbers.next(), numbers.next()) {
+                (None, _) => return false,
+                (Some(number), None) if candidates[number] != bit => {
+                    candidates[number] = bit;
+                    changed = true;
+                }
+                _ => {}
+            }
+        }
+        if !changed {
+            return true;
+        }
+    }
+}
+
+fn search(
+    mut candidates: [Candidates; OPCODE_NUMBERS],
+    limit: usize,
+    assignments: &mut Vec<[Opcode; OPCODE_NUMBERS]>,
+) {
+    if assignments.len() >= limit || !propagate(&mut candidates) {
+        return;
+    }
+    let Some(number) = (0..OPCODE_NUMBERS)
+        .filter(|&number| candidates[number].count_ones() > 1)
+        .min_by_key(|&number| candidates[number].count_ones())
+    else {
+        assignments.push(candidates.map(|bit| Opcode::ALL[bit.trailing_zeros() as usize]));
+        return;
+    };
+    for opcode in 0..Opcode::ALL.len() {
+        if candidates[number] & (1 << opcode) != 0 {
+            let mut next = candidates;
+            next[number] = 1 << opcode;
+            search(next, limit, assignments);
+        }
+    }
+}
+
+/// Extends the matching of opcodes to opcode numbers with an alternating path from a number,
+/// as in Kuhn's algorithm.
+fn augment(
+    numbers: &[Number],
+    number: usize,
+    visited: &mut Candidates,
+    matched_number: &mut [Option<usize>; OPCODE_NUMBERS],
+) -> bool {
+    for opcode in 0..Opcode::ALL.len() {
+        let bit = 1 << opcode;
+        if numbers[number].candidates & bit == 0 || *visited & bit != 0 {
+            continue;
+        }
+        *visited |= bit;
+        if matched_number[opcode]
+            .is_none_or(|other| augment(numbers, other, visited, matched_number))
+        {
+            matched_number[opcode] = Some(number);
+            return true;
+        }
+    }
+    false
+}
+
+/// Describes why no assignment is consistent with the candidates of the opcode numbers.
+fn explain_contradiction(numbers: &[Number]) -> String {
+    let mut matched_number = [None; OPCODE_NUMBERS];
+    let mut unmatched = None;
+    for number in 0..OPCODE_NUMBERS {
+        if !augment(numbers, number, &mut 0, &mut matched_number) {
+            unmatched = Some(number);
+        }
+    }
+    let Some(unmatched) = unmatched else {
+        return "Internal error - the samples have a consistent assignment".to_string();
+    };
+
+    // The numbers reachable through alternating paths only have matched candidates, which
+    // are matched to the other numbers reached:
+    let mut reached = vec![unmatched];
+    let mut reached_candidates: Candidates = 0;
+    let mut next = 0;
+    while let Some(&number) = reached.get(next) {
+        next += 1;
+        let new_candidates = numbers[number].candidates & !reached_candidates;
+        reached_candidates |= new_candidates;
+        for (opcode, &matched) in matched_number.iter().enumerate() {
+            if new_candidates & (1 << opcode) != 0 {
+                reached.extend(matched);
+            }
+        }
+    }
+    reached.sort_unstable();
+
+    let mut samples = reached
+        .iter()
+        .flat_map(|&number| numbers[number].samples_narrowing_to(reached_candidates))
+        .collect::<Vec<_>>();
+    samples.sort_unstable();
+    format!(
+        "Opcode numbers {} can only mean {} between them, as shown by samples {}",
+        list(reached),
+        names(reached_candidates),
+        list(samples)
+    )
+}
+
+/// Finds up to `limit` assignments of opcodes to the opcode numbers which are consistent with
+/// the samples, failing with the samples in conflict if there are none.
+pub fn consistent_assignments(
+    samples: &[Sample],
+    limit: usize,
+) -> Result<Vec<[Opcode; OPCODE_NUMBERS]>, String> {
+    let numbers = narrow_down(samples)?;
+    let mut candidates = [ALL_CANDIDATES; OPCODE_NUMBERS];
+    for (number, number_candidates) in numbers.iter().zip(candidates.iter_mut()) {
+        *number_candidates = number.candidates;
+    }
+
+    let mut assignments = Vec::new();
+    search(candidates, limit, &mut assignments);
+    if assignments.is_empty() && limit > 0 {
+        return Err(explain_contradiction(&numbers));
+    }
+    Ok(assignments)
+}
+
+/// Finds the only assignment of opcodes to the opcode numbers which is consistent with the
+/// samples, failing if the samples are in conflict or allow more than one.
+pub fn infer_opcodes(samples: &[Sample]) -> Result<[Opcode; OPCODE_NUMBERS], String> {
+    match consistent_assignments(samples, 2)?[..] {
+        [assignment] => Ok(assignment),
+        [first, second] => {
+            let alternatives = (0..OPCODE_NUMBERS)
+                .filter(|&number| first[number] != second[number])
+                .map(|number| {
+                    format!(
+                        "opcode number {number} as {} or {}",
+                        first[number].name(),
+                        second[number].name()
+                    )
+                })
+                .collect::<Vec<_>>();
+            Err(format!(
+                "The samples allow more than one assignment of opcodes, such as with {}",
+                alternatives.join(", ")
+            ))
+        }
+        _ => Err("Internal error - no assignment found".to_string()),
+    }
+}
+
+#[test]
+#[allow(clippy::unwrap_used)]
+fn test() {
+    let sample = |before: [u64; 4], instruction: [u64; 4], after: [u64; 4]| {
+        let mut sample = Sample {
+            before: Registers::new(),
+            instruction,
+            after: Registers::new(),
+        };
+        sample.before.values[..4].copy_from_slice(&before);
+        sample.after.values[..4].copy_from_slice(&after);
+        sample
+    };
+    let names_of = |opcodes: &[Opcode]| {
+        opcodes
+            .iter()
+            .map(|opcode| opcode.name())
+            .collect::<Vec<_>>()
+    };
+
+    // 1 + 2 = 3 and 1 | 2 = 3:
+    let add_or_bitwise_or = |number| sample([1, 2, 0, 0], [number, 0, 1, 2], [1, 2, 3, 0]);
+    // 3 * 3 = 9:
+    let multiply = |number| sample([3, 0, 0, 0], [number, 0, 0, 2], [3, 0, 9, 0]);
+
+    assert_eq!(
+        names_of(&add_or_bitwise_or(0).matching_opcodes().collect::<Vec<_>>()),
+        ["addr", "borr"]
+    );
+    assert_eq!(
+        names_of(&multiply(0).matching_opcodes().collect::<Vec<_>>()),
+        ["mulr"]
+    );
+
+    // Without samples, any permutation of the opcodes is consistent:
+    assert_eq!(consistent_assignments(&[], 3).unwrap().len(), 3);
+    assert!(infer_opcodes(&[multiply(0)]).err().unwrap().starts_with(
+        "The samples allow more than one assignment of opcodes, such as with opcode number"
+    ));
+
+    assert_eq!(
+        infer_opcodes(&[sample([0; 4], [0, 0, 0, 0], [5; 4])]).err(),
+        Some("Sample 1 does not behave like any opcode".to_string())
+    );
+    assert_eq!(
+        infer_opcodes(&[multiply(16)]).err(),
+        Some("Sample 1: Opcode number 16 is not below 16".to_string())
+    );
+    assert_eq!(
+        infer_opcodes(&[
+            add_or_bitwise_or(4),
+            multiply(1),
+            add_or_bitwise_or(4),
+            multiply(4)
+        ])
+        .err(),
+        Some("No opcode behaves like all of samples 3, 4, with opcode number 4".to_string())
+    );
+    assert_eq!(
+        infer_opcodes(&[
+            add_or_bitwise_or(2),
+            multiply(1),
+            add_or_bitwise_or(3),
+            add_or_bitwise_or(7)
+        ])
+        .err(),
+        Some(
+            "Opcode numbers 2, 3, 7 can only mean addr, borr between them, as shown by samples 1, 3, 4"
+                .to_string()
+        )
+    );
+    assert_eq!(
+        infer_opcodes(&[multiply(1), multiply(5)]).err(),
+        Some(
+            "Opcode numbers 1, 5 can only mean mulr between them, as shown by samples 1, 2"
+                .to_string()
+        )
+    );
+}
@@ -0,0 +1,482 @@
+//! An optimizer replacing loops of elfcode programs with native operations.
+//!
+//! Loops are recognized by matching instructions against templates, where lowercase
+//! letters are registers, uppercase letters are values, `ip` is the register bound to the
+//! instruction pointer and `_` is ignored. Operands of commutative instructions may be
+//! given in any order, and the registers of a loop are only required to be distinct where
+//! the replacement depends on it.
+//!
+//! A replaced loop keeps its instructions, with the first one running the native operation
+//! instead and jumping to the exit of the loop. If the native operation is not valid for
+//! the current register values, such as on overflow, the original instruction is run.
+
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+use super::{Computer, Instruction, Opcode};
+use crate::common::register_machine::{Effect, Machine, NativeLoops, NativeOperation};
+
+/// A division by a constant, as a loop incrementing a quotient until
+/// `(quotient + 1) * divisor > dividend`.
+const DIVISION: [&str; 8] = [
+    "addi q 1 t",
+    "muli t D t",
+    "gtrr t n f",
+    "addr f ip ip",
+    "seti S _ ip",
+    "seti X _ ip",
+    "addi q 1 q",
+    "seti B _ ip",
+];
+
+/// A loop adding a factor to a sum if the product of it and a multiplier, counting up to
+/// a number, is the number.
+const DIVISOR_CHECK: [&str; 9] = [
+    "mulr a b p",
+    "eqrr p n f",
+    "addr f ip ip",
+    "seti S _ ip",
+    "addr a x x",
+    "addi b 1 b",
+    "gtrr b n g",
+    "addr g ip ip",
+    "seti B _ ip",
+];
+
+/// A loop running the divisor check loop for factors counting up to the number, which
+/// computes the sum of the divisors of the number.
+const DIVISOR_SUM: [&str; 14] = [
+    "seti 1 _ b",
+    "mulr a b p",
+    "eqrr p n f",
+    "addr f ip ip",
+    "seti S _ ip",
+    "addr a x x",
+    "addi b 1 b",
+    "gtrr b n g",
+    "addr g ip ip",
+    "seti B _ ip",
+    "addi a 1 a",
+    "gtrr a n h",
+    "addr h ip ip",
+    "seti C _ ip",
+];
+
+#[derive(Copy, Clone)]
+pub enum NativeLoop {
+    /// Sets `quotient` to `dividend / divisor`, if larger.
+    Division {
+        dividend: usize,
+        divisor: u64,
+        quotient: usize,
+        product: usize,
+        flag: usize,
+        exit: usize,
+    },
+    /// Adds `factor` to `sum` if it is a divisor of `number` with a quotient of at least
+    /// `multiplier`, and sets `multiplier` to `number + 1`.
+    DivisorCheck {
+        factor: usize,
+        multiplier: usize,
+        number: usize,
+        sum: usize,
+        product: usize,
+        flag: usize,
+        loop_flag: usize,
+        exit: usize,
+    },
+    /// Adds the divisors of `number` from `factor` to `sum`, and sets `factor` and
+    /// `multiplier` to `number + 1`.
+    DivisorSum {
+        factor: usize,
+        multiplier: usize,
+        number: usize,
+        sum: usize,
+        product: usize,
+        flag: usize,
+        loop_flag: usize,
+        outer_loop_flag: usize,
+        exit: usize,
+    },
+}
+
+impl NativeOperation<Computer> for NativeLoop {
+    fn execute(self, machine: &mut Machine<Computer>) -> Option<Effect<u64>> {
+        let registers = &mut machine.registers;
+        let exit = match self {
+            Self::Division {
+                dividend,
+                divisor,
+                quotient,
+                product,
+                flag,
+                exit,
+            } => {
+                let quotient_value = registers[quotient].max(registers[dividend] / divisor);
+                let product_value = quotient_value.checked_add(1)?.checked_mul(divisor)?;
+                registers[quotient] = quotient_value;
+                registers[product] = product_value;
+                registers[flag] = 1;
+                exit
+            }
+            Self::DivisorCheck {
+                factor,
+                multiplier,
+                number,
+                sum,
+                product,
+                flag,
+                loop_flag,
+                exit,
+            } => {
+                let (factor_value, number_value) = (registers[factor], registers[number]);
+                if factor_value == 0 {
+                    return None;
+                }
+                let last_multiplier = registers[multiplier].max(number_value);
+                let product_value = factor_value.checked_mul(last_multiplier)?;
+                let multiplier_value = last_multiplier.checked_add(1)?;
+                let sum_value = if number_value % factor_value == 0
+                    && number_value / factor_value >= registers[multiplier]
+                {
+                    registers[sum].checked_add(factor_value)?
+                } else {
+                    registers[sum]
+                };
+                registers[sum] = sum_value;
+                registers[product] = product_value;
+                registers[flag] = u64::from(product_value == number_value);
+                registers[multiplier] = multiplier_value;
+                registers[loop_flag] = 1;
+                exit
+            }
+            Self::DivisorSum {
+                factor,
+                multiplier,
+                number,
+                sum,
+                product,
+                flag,
+                loop_flag,
+                outer_loop_flag,
+                exit,
+            } => {
+                let (factor_value, number_value) = (registers[factor], registers[number]);
+                if factor_value == 0 || factor_value > number_value {
+                    return None;
+                }
+                let mut divisor_sum = registers[sum];
+                for divisor in (1..).take_while(|&i| i <= number_value / i) {
+                    if number_value % divisor == 0 {
+                        let paired_divisor = number_value / divisor;
+                        if divisor >= factor_value {
+                            divisor_sum = divisor_sum.checked_add(divisor)?;
+                        }
+                        if paired_divisor != divisor && paired_divisor >= factor_value {
+                            divisor_sum = divisor_sum.checked_add(paired_divisor)?;
+                        }
+                    }
+                }
+                let product_value = number_value.checked_mul(number_value)?;
+                let last_factor = number_value.checked_add(1)?;
+                registers[sum] = divisor_sum;
+                registers[product] = product_value;
+                registers[flag] = u64::from(product_value == number_value);
+                registers[multiplier] = last_factor;
+                registers[loop_flag] = 1;
+                registers[factor] = last_factor;
+                registers[outer_loop_flag] = 1;
+                exit
+            }
+        };
+        Some(Effect::JumpTo(exit))
+    }
+}
+
+impl Display for NativeLoop {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Division {
+                dividend,
+                divisor,
+                quotient,
+                exit,
+                ..
+            } => write!(
+                f,
+                "r{quotient} = max(r{quotient}, r{dividend} / {divisor}), goto {exit}"
+            ),
+            Self::DivisorCheck {
+                factor,
+                number,
+                sum,
+                exit,
+                ..
+            } => write!(
+                f,
+                "r{sum} += r{factor} if it divides r{number}, goto {exit}"
+            ),
+            Self::DivisorSum {
+                factor,
+                number,
+                sum,
+                exit,
+                ..
+            } => write!(
+                f,
+                "r{sum} += divisors of r{number} from r{factor}, goto {exit}"
+            ),
+        }
+    }
+}
+
+type Bindings = HashMap<char, u64>;
+
+impl Computer {
+    /// Optimizes a program by replacing arithmetic on the instruction pointer with constants,
+    /// which turns relative jumps into absolute ones, and recognized loops with native
+    /// operations.
+    pub fn optimize(machine: &mut Machine<Self>) {
+        let Some(instruction_pointer_index) = machine.instruction_pointer_register else {
+            return;
+        };
+        let ip = instruction_pointer_index as u64;
+        for (line, instruction) in machine.instructions.iter_mut().enumerate() {
+            let value = match instruction.opcode {
+                Opcode::Addi if instruction.a == ip => line as u64 + instruction.b,
+                Opcode::Mulr if instruction.a == ip && instruction.b == ip => {
+                    line as u64 * line as u64
+                }
+                Opcode::Muli if instruction.a == ip => line as u64 * instruction.b,
+                _ => continue,
+            };
+            instruction.opcode = Opcode::Seti;
+            instruction.a = value;
+            instruction.b = 0; // ignored
+        }
+
+        let loops = NativeLoops::find(&machine.instructions, |start, instructions| {
+            None.or_else(|| divisor_sum(start, &match_templates(instructions, &DIVISOR_SUM, ip)?))
+                .or_else(|| {
+                    divisor_check(start, &match_templates(instructions, &DIVISOR_CHECK, ip)?)
+                })
+                .or_else(|| division(start, &match_templates(instructions, &DIVISION, ip)?))
+        });
+        machine.state.loops = Some(loops);
+    }
+}
+
+/// Looks up register variables, which have to be distinct and not the instruction pointer.
+fn distinct_registers<const N: usize>(
+    bindings: &Bindings,
+    names: [char; N],
+    ip: u64,
+) -> Option<[usize; N]> {
+    let registers = names.map(|name| bindings[&name]);
+    let distinct = registers.iter().enumerate().all(|(i, register)| {
+        *register < 6 && *register != ip && !registers[..i].contains(register)
+    });
+    distinct.then(|| registers.map(|register| register as usize))
+}
+
+/// Looks up register variables written by a loop, which have to be other registers than
+/// those in `used`, but may be the same register.
+fn temporary_registers<const N: usize>(
+    bindings: &Bindings,
+    names: [char; N],
+    used: &[usize],
+) -> Option<[usize; N]> {
+    let registers = names.map(|name| bindings[&name] as usize);
+    registers
+        .iter()
+        .all(|register| !used.contains(register))
+        .then_some(registers)
+}
+
+/// If the jump of a `seti` template variable goes to the given instruction index.
+fn jumps_to(bindings: &Bindings, name: char, target: usize) -> bool {
+    bindings[&name] + 1 == target as u64
+}
+
+fn division(start: usize, bindings: &Bindings) -> Option<NativeLoop> {
+    let ip = bindings[&'i'];
+    let [quotient, dividend] = distinct_registers(bindings, ['q', 'n'], ip)?;
+    let [product, flag] =
+        temporary_registers(bindings, ['t', 'f'], &[quotient, dividend, ip as usize])?;
+    let divisor = bindings[&'D'];
+    (divisor > 0 && jumps_to(bindings, 'S', start + 6) && jumps_to(bindings, 'B', start)).then(
+        || NativeLoop::Division {
+            dividend,
+            divisor,
+            quotient,
+            product,
+            flag,
+            exit: bindings[&'X'] as usize + 1,
+        },
+    )
+}
+
+fn divisor_check(start: usize, bindings: &Bindings) -> Option<NativeLoop> {
+    let ip = bindings[&'i'];
+    let [factor, multiplier, number, sum] = distinct_registers(bindings, ['a', 'b', 'n', 'x'], ip)?;
+    let [product, flag, loop_flag] = temporary_registers(
+        bindings,
+        ['p', 'f', 'g'],
+        &[factor, multiplier, number, sum, ip as usize],
+    )?;
+    (jumps_to(bindings, 'S', start + 5) && jumps_to(bindings, 'B', start)).then_some(
+        NativeLoop::DivisorCheck {
+            factor,
+            multiplier,
+            number,
+            sum,
+            product,
+            flag,
+            loop_flag,
+            exit: start + 9,
+        },
+    )
+}
+
+fn divisor_sum(start: usize, bindings: &Bindings) -> Option<NativeLoop> {
+    let ip = bindings[&'i'];
+    let [factor, multiplier, number, sum] = distinct_registers(bindings, ['a', 'b', 'n', 'x'], ip)?;
+    let [product, flag, loop_flag, outer_loop_flag] = temporary_registers(
+        bindings,
+        ['p', 'f', 'g', 'h'],
+        &[factor, multiplier, number, sum, ip as usize],
+    )?;
+    (jumps_to(bindings, 'S', start + 6)
+        && jumps_to(bindings, 'B', start + 1)
+        && jumps_to(bindings, 'C', start))
+    .then_some(NativeLoop::DivisorSum {
+        factor,
+        multiplier,
+        number,
+        sum,
+        product,
+        flag,
+        loop_flag,
+        outer_loop_flag,
+        exit: start + 14,
+    })
+}
+
+/// Matches instructions against templates, returning the values bound to the variables,
+/// with the instruction pointer register bound to `i`.
+fn match_templates(instructions: &[Instruction], templates: &[&str], ip: u64) -> Option<Bindings> {
+    let mut bindings = Bindings::new();
+    bindings.insert('i', ip);
+    match_from(instructions, templates, bindings)
+}
+
+fn match_from(
+    instructions: &[Instruction],
+    templates: &[&str],
+    bindings: Bindings,
+) -> Option<Bindings> {
+    let Some((template, templates)) = templates.split_first() else {
+        return Some(bindings);
+    };
+    let (instruction, instructions) = instructions.split_first()?;
+    let mut words = template.split(' ');
+    if words.next() != Some(instruction.opcode.name()) {
+        return None;
+    }
+    let operands = [words.next()?, words.next()?, words.next()?];
+
+    let Instruction { a, b, c, .. } = *instruction;
+    let commutative = matches!(
+        instruction.opcode,
+        Opcode::Addr | Opcode::Mulr | Opcode::Banr | Opcode::Borr | Opcode::Eqrr
+    );
+    let orders = if commutative {
+        &[[a, b, c], [b, a, c]][..]
+    } else {
+        &[[a, b, c]][..]
+    };
+    orders.iter().find_map(|values| {
+        let mut bindings = bindings.clone();
+        operands
+            .iter()
+            .zip(values)
+            .all(|(operand, &value)| match *operand {
+                "_" => true,
+                "ip" => value == bindings[&'i'],
+                operand if operand.starts_with(|c: char| c.is_ascii_digit()) => {
+                    operand.parse() == Ok(value)
+                }
+                operand => operand
+                    .chars()
+                    .next()
+                    .is_some_and(|name| *bindings.entry(name).or_insert(value) == value),
+            })
+            .then(|| match_from(instructions, templates, bindings))
+            .flatten()
+    })
+}
+
+#[test]
+#[allow(clippy::unwrap_used)]
+fn test() {
+    // A division of r2 by 256 into r4, with the registers and operands reordered from
+    // how they appear in 2018 day 21:
+    let mut program = Computer::parse(
+        "#ip 0
+seti 1000 0 2
+seti 0 0 4
+addi 4 1 3
+muli 3 256 3
+gtrr 3 2 3
+addr 0 3 0
+addi 0 1 0
+seti 9 0 0
+addi 4 1 4
+seti 1 0 0
+seti 7 0 1",
+    )
+    .unwrap();
+    Computer::optimize(&mut program);
+    assert!(program.state.loops.as_ref().unwrap().get(2).is_some());
+    program.run_until_halt(100).unwrap();
+    assert_eq!(program.registers.values, [11, 7, 1000, 1, 3, 0]);
+    assert_eq!(program.executed_instructions, 4);
+
+    // The sum of the divisors of r1 = 60, which is 168, from r4 = 2 into r0:
+    let mut program = Computer::parse(
+        "#ip 5
+seti 60 0 1
+seti 2 0 4
+seti 1 0 2
+mulr 2 4 3
+eqrr 3 1 3
+addr 3 5 5
+addi 5 1 5
+addr 4 0 0
+addi 2 1 2
+gtrr 2 1 3
+addr 5 3 5
+seti 2 0 5
+addi 4 1 4
+gtrr 4 1 3
+addr 3 5 5
+seti 1 0 5",
+    )
+    .unwrap();
+    Computer::optimize(&mut program);
+    // The relative jump skipping the addition is now an absolute one:
+    assert_eq!(program.instructions[6].to_string(), "seti 7 0 5");
+    let loops = program.state.loops.as_ref().unwrap();
+    assert_eq!(
+        loops.get(2).unwrap().to_string(),
+        "r0 += divisors of r1 from r4, goto 16"
+    );
+    // The inner loop alone, as entered from the outer one:
+    assert_eq!(
+        loops.get(3).unwrap().to_string(),
+        "r0 += r4 if it divides r1, goto 12"
+    );
+    program.run_until_halt(100).unwrap();
+    assert_eq!(program.registers.values, [167, 60, 61, 1, 61, 16]);
+    assert_eq!(program.executed_instructions, 3);
+}
@@ -10,8 +10,8 @@ pub fn solve(input: &Input) -> Result<Word, String> {
     for noun in input.part_values(12..=12, 0..=99) {
         for verb in input.part_values(2..=2, 0..=99) {
             let mut program = initial_program.clone();
-            program.write_memory(1, noun);
-            program.write_memory(2, verb);
+            program.write_memory(1, noun)?;
+            program.write_memory(2, verb)?;
             program.run_until_halt_or_input(10_000)?;
             let memory_value = program.read_memory(0);
             if input.is_part_one() {
@@ -1,7 +1,42 @@
+use super::int_code::network::{Flow, Network, Outbox, Router, Scheduler};
 use super::int_code::{Program, Word};
 use crate::common::permutation::all_permutations;
 use crate::input::Input;
-use std::cell::RefCell;
+
+/// Passes the signal from each amplifier to the next, optionally feeding the output of
+/// the last amplifier back into the first one.
+struct Amplifiers {
+    feedback_loop: bool,
+    last_signal_output: Word,
+}
+
+impl Router for Amplifiers {
+    type Output = Word;
+    type Input = Word;
+    type Result = Word;
+
+    fn route(
+        &mut self,
+        from: usize,
+        signal: Word,
+        outbox: &mut Outbox<Word>,
+    ) -> Result<Flow<Word>, String> {
+        let next = from + 1;
+        if next == outbox.machine_count() {
+            self.last_signal_output = signal;
+            if self.feedback_loop {
+                outbox.send(0, signal);
+            }
+        } else {
+            outbox.send(next, signal);
+        }
+        Ok(Flow::Continue)
+    }
+
+    fn idle(&mut self, _outbox: &mut Outbox<Word>) -> Result<Flow<Word>, String> {
+        Ok(Flow::Stop(self.last_signal_output))
+    }
+}
 
 pub fn solve(input: &Input) -> Result<i64, String> {
     let program = Program::parse(input.text)?;
@@ -13,37 +48,18 @@ pub fn solve(input: &Input) -> Result<i64, String> {
     let mut strongest_signal = 0;
 
     all_permutations(&mut phase_settings, &mut |permutation: &[Word]| {
-        let mut amplifier_programs = Vec::with_capacity(5);
-        for &phase in permutation.iter() {
-            let mut new_program = program.clone();
-            new_program.input(phase);
-            amplifier_programs.push(RefCell::new(new_program));
-        }
-
-        amplifier_programs[0].borrow_mut().input(0);
-
-        let mut last_signal_output = 0;
-        'outer: loop {
-            for i in 0..5 {
-                let mut current_program = amplifier_programs[i].borrow_mut();
-                let output = current_program.run_for_output_limited(10_000)?;
-
-                if i == 4 {
-                    if let Some(&value) = output.last() {
-                        last_signal_output = value;
-                    }
-                    if input.is_part_one() || current_program.is_halted() {
-                        break 'outer;
-                    }
-                }
-
-                let mut next_program = amplifier_programs[(i + 1) % 5].borrow_mut();
-                for &value in output.iter() {
-                    next_program.input(value);
-                }
-            }
+        let mut network = Network::new(vec![program.clone(); permutation.len()]);
+        network.set_instruction_limit(10_000);
+        for (amplifier, &phase) in permutation.iter().enumerate() {
+            network.send(amplifier, phase);
         }
+        network.send(0, 0);
 
+        let mut amplifiers = Amplifiers {
+            feedback_loop: input.is_part_two(),
+            last_signal_output: 0,
+        };
+        let last_signal_output = network.run(&mut amplifiers, Scheduler::RoundRobin)?;
         strongest_signal = std::cmp::max(strongest_signal, last_signal_output);
         Ok(())
     })?;
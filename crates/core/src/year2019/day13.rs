@@ -1,53 +1,23 @@
+pub mod arcade;
+
 use super::int_code::{Program, Word};
 use crate::input::Input;
+use arcade::Arcade;
 
 pub fn solve(input: &Input) -> Result<Word, String> {
-    let mut program = Program::parse(input.text)?;
-
-    let is_part_one = input.is_part_one();
+    let program = Program::parse(input.text)?;
+    let mut arcade = Arcade::new(program, input.is_part_two())?;
+    arcade.run()?;
 
-    // "Memory address 0 represents the number of quarters that have been
-    // inserted; set it to 2 to play for free."
-    if !is_part_one {
-        program.write_memory(0, 2);
+    if input.is_part_one() {
+        return Ok(arcade.block_count() as Word);
     }
 
-    let mut current_score = 0;
-    let mut ball_x = -1;
-    let mut paddle_x = -1;
-
-    loop {
-        let output = program.run_for_output()?;
-        output.chunks_exact(3).for_each(|chunk| {
-            let (x, y, third) = (chunk[0], chunk[1], chunk[2]);
-            if x == -1 && y == 0 {
-                current_score = third;
-            } else {
-                match third {
-                    3 => paddle_x = x,
-                    4 => ball_x = x,
-                    _ => {}
-                }
-            }
-        });
-
-        if is_part_one {
-            return Ok(output
-                .iter()
-                .skip(2)
-                .step_by(3)
-                .filter(|&&t| t == 2)
-                .count() as Word);
-        }
-
-        if program.is_halted() {
-            break;
-        }
-
-        program.input(ball_x.cmp(&paddle_x) as Word);
+    while !arcade.is_game_over() {
+        arcade.move_joystick(arcade.automatic_joystick());
+        arcade.run()?;
     }
-
-    Ok(current_score)
+    Ok(arcade.score())
 }
 
 #[test]
@@ -0,0 +1,174 @@
+//! The arcade cabinet of [2019 day 13](https://adventofcode.com/2019/day/13), which runs
+//! a breakout game drawing tiles on a screen and reading the position of a joystick.
+//!
+//! # Example
+//! ```
+//! use advent_of_code::arcade::Arcade;
+//! use advent_of_code::int_code::Program;
+//!
+//! // Draws a ball at (1, 2) and a score of 10 before halting:
+//! let program = Program::parse("104,1,104,2,104,4,104,-1,104,0,104,10,99").unwrap();
+//! let mut arcade = Arcade::new(program, false).unwrap();
+//! arcade.run().unwrap();
+//! assert!(arcade.is_game_over());
+//! assert_eq!(arcade.ball_position(), Some((1, 2)));
+//! assert_eq!(arcade.score(), 10);
+//! ```
+
+use crate::int_code::{Program, Word};
+
+/// The largest width and height of the screen, which the game stays well within.
+const MAX_SCREEN_SIZE: usize = 500;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tile {
+    Empty,
+    Wall,
+    Block,
+    Paddle,
+    Ball,
+}
+
+impl Tile {
+    fn from_id(id: Word) -> Result<Self, String> {
+        Ok(match id {
+            0 => Self::Empty,
+            1 => Self::Wall,
+            2 => Self::Block,
+            3 => Self::Paddle,
+            4 => Self::Ball,
+            _ => return Err(format!("Invalid tile id: {id}")),
+        })
+    }
+}
+
+/// The arcade cabinet, keeping track of the screen drawn by the game program.
+pub struct Arcade {
+    program: Program,
+    /// The tiles of the screen, indexed by row and column.
+    screen: Vec<Vec<Tile>>,
+    score: Word,
+    ball_position: Option<(usize, usize)>,
+    paddle_position: Option<(usize, usize)>,
+}
+
+impl Arcade {
+    /// Creates an arcade running the game, with quarters inserted to play it if
+    /// `play_for_free` is set. Otherwise the game only draws the initial screen.
+    pub fn new(mut program: Program, play_for_free: bool) -> Result<Self, String> {
+        // "Memory address 0 represents the number of quarters that have been
+        // inserted; set it to 2 to play for free."
+        if play_for_free {
+            program.write_memory(0, 2)?;
+        }
+        Ok(Self {
+            program,
+            screen: Vec::new(),
+            score: 0,
+            ball_position: None,
+            paddle_position: None,
+        })
+    }
+
+    /// Runs the game until it reads the joystick or is over, drawing its output.
+    pub fn run(&mut self) -> Result<(), String> {
+        if self.program.is_halted() {
+            return Ok(());
+        }
+        let output = self.program.run_for_output()?;
+        for chunk in output.chunks_exact(3) {
+            let (x, y, value) = (chunk[0], chunk[1], chunk[2]);
+            if x == -1 && y == 0 {
+                self.score = value;
+                continue;
+            }
+
+            let (Ok(x), Ok(y)) = (usize::try_from(x), usize::try_from(y)) else {
+                return Err(format!("Invalid tile position: ({x}, {y})"));
+            };
+            if x >= MAX_SCREEN_SIZE || y >= MAX_SCREEN_SIZE {
+                return Err(format!("Tile position outside of the screen: ({x}, {y})"));
+            }
+            let tile = Tile::from_id(value)?;
+            if self.screen.len() <= y {
+                self.screen.resize(y + 1, Vec::new());
+            }
+            let row = &mut self.screen[y];
+            if row.len() <= x {
+                row.resize(x + 1, Tile::Empty);
+            }
+            row[x] = tile;
+            match tile {
+                Tile::Ball => self.ball_position = Some((x, y)),
+                Tile::Paddle => self.paddle_position = Some((x, y)),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    pub const fn is_game_over(&self) -> bool {
+        self.program.is_halted()
+    }
+
+    /// Tilts the joystick left for a negative direction, right for a positive one or keeps
+    /// it neutral for zero, after which the game can continue with [`Arcade::run`].
+    pub fn move_joystick(&mut self, direction: Word) {
+        self.program.input(direction.signum());
+    }
+
+    /// The joystick direction which moves the paddle towards the ball, so that it is never
+    /// missed and all blocks are eventually broken.
+    pub fn automatic_joystick(&self) -> Word {
+        match (self.ball_position, self.paddle_position) {
+            (Some((ball_x, _)), Some((paddle_x, _))) => ball_x.cmp(&paddle_x) as Word,
+            _ => 0,
+        }
+    }
+
+    pub const fn score(&self) -> Word {
+        self.score
+    }
+
+    /// The tiles drawn on the screen, indexed by row and column.
+    pub fn screen(&self) -> &[Vec<Tile>] {
+        &self.screen
+    }
+
+    pub const fn ball_position(&self) -> Option<(usize, usize)> {
+        self.ball_position
+    }
+
+    pub const fn paddle_position(&self) -> Option<(usize, usize)> {
+        self.paddle_position
+    }
+
+    pub fn block_count(&self) -> usize {
+        self.screen
+            .iter()
+            .flatten()
+            .filter(|&&tile| tile == Tile::Block)
+            .count()
+    }
+}
+
+#[test]
+#[allow(clippy::unwrap_used)]
+fn test() {
+    let arcade = |program| Arcade::new(Program::parse(program).unwrap(), false).unwrap();
+
+    let mut drawing = arcade("104,3,104,1,104,2,104,0,104,0,104,1,99");
+    assert_eq!(drawing.run(), Ok(()));
+    assert_eq!(drawing.block_count(), 1);
+    assert_eq!(drawing.screen()[1][3], Tile::Block);
+    assert_eq!(drawing.screen()[0][0], Tile::Wall);
+
+    assert_eq!(
+        arcade("104,1000000000000,104,0,104,1,99").run(),
+        Err("Tile position outside of the screen: (1000000000000, 0)".to_string())
+    );
+    assert_eq!(
+        arcade("104,-2,104,0,104,1,99").run(),
+        Err("Invalid tile position: (-2, 0)".to_string())
+    );
+}
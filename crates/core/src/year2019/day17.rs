@@ -104,7 +104,7 @@ pub fn solve(input: &Input) -> Result<String, String> {
         return part1_map(&map);
     }
 
-    program.write_memory(0, 2);
+    program.write_memory(0, 2)?;
 
     let output = program.run_for_output()?;
     let map: String = output.iter().map(|&b| (b as u8) as char).collect();
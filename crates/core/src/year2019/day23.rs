@@ -1,69 +1,93 @@
+use super::int_code::network::{Flow, Network, Outbox, Router, Scheduler};
 use super::int_code::{Program, Word};
 use crate::input::Input;
-use std::collections::VecDeque;
 
-pub fn solve(input: &Input) -> Result<Word, String> {
-    let program = Program::parse(input.text)?;
-    let mut programs = vec![program; 50];
-    let mut input_queues = vec![VecDeque::<(Word, Word)>::new(); 50];
+const NAT_ADDRESS: Word = 255;
 
-    // Assign network addresses:
-    for (i, program) in programs.iter_mut().enumerate() {
-        program.input(i as Word);
-    }
+/// Delivers packets between computers, with packets to address 255 going to the NAT.
+///
+/// The NAT resends its last received packet to computer 0 when the network is idle.
+struct Nat {
+    stop_at_first_packet: bool,
+    last_packet_to_nat: Option<(Word, Word)>,
+    last_emitted_y: Option<Word>,
+}
 
-    let mut last_packet_to_nat = (-1, -1);
-    let mut last_emitted_packet_from_nat = (-1, -1);
+impl Router for Nat {
+    type Output = (Word, Word, Word);
+    type Input = (Word, Word);
+    type Result = Word;
 
-    loop {
-        for (program, input_queue) in programs.iter_mut().zip(input_queues.iter_mut()) {
-            if input_queue.is_empty() {
-                program.input(-1);
-            } else {
-                while let Some((x, y)) = input_queue.pop_front() {
-                    program.input(x);
-                    program.input(y);
-                }
+    fn empty_input(&self) -> Option<Word> {
+        Some(-1)
+    }
+
+    fn route(
+        &mut self,
+        _from: usize,
+        (destination_address, x, y): (Word, Word, Word),
+        outbox: &mut Outbox<(Word, Word)>,
+    ) -> Result<Flow<Word>, String> {
+        if destination_address == NAT_ADDRESS {
+            if self.stop_at_first_packet {
+                return Ok(Flow::Stop(y));
             }
+            self.last_packet_to_nat = Some((x, y));
+        } else {
+            let destination_address = usize::try_from(destination_address)
+                .map_err(|_| "Destination address out of bounds")?;
+            outbox.send(destination_address, (x, y));
         }
+        Ok(Flow::Continue)
+    }
 
-        let mut network_idle = true;
-        for program in programs.iter_mut() {
-            let output = program.run_for_output()?;
-            for chunk in output.chunks_exact(3) {
-                let (destination_address, packet) = (chunk[0], (chunk[1], chunk[2]));
-
-                if destination_address == 255 {
-                    if input.is_part_one() {
-                        return Ok(packet.1);
-                    } else {
-                        last_packet_to_nat = packet;
-                    }
-                } else {
-                    network_idle = false;
-                    input_queues
-                        .get_mut(destination_address as usize)
-                        .ok_or("Destination address out of bonds")?
-                        .push_back(packet);
-                }
-            }
+    fn idle(&mut self, outbox: &mut Outbox<(Word, Word)>) -> Result<Flow<Word>, String> {
+        let packet = self
+            .last_packet_to_nat
+            .ok_or("Network idle without any packet sent to the NAT")?;
+        if self.last_emitted_y == Some(packet.1) {
+            return Ok(Flow::Stop(packet.1));
         }
+        self.last_emitted_y = Some(packet.1);
+        outbox.send(0, packet);
+        Ok(Flow::Continue)
+    }
+}
 
-        if network_idle {
-            if last_packet_to_nat.1 == last_emitted_packet_from_nat.1 {
-                return Ok(last_packet_to_nat.1);
-            }
+pub fn solve(input: &Input) -> Result<Word, String> {
+    let program = Program::parse(input.text)?;
+    run_network(&program, input.is_part_one(), Scheduler::RoundRobin)
+}
 
-            last_emitted_packet_from_nat = last_packet_to_nat;
-            input_queues[0].push_back(last_packet_to_nat);
-        }
+fn run_network(
+    program: &Program,
+    stop_at_first_packet: bool,
+    scheduler: Scheduler,
+) -> Result<Word, String> {
+    let mut network = Network::new(vec![program.clone(); 50]);
+
+    // Assign network addresses:
+    for address in 0..50 {
+        network.send(address, address as Word);
     }
+
+    let mut nat = Nat {
+        stop_at_first_packet,
+        last_packet_to_nat: None,
+        last_emitted_y: None,
+    };
+    network.run(&mut nat, scheduler)
 }
 
 #[test]
+#[allow(clippy::unwrap_used)]
 pub fn tests() {
     use crate::input::{test_part_one, test_part_two};
     let input = include_str!("day23_input.txt");
     test_part_one!(input => 16549);
     test_part_two!(input => 11462);
+
+    let program = Program::parse(input).unwrap();
+    assert_eq!(run_network(&program, true, Scheduler::Threaded), Ok(16549));
+    assert_eq!(run_network(&program, false, Scheduler::Threaded), Ok(11462));
 }
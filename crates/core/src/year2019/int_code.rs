@@ -1,10 +1,65 @@
+//! A virtual machine for the Intcode computer used by many of the 2019 problems.
+//!
+//! The specification is spread out over days [2](https://adventofcode.com/2019/day/2),
+//! [5](https://adventofcode.com/2019/day/5) and [9](https://adventofcode.com/2019/day/9).
+//!
+//! # Example
+//! ```
+//! use advent_of_code::int_code::{Program, RunState};
+//!
+//! // Reads a value into address 9 and outputs it doubled:
+//! let mut program = Program::parse("3,9,102,2,9,10,4,10,99,0,0").unwrap();
+//! assert_eq!(program.run(1000), Ok(RunState::NeedsInput));
+//! program.input(21);
+//! assert_eq!(program.run(1000), Ok(RunState::Output(42)));
+//! assert_eq!(program.run(1000), Ok(RunState::Halted));
+//! assert_eq!(program.read_memory(9), 21);
+//! ```
+
+pub mod assembler;
+pub mod control_flow;
+pub mod coverage;
+pub mod debugger;
+pub mod disassembler;
+pub mod network;
+pub mod state;
+
 use std::collections::VecDeque;
+use std::sync::Arc;
 
+/// The value of a memory location.
 pub type Word = i64;
 
+/// The default highest address a program may access, see [`Program::set_memory_limit`].
+pub const DEFAULT_MEMORY_LIMIT: usize = 1 << 20;
+
+/// The state of a program after a call to [`Program::run`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RunState {
+    /// The program has executed the halt instruction, and cannot be run further.
+    Halted,
+    /// The program is waiting for a value, which should be supplied with [`Program::input`].
+    NeedsInput,
+    /// The program has produced an output value, and can be run again to continue.
+    Output(Word),
+    /// The instruction budget ran out, and the program can be run again to continue.
+    BudgetExhausted,
+}
+
+/// An Intcode program together with its execution state.
+///
+/// Memory grows as needed when written to, and reading from outside of it gives zero.
+///
+/// All words of the initial memory are decoded as instructions up front. The decoded
+/// instruction is only used if its word has not since been modified, so self-modifying
+/// programs work as expected. The decoded instructions are shared between clones, so
+/// that cloning a program to explore different inputs stays cheap.
 #[derive(Clone)]
 pub struct Program {
     memory: Vec<Word>,
+    /// The decoding of each word of the initial memory.
+    decoded: Arc<Vec<DecodedInstruction>>,
+    memory_limit: usize,
     instruction_pointer: usize,
     output_values: Vec<Word>,
     input_values: VecDeque<Word>,
@@ -13,12 +68,39 @@ pub struct Program {
     relative_base: Word,
 }
 
+/// The opcode and parameter modes of an instruction, decoded from `word`.
+#[derive(Clone, Copy)]
+struct DecodedInstruction {
+    word: Word,
+    opcode: i8,
+    modes: [u8; 3],
+}
+
+impl DecodedInstruction {
+    const fn decode(word: Word) -> Self {
+        Self {
+            word,
+            opcode: (word % 100) as i8,
+            modes: [
+                ((word / 100) % 10) as u8,
+                ((word / 1000) % 10) as u8,
+                ((word / 10000) % 10) as u8,
+            ],
+        }
+    }
+
+    fn decode_all(memory: &[Word]) -> Arc<Vec<Self>> {
+        Arc::new(memory.iter().map(|&word| Self::decode(word)).collect())
+    }
+}
+
 enum Parameter {
     Value(Word),
     Address(usize),
 }
 
 impl Program {
+    /// Parses a program from comma-separated integers.
     pub fn parse(input: &str) -> Result<Self, String> {
         let mut memory: Vec<Word> = Vec::new();
         for word_string in input.trim().split(',') {
@@ -31,21 +113,73 @@ impl Program {
                 }
             }
         }
-        Ok(Self {
+        Ok(Self::from_memory(memory))
+    }
+
+    fn from_memory(memory: Vec<Word>) -> Self {
+        Self {
+            decoded: DecodedInstruction::decode_all(&memory),
             memory,
+            memory_limit: DEFAULT_MEMORY_LIMIT,
             instruction_pointer: 0,
             output_values: Vec::new(),
             input_values: VecDeque::new(),
             halted: false,
             requires_input_to: None,
             relative_base: 0,
-        })
+        }
+    }
+
+    /// Sets the highest address the program may access, defaulting to [`DEFAULT_MEMORY_LIMIT`].
+    ///
+    /// Memory is only allocated as it is written to, but the limit catches programs
+    /// with runaway addresses instead of allocating memory for them.
+    pub fn set_memory_limit(&mut self, memory_limit: usize) {
+        self.memory_limit = memory_limit;
+    }
+
+    pub const fn memory_limit(&self) -> usize {
+        self.memory_limit
     }
 
     pub const fn is_halted(&self) -> bool {
         self.halted
     }
 
+    /// If the program is waiting for an input value before it can continue.
+    pub const fn needs_input(&self) -> bool {
+        self.requires_input_to.is_some()
+    }
+
+    /// Runs the program until it halts, needs input, produces an output value or has
+    /// executed `max_instructions` instructions.
+    ///
+    /// Output produced by this method is not collected by [`Program::run_for_output`].
+    /// Errors are only returned for invalid instructions or memory accesses.
+    pub fn run(&mut self, max_instructions: u32) -> Result<RunState, String> {
+        for _ in 0..max_instructions {
+            if self.halted {
+                return Ok(RunState::Halted);
+            } else if self.requires_input_to.is_some() {
+                return Ok(RunState::NeedsInput);
+            } else if let Some(output) = self.evaluate()? {
+                return Ok(RunState::Output(output));
+            }
+        }
+        Ok(if self.halted {
+            RunState::Halted
+        } else if self.requires_input_to.is_some() {
+            RunState::NeedsInput
+        } else {
+            RunState::BudgetExhausted
+        })
+    }
+
+    /// Runs the program until it halts or needs input, collecting output values to be
+    /// returned by [`Program::run_for_output`].
+    ///
+    /// Fails if the program is already halted or waiting for input, or if it does not stop
+    /// within `max_instructions` instructions.
     pub fn run_until_halt_or_input(&mut self, max_instructions: u32) -> Result<(), String> {
         if self.requires_input_to.is_some() {
             return Err("Cannot run program requiring input".to_string());
@@ -55,7 +189,9 @@ impl Program {
 
         let mut current_instruction = 0;
         while !self.halted && self.requires_input_to.is_none() {
-            self.evaluate()?;
+            if let Some(output) = self.evaluate()? {
+                self.output_values.push(output);
+            }
 
             current_instruction += 1;
             if current_instruction == max_instructions {
@@ -65,6 +201,7 @@ impl Program {
         Ok(())
     }
 
+    /// Runs the program until it halts or needs input, returning the output produced.
     pub fn run_for_output(&mut self) -> Result<Vec<Word>, String> {
         self.run_until_halt_or_input(1_000_000_000)?;
         Ok(std::mem::take(&mut self.output_values))
@@ -75,69 +212,94 @@ impl Program {
         Ok(std::mem::take(&mut self.output_values))
     }
 
+    /// Supplies an input value, either to the pending input instruction or queued for a later one.
     pub fn input(&mut self, input_value: Word) {
         if let Some(save_address) = self.requires_input_to {
-            self.write_memory(save_address, input_value);
+            self.store(save_address, input_value);
             self.requires_input_to = None;
         } else {
             self.input_values.push_back(input_value);
         }
     }
 
+    /// Supplies each byte of an ASCII string as an input value.
     pub fn input_string(&mut self, input_string: &str) {
         input_string.bytes().for_each(|c| {
             self.input(Word::from(c));
         });
     }
 
+    /// Decodes the instruction at the instruction pointer, using the pre-decoded one if
+    /// it has not been modified.
+    fn current_instruction(&mut self) -> DecodedInstruction {
+        let address = self.instruction_pointer;
+        let word = self.read_memory(address);
+        match self.decoded.get(address) {
+            Some(decoded) if decoded.word == word => *decoded,
+            _ => {
+                let decoded = DecodedInstruction::decode(word);
+                // Update the decoding of modified code, unless shared with another clone:
+                if let Some(entry) =
+                    Arc::get_mut(&mut self.decoded).and_then(|all| all.get_mut(address))
+                {
+                    *entry = decoded;
+                }
+                decoded
+            }
+        }
+    }
+
     fn parameter_mode(
         &self,
-        instruction: Word,
-        parameter_position: u32,
+        instruction: &DecodedInstruction,
+        parameter_idx: usize,
     ) -> Result<Parameter, String> {
-        let parameter = self.read_memory(self.instruction_pointer + parameter_position as usize);
-        let divider = 10_i64.pow(parameter_position + 1);
-        let mode = ((instruction / divider) % 10) as u8;
-        let address = match mode {
+        let parameter = self.read_memory(self.instruction_pointer + 1 + parameter_idx);
+        let address = match instruction.modes[parameter_idx] {
             1 => {
                 return Ok(Parameter::Value(parameter));
             }
             2 => parameter + self.relative_base,
             _ => parameter,
         };
-        if !(0..=10_000).contains(&address) {
-            return Err(format!("Bad address: {address}"));
+        match usize::try_from(address) {
+            Ok(address) if address <= self.memory_limit => Ok(Parameter::Address(address)),
+            _ => Err(format!("Bad address: {address}")),
         }
-        Ok(Parameter::Address(address as usize))
     }
 
-    fn output_location(&self, instruction: Word, parameter_position: u32) -> Result<usize, String> {
-        if let Parameter::Address(location) =
-            self.parameter_mode(instruction, parameter_position)?
-        {
+    fn output_location(
+        &self,
+        instruction: &DecodedInstruction,
+        parameter_idx: usize,
+    ) -> Result<usize, String> {
+        if let Parameter::Address(location) = self.parameter_mode(instruction, parameter_idx)? {
             return Ok(location);
         }
         Err("Invalid parameter mode for where to write".to_string())
     }
 
-    fn parameter_value(&self, instruction: Word, parameter_position: u32) -> Result<Word, String> {
-        Ok(
-            match self.parameter_mode(instruction, parameter_position)? {
-                Parameter::Value(value) => value,
-                Parameter::Address(location) => self.read_memory(location),
-            },
-        )
+    fn parameter_value(
+        &self,
+        instruction: &DecodedInstruction,
+        parameter_idx: usize,
+    ) -> Result<Word, String> {
+        Ok(match self.parameter_mode(instruction, parameter_idx)? {
+            Parameter::Value(value) => value,
+            Parameter::Address(location) => self.read_memory(location),
+        })
     }
 
-    fn evaluate(&mut self) -> Result<(), String> {
-        let instruction = self.read_memory(self.instruction_pointer);
-        let opcode = instruction % 100;
+    /// Executes a single instruction, returning its output value if any.
+    fn evaluate(&mut self) -> Result<Option<Word>, String> {
+        let instruction = &self.current_instruction();
+        let opcode = instruction.opcode;
 
         match opcode {
             1 | 2 => {
-                let parameter1 = self.parameter_value(instruction, 1)?;
-                let parameter2 = self.parameter_value(instruction, 2)?;
-                let output_location = self.output_location(instruction, 3)?;
+                let parameter1 = self.parameter_value(instruction, 0)?;
+                let parameter2 = self.parameter_value(instruction, 1)?;
+                let output_location = self.output_location(instruction, 2)?;
                 let value = if opcode == 1 {
                     parameter1.checked_add(parameter2)
                 } else {
@@ -145,14 +307,14 @@ impl Program {
                 }
                 .ok_or("Overflow in program")?;
 
-                self.write_memory(output_location, value);
+                self.store(output_location, value);
                 self.instruction_pointer += 4;
             }
             3 => {
                 // Takes a single integer as input and saves it to the address given by its only parameter.
-                let output_location = self.output_location(instruction, 1)?;
+                let output_location = self.output_location(instruction, 0)?;
                 if let Some(input_value) = self.input_values.pop_front() {
-                    self.write_memory(output_location, input_value);
+                    self.store(output_location, input_value);
                 } else {
                     self.requires_input_to = Some(output_location);
                 }
@@ -160,9 +322,9 @@ impl Program {
             }
             4 => {
                 // Opcode 4 outputs the value of its only parameter.
-                self.output_values
-                    .push(self.parameter_value(instruction, 1)?);
+                let output = self.parameter_value(instruction, 0)?;
                 self.instruction_pointer += 2;
+                return Ok(Some(output));
             }
             5 | 6 => {
                 // Opcode 5 is is jump-if-true: if the first parameter is non-zero, it sets the instruction pointer to the
@@ -170,9 +332,9 @@ impl Program {
                 // Opcode 6 is jump-if-false: if the first parameter is zero, it sets the instruction pointer
                 // to the value from the second parameter. Otherwise, it does nothing.
                 let jump_if = opcode == 5;
-                let parameter_1_true = self.parameter_value(instruction, 1)? != 0;
+                let parameter_1_true = self.parameter_value(instruction, 0)? != 0;
                 if parameter_1_true == jump_if {
-                    self.instruction_pointer = self.parameter_value(instruction, 2)? as usize;
+                    self.instruction_pointer = self.parameter_value(instruction, 1)? as usize;
                 } else {
                     self.instruction_pointer += 3;
                 }
@@ -182,19 +344,19 @@ impl Program {
                 // it stores 1 in the position given by the third parameter. Otherwise, it stores 0.
                 // Opcode 8 is equals: if the first parameter is equal to the second parameter,
                 // it stores 1 in the position given by the third parameter. Otherwise, it stores 0.
-                let parameter_1 = self.parameter_value(instruction, 1);
-                let parameter_2 = self.parameter_value(instruction, 2);
+                let parameter_1 = self.parameter_value(instruction, 0)?;
+                let parameter_2 = self.parameter_value(instruction, 1)?;
                 let output_value = i64::from(
                     (opcode == 7 && (parameter_1 < parameter_2))
                         || (opcode == 8 && (parameter_1 == parameter_2)),
                 );
 
-                let output_location = self.output_location(instruction, 3)?;
-                self.write_memory(output_location, output_value);
+                let output_location = self.output_location(instruction, 2)?;
+                self.store(output_location, output_value);
                 self.instruction_pointer += 4;
             }
             9 => {
-                self.relative_base += self.parameter_value(instruction, 1)?;
+                self.relative_base += self.parameter_value(instruction, 0)?;
                 self.instruction_pointer += 2;
             }
             99 => {
@@ -205,17 +367,72 @@ impl Program {
             }
         }
 
-        Ok(())
+        Ok(None)
+    }
+
+    /// The address of the next instruction to execute.
+    pub const fn instruction_pointer(&self) -> usize {
+        self.instruction_pointer
+    }
+
+    /// The base address of parameters in relative mode, adjusted by opcode 9.
+    pub const fn relative_base(&self) -> Word {
+        self.relative_base
+    }
+
+    /// Input values supplied but not yet read by the program.
+    pub const fn pending_input(&self) -> &VecDeque<Word> {
+        &self.input_values
+    }
+
+    /// The memory written so far, which may be followed by zeros not yet written to.
+    pub fn memory(&self) -> &[Word] {
+        &self.memory
     }
 
     pub fn read_memory(&self, address: usize) -> Word {
         *self.memory.get(address).unwrap_or(&0_i64)
     }
 
-    pub fn write_memory(&mut self, address: usize, value: Word) {
+    /// Writes a value to memory, which grows as needed up to the memory limit.
+    pub fn write_memory(&mut self, address: usize, value: Word) -> Result<(), String> {
+        if address > self.memory_limit {
+            return Err(format!(
+                "Address {address} is above the memory limit of {}",
+                self.memory_limit
+            ));
+        }
+        self.store(address, value);
+        Ok(())
+    }
+
+    /// Writes a value to an address already checked against the memory limit.
+    fn store(&mut self, address: usize, value: Word) {
         if self.memory.len() <= address {
             self.memory.resize(address + 1, 0);
         }
         self.memory[address] = value;
     }
 }
+
+#[test]
+#[allow(clippy::unwrap_used)]
+fn test() {
+    // Self-modifying code - the jump target at address 0 is rewritten after being decoded:
+    let mut program = Program::parse("104,7,1101,3,1,0,1105,1,0").unwrap();
+    assert_eq!(program.run(100), Ok(RunState::Output(7)));
+    assert_eq!(program.run(100), Ok(RunState::Output(1)));
+
+    let mut program = Program::parse("1101,1,1,20000,99").unwrap();
+    program.set_memory_limit(100);
+    assert_eq!(program.run(100), Err("Bad address: 20000".to_string()));
+    assert_eq!(
+        program.write_memory(usize::MAX, 1),
+        Err(format!(
+            "Address {} is above the memory limit of 100",
+            usize::MAX
+        ))
+    );
+    assert_eq!(program.write_memory(100, 1), Ok(()));
+    assert_eq!(program.read_memory(100), 1);
+}
@@ -0,0 +1,307 @@
+//! Assembly of Intcode programs from the text format produced by the [disassembler].
+//!
+//! Each line contains an optional label definition (`name:`), followed by either an
+//! instruction such as `add [rb+1], #-3, [100]` or a data directive such as `data 1, 2, 3`.
+//! Comments start with `;` and continue to the end of the line.
+//!
+//! Operands and data values are integers or label names, optionally followed by an added
+//! or subtracted integer as in `#loop+2` or `[buffer - 1]`.
+//!
+//! [disassembler]: super::disassembler
+
+use std::collections::HashMap;
+
+use super::disassembler::{Mode, INSTRUCTIONS};
+use super::Word;
+
+/// A value which may refer to a label not yet defined.
+struct Expression<'a> {
+    label: Option<&'a str>,
+    offset: Word,
+}
+
+impl<'a> Expression<'a> {
+    fn parse(text: &'a str) -> Result<Self, String> {
+        let text = text.trim();
+        if let Ok(value) = text.parse::<Word>() {
+            return Ok(Self {
+                label: None,
+                offset: value,
+            });
+        }
+        let (label, offset) = match text.find(['+', '-']) {
+            Some(idx) => {
+                // The sign may be followed by spaces, as in `start - 2`:
+                let digits = text[idx + 1..].trim_start();
+                let offset = digits
+                    .starts_with(|c: char| c.is_ascii_digit())
+                    .then(|| digits.parse::<Word>().ok())
+                    .flatten()
+                    .ok_or_else(|| format!("Invalid offset in '{text}'"))?;
+                let offset = if text[idx..].starts_with('-') {
+                    -offset
+                } else {
+                    offset
+                };
+                let label = text[..idx].trim();
+                if label.is_empty() {
+                    return Ok(Self {
+                        label: None,
+                        offset,
+                    });
+                }
+                (label, offset)
+            }
+            None => (text, 0),
+        };
+        validate_label(label)?;
+        Ok(Self {
+            label: Some(label),
+            offset,
+        })
+    }
+
+    fn evaluate(&self, labels: &HashMap<&str, usize>) -> Result<Word, String> {
+        self.label.map_or(Ok(self.offset), |label| {
+            labels
+                .get(label)
+                .map(|&address| address as Word + self.offset)
+                .ok_or_else(|| format!("Undefined label '{label}'"))
+        })
+    }
+}
+
+struct Operand<'a> {
+    mode: Mode,
+    value: Expression<'a>,
+}
+
+impl<'a> Operand<'a> {
+    fn parse(text: &'a str) -> Result<Self, String> {
+        let text = text.trim();
+        if let Some(value) = text.strip_prefix('#') {
+            return Ok(Self {
+                mode: Mode::Immediate,
+                value: Expression::parse(value)?,
+            });
+        }
+        let inner = text
+            .strip_prefix('[')
+            .and_then(|text| text.strip_suffix(']'))
+            .ok_or_else(|| format!("Invalid operand '{text}' - expected #N, [N] or [rb+N]"))?
+            .trim();
+        if let Some(offset) = inner.strip_prefix("rb") {
+            let offset = offset.trim();
+            return Ok(Self {
+                mode: Mode::Relative,
+                value: if offset.is_empty() {
+                    Expression {
+                        label: None,
+                        offset: 0,
+                    }
+                } else if offset.starts_with(['+', '-']) {
+                    Expression::parse(offset.trim_start_matches('+'))?
+                } else {
+                    return Err(format!("Invalid relative operand '{text}'"));
+                },
+            });
+        }
+        Ok(Self {
+            mode: Mode::Position,
+            value: Expression::parse(inner)?,
+        })
+    }
+}
+
+enum Statement<'a> {
+    Instruction {
+        opcode: u8,
+        operands: Vec<Operand<'a>>,
+    },
+    Data(Vec<Expression<'a>>),
+}
+
+impl Statement<'_> {
+    fn size(&self) -> usize {
+        match self {
+            Self::Instruction { operands, .. } => 1 + operands.len(),
+            Self::Data(values) => values.len(),
+        }
+    }
+}
+
+fn validate_label(label: &str) -> Result<(), String> {
+    if label == "rb"
+        || !label.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        || !label.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        return Err(format!("Invalid label '{label}'"));
+    }
+    Ok(())
+}
+
+fn parse_statement(text: &str) -> Result<Statement<'_>, String> {
+    let (mnemonic, arguments) = text
+        .split_once(char::is_whitespace)
+        .map_or((text, ""), |(mnemonic, arguments)| {
+            (mnemonic, arguments.trim())
+        });
+    let arguments = if arguments.is_empty() {
+        Vec::new()
+    } else {
+        arguments.split(',').collect()
+    };
+
+    if mnemonic == "data" {
+        if arguments.is_empty() {
+            return Err("Empty data directive".to_string());
+        }
+        return Ok(Statement::Data(
+            arguments
+                .into_iter()
+                .map(Expression::parse)
+                .collect::<Result<_, _>>()?,
+        ));
+    }
+
+    let &(opcode, _, parameter_count) = INSTRUCTIONS
+        .iter()
+        .find(|(_, name, _)| *name == mnemonic)
+        .ok_or_else(|| format!("Unknown mnemonic '{mnemonic}'"))?;
+    if arguments.len() != parameter_count {
+        return Err(format!(
+            "'{mnemonic}' takes {parameter_count} operands, not {}",
+            arguments.len()
+        ));
+    }
+    let operands = arguments
+        .into_iter()
+        .map(Operand::parse)
+        .collect::<Result<Vec<_>, _>>()?;
+    let written_operand = match opcode {
+        1 | 2 | 7 | 8 => operands.get(2),
+        3 => operands.first(),
+        _ => None,
+    };
+    if written_operand.is_some_and(|operand| operand.mode == Mode::Immediate) {
+        return Err(format!("'{mnemonic}' cannot write to an immediate operand"));
+    }
+    Ok(Statement::Instruction { opcode, operands })
+}
+
+/// Assembles a program into Intcode words, which can be joined with commas to be
+/// parsed by [`Program::parse`](super::Program::parse).
+///
+/// # Example
+/// ```
+/// use advent_of_code::int_code::assembler::assemble;
+/// let words = assemble("
+///     in [value]
+///     mul [value], #2, [value]
+///     out [value]
+///     hlt
+/// value: data 0
+/// ").unwrap();
+/// assert_eq!(words, vec![3, 9, 1002, 9, 2, 9, 4, 9, 99, 0]);
+/// ```
+pub fn assemble(source: &str) -> Result<Vec<Word>, String> {
+    let mut labels = HashMap::new();
+    let mut statements = Vec::new();
+    let mut address = 0;
+
+    for (line_idx, line) in source.lines().enumerate() {
+        let on_line = |error: String| format!("Line {}: {error}", line_idx + 1);
+        let mut text = line.split_once(';').map_or(line, |(code, _)| code).trim();
+
+        if let Some((label, rest)) = text.split_once(':') {
+            let label = label.trim();
+            validate_label(label).map_err(on_line)?;
+            if labels.insert(label, address).is_some() {
+                return Err(on_line(format!("Duplicate label '{label}'")));
+            }
+            text = rest.trim();
+        }
+        if text.is_empty() {
+            continue;
+        }
+
+        let statement = parse_statement(text).map_err(on_line)?;
+        address += statement.size();
+        statements.push((line_idx, statement));
+    }
+
+    let mut words = Vec::with_capacity(address);
+    for (line_idx, statement) in statements {
+        let on_line = |error: String| format!("Line {}: {error}", line_idx + 1);
+        match statement {
+            Statement::Instruction { opcode, operands } => {
+                let mut instruction = Word::from(opcode);
+                let mut mode_multiplier = 100;
+                for operand in &operands {
+                    instruction += mode_multiplier * operand.mode as Word;
+                    mode_multiplier *= 10;
+                }
+                words.push(instruction);
+                for operand in operands {
+                    words.push(operand.value.evaluate(&labels).map_err(on_line)?);
+                }
+            }
+            Statement::Data(values) => {
+                for value in values {
+                    words.push(value.evaluate(&labels).map_err(on_line)?);
+                }
+            }
+        }
+    }
+    Ok(words)
+}
+
+#[test]
+#[allow(clippy::unwrap_used)]
+fn test() {
+    use super::disassembler::disassemble;
+    use super::Program;
+
+    assert_eq!(
+        assemble("start: arb #-2 ; comment\n  jt #1, #start+2\n[x]: hlt",),
+        Err("Line 3: Invalid label '[x]'".to_string())
+    );
+    assert_eq!(
+        assemble("add #1, #2, #3"),
+        Err("Line 1: 'add' cannot write to an immediate operand".to_string())
+    );
+    assert_eq!(
+        assemble("out [x]"),
+        Err("Line 1: Undefined label 'x'".to_string())
+    );
+    assert_eq!(
+        assemble("a: hlt\na: hlt"),
+        Err("Line 2: Duplicate label 'a'".to_string())
+    );
+    assert_eq!(
+        assemble("out #1, #2"),
+        Err("Line 1: 'out' takes 1 operands, not 2".to_string())
+    );
+    assert_eq!(
+        assemble("start: arb #-2\nin [rb]\nadd [rb-1], [rb+ 2], [end]\nlt #start+3, [5], [6]\nend: data start, -7"),
+        Ok(vec![109, -2, 203, 0, 2201, -1, 2, 12, 107, 3, 5, 6, 0, -7])
+    );
+    assert_eq!(
+        assemble("start: jt #1, #start + 2\nadd [rb - 1], #2, [end - 1]\nend: data start - 2"),
+        Ok(vec![1105, 1, 2, 1201, -1, 2, 6, -2])
+    );
+    assert_eq!(
+        assemble("data start + x\nstart: hlt"),
+        Err("Line 1: Invalid offset in 'start + x'".to_string())
+    );
+
+    for day in [
+        include_str!("../day09_input.txt"),
+        include_str!("../day17_input.txt"),
+        include_str!("../day25_input.txt"),
+    ] {
+        let program = Program::parse(day).unwrap();
+        let source = disassemble(&program).to_string();
+        assert_eq!(assemble(&source).unwrap(), program.memory());
+    }
+}
@@ -0,0 +1,332 @@
+//! Control-flow graphs of programs, built from their [disassembly].
+//!
+//! The instructions found are split into basic blocks, which are only entered at their
+//! first instruction and only left after their last one. Blocks are connected by edges
+//! for jumps with immediate targets, for falling through to the next instruction and for
+//! returning from function calls using the common calling convention.
+//!
+//! Instructions writing to code, which is how Intcode programs commonly index arrays, are
+//! listed as self-modifying writes when the address written to is known statically.
+//! Writes found while running a program are collected by [`Coverage`], which can also be
+//! shown in the graph.
+//!
+//! [disassembly]: super::disassembler
+//! [`Coverage`]: super::coverage::Coverage
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use super::coverage::Coverage;
+use super::disassembler::{disassemble_from, Disassembly, Mode};
+use super::{Program, Word};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EdgeKind {
+    /// Execution continues with the instruction following the block.
+    FallThrough,
+    /// A conditional or unconditional jump to an immediate address.
+    Jump,
+    /// The return from a function called at the end of the block, to the return address
+    /// pushed before the call.
+    CallReturn,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Edge {
+    pub target: usize,
+    pub kind: EdgeKind,
+}
+
+/// A write by an instruction to a word which is part of an instruction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct CodeWrite {
+    /// The address of the writing instruction.
+    pub instruction: usize,
+    /// The address written to.
+    pub address: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BasicBlock {
+    /// The addresses of the instructions in the block, in order.
+    pub instructions: Vec<usize>,
+    /// The address following the last instruction of the block.
+    pub end: usize,
+    pub successors: Vec<Edge>,
+    /// If the block ends with a jump to an address only known when running the program,
+    /// such as a function return.
+    pub indirect_jump: bool,
+}
+
+impl BasicBlock {
+    pub fn start(&self) -> usize {
+        self.instructions[0]
+    }
+}
+
+/// The control-flow graph of a program.
+pub struct ControlFlowGraph {
+    disassembly: Disassembly,
+    /// The basic blocks indexed by their start address.
+    blocks: BTreeMap<usize, BasicBlock>,
+    code_writes: Vec<CodeWrite>,
+}
+
+impl ControlFlowGraph {
+    /// Builds the graph of the code reachable from the instruction pointer of a program.
+    pub fn new(program: &Program) -> Self {
+        Self::with_entry_points(program, [program.instruction_pointer()])
+    }
+
+    /// Builds the graph of the code reachable from the given entry points, such as the
+    /// addresses of instructions executed as recorded by [`Coverage`].
+    pub fn with_entry_points(
+        program: &Program,
+        entry_points: impl IntoIterator<Item = usize>,
+    ) -> Self {
+        let entry_points = entry_points.into_iter().collect::<Vec<_>>();
+        let disassembly = disassemble_from(program, entry_points.iter().copied());
+
+        let mut blocks: BTreeMap<usize, BasicBlock> = BTreeMap::new();
+        let mut current: Option<BasicBlock> = None;
+        for (address, instruction) in disassembly.instructions() {
+            let is_leader =
+                entry_points.contains(&address) || disassembly.labels().contains(&address);
+            // Close the current block if this instruction starts a new one:
+            if let Some(mut block) = current.take_if(|block| is_leader || block.end != address) {
+                if block.end == address {
+                    block.successors.push(Edge {
+                        target: address,
+                        kind: EdgeKind::FallThrough,
+                    });
+                }
+                blocks.insert(block.start(), block);
+            }
+
+            let block = current.get_or_insert_with(|| BasicBlock {
+                instructions: Vec::new(),
+                end: address,
+                successors: Vec::new(),
+                indirect_jump: false,
+            });
+            block.instructions.push(address);
+            block.end = address + instruction.size();
+
+            if !matches!(instruction.opcode, 5 | 6 | 99) {
+                continue;
+            }
+            if let Some(mut block) = current.take() {
+                let end = block.end;
+                if let Some(target) = instruction.jump_target() {
+                    if let Ok(target) = usize::try_from(target) {
+                        block.successors.push(Edge {
+                            target,
+                            kind: EdgeKind::Jump,
+                        });
+                    }
+                } else if instruction.opcode != 99 {
+                    block.indirect_jump = true;
+                }
+                if instruction.falls_through() && disassembly.instruction_at(end).is_some() {
+                    block.successors.push(Edge {
+                        target: end,
+                        kind: EdgeKind::FallThrough,
+                    });
+                }
+                if let Some(call) = block.instructions.iter().rev().nth(1) {
+                    let pushed_return_address = disassembly
+                        .instruction_at(*call)
+                        .and_then(|call| call.pushed_return_address());
+                    if instruction.is_unconditional_jump()
+                        && pushed_return_address == Some(end as Word)
+                        && disassembly.instruction_at(end).is_some()
+                    {
+                        block.successors.push(Edge {
+                            target: end,
+                            kind: EdgeKind::CallReturn,
+                        });
+                    }
+                }
+                blocks.insert(block.start(), block);
+            }
+        }
+        if let Some(block) = current {
+            blocks.insert(block.start(), block);
+        }
+
+        let mut code_writes = Vec::new();
+        let code_addresses = blocks
+            .values()
+            .flat_map(|block| block.start()..block.end)
+            .collect::<BTreeSet<_>>();
+        for (address, instruction) in disassembly.instructions() {
+            // Relative mode writes depend on the relative base when running:
+            let written = instruction
+                .written_parameter()
+                .filter(|parameter| parameter.mode == Mode::Position)
+                .and_then(|parameter| usize::try_from(parameter.value).ok());
+            if let Some(written) = written.filter(|written| code_addresses.contains(written)) {
+                code_writes.push(CodeWrite {
+                    instruction: address,
+                    address: written,
+                });
+            }
+        }
+
+        Self {
+            disassembly,
+            blocks,
+            code_writes,
+        }
+    }
+
+    pub const fn disassembly(&self) -> &Disassembly {
+        &self.disassembly
+    }
+
+    /// The basic blocks indexed by their start address.
+    pub const fn blocks(&self) -> &BTreeMap<usize, BasicBlock> {
+        &self.blocks
+    }
+
+    /// The block containing the instruction at `address`, if any.
+    pub fn block_containing(&self, address: usize) -> Option<&BasicBlock> {
+        self.blocks
+            .range(..=address)
+            .next_back()
+            .map(|(_, block)| block)
+            .filter(|block| address < block.end)
+    }
+
+    /// Writes to code at addresses known without running the program.
+    pub fn code_writes(&self) -> &[CodeWrite] {
+        &self.code_writes
+    }
+
+    /// Exports the graph in the Graphviz DOT format, with blocks labeled by their
+    /// disassembly and colored by how many times they were executed if a coverage is given.
+    ///
+    /// Edges of self-modifying writes, both those found statically and those recorded in
+    /// the coverage, are drawn in red from the writing block to the block written to.
+    pub fn to_dot(&self, coverage: Option<&Coverage>) -> String {
+        let mut result = String::from("digraph intcode {\n");
+        result.push_str("    node [shape=box, fontname=\"monospace\"];\n");
+
+        for (&start, block) in &self.blocks {
+            let mut label = format!("{}:\\l", Disassembly::label_name(start));
+            for &address in &block.instructions {
+                if let Some(instruction) = self.disassembly.instruction_at(address) {
+                    let _ = write!(
+                        label,
+                        "{address}: {}\\l",
+                        self.disassembly.format_instruction(address, instruction)
+                    );
+                }
+            }
+            let style = match coverage.map(|coverage| coverage.hits(start)) {
+                Some(0) => ", style=filled, fillcolor=\"#dddddd\"",
+                Some(_) => ", style=filled, fillcolor=\"#c8f0c8\"",
+                None => "",
+            };
+            if let Some(hits) = coverage.map(|coverage| coverage.hits(start)) {
+                let _ = write!(label, "executed {hits} times\\l");
+            }
+            let _ = writeln!(
+                result,
+                "    b{start} [label=\"{}\"{style}];",
+                label.replace('"', "\\\"")
+            );
+            if block.indirect_jump {
+                let _ = writeln!(result, "    b{start} -> indirect{start} [style=dotted];");
+                let _ = writeln!(result, "    indirect{start} [label=\"?\", shape=circle];");
+            }
+            for edge in &block.successors {
+                if !self.blocks.contains_key(&edge.target) {
+                    continue;
+                }
+                let attributes = match edge.kind {
+                    EdgeKind::FallThrough => "",
+                    EdgeKind::Jump => " [label=\"jump\"]",
+                    EdgeKind::CallReturn => " [label=\"return\", style=dashed]",
+                };
+                let _ = writeln!(result, "    b{start} -> b{}{attributes};", edge.target);
+            }
+        }
+
+        let mut code_writes = self.code_writes.clone();
+        if let Some(coverage) = coverage {
+            code_writes.extend(coverage.code_writes().iter().copied());
+        }
+        let mut write_edges = code_writes
+            .iter()
+            .filter_map(|write| {
+                Some((
+                    self.block_containing(write.instruction)?.start(),
+                    self.block_containing(write.address)?.start(),
+                ))
+            })
+            .collect::<Vec<_>>();
+        write_edges.sort_unstable();
+        write_edges.dedup();
+        for (from, to) in write_edges {
+            let _ = writeln!(
+                result,
+                "    b{from} -> b{to} [label=\"writes\", color=red, fontcolor=red, style=dotted];"
+            );
+        }
+
+        result.push_str("}\n");
+        result
+    }
+}
+
+#[test]
+#[allow(clippy::unwrap_used)]
+fn test() {
+    // A function call pushing a return address, a loop patching its own input address,
+    // and a function returning with an indirect jump:
+    let program = Program::parse(
+        "109,100,21101,9,0,0,1105,1,13,4,23,99,99,1001,23,-1,23,1005,23,13,2106,0,0,3",
+    )
+    .unwrap();
+    let graph = ControlFlowGraph::new(&program);
+    let blocks = graph
+        .blocks()
+        .values()
+        .map(|block| (block.instructions.clone(), block.successors.clone()))
+        .collect::<Vec<_>>();
+    let edge = |target, kind| Edge { target, kind };
+    assert_eq!(
+        blocks,
+        vec![
+            (
+                vec![0, 2, 6],
+                vec![edge(13, EdgeKind::Jump), edge(9, EdgeKind::CallReturn)]
+            ),
+            (vec![9, 11], vec![]),
+            (
+                vec![13, 17],
+                vec![edge(13, EdgeKind::Jump), edge(20, EdgeKind::FallThrough)]
+            ),
+            (vec![20], vec![]),
+        ]
+    );
+    assert!(graph.blocks()[&20].indirect_jump);
+    assert_eq!(graph.block_containing(15).map(BasicBlock::start), Some(13));
+    assert_eq!(graph.block_containing(12), None);
+    assert!(graph.code_writes().is_empty());
+
+    // Patches the address of its output instruction before executing it:
+    let program = Program::parse("1101,0,8,5,4,0,99,0,42").unwrap();
+    let graph = ControlFlowGraph::new(&program);
+    assert_eq!(
+        graph.code_writes(),
+        &[CodeWrite {
+            instruction: 0,
+            address: 5
+        }]
+    );
+    let dot = graph.to_dot(None);
+    assert!(dot.contains("b0 [label=\"L0:\\l0: add #0, #8, [5]\\l4: out [0]\\l6: hlt\\l\"];"));
+    assert!(dot.contains("b0 -> b0 [label=\"writes\""));
+}
@@ -0,0 +1,235 @@
+//! Dynamic coverage of the instructions executed by a program.
+
+use std::collections::BTreeSet;
+
+use super::control_flow::CodeWrite;
+use super::disassembler::{disassemble_from, Instruction};
+use super::{Program, RunState};
+
+/// The instructions executed by programs run with [`Coverage::run`], with the number of
+/// times each one was executed and the writes made to code.
+#[derive(Default)]
+pub struct Coverage {
+    /// The number of times the instruction at each address was executed.
+    hits: Vec<u64>,
+    /// If each address has been part of an executed instruction.
+    executed_words: Vec<bool>,
+    /// The address of the instruction last writing to each address.
+    last_writers: Vec<Option<usize>>,
+    code_writes: BTreeSet<CodeWrite>,
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Runs the program as [`Program::run`] does, while recording the instructions executed.
+    ///
+    /// A write to code is recorded both when code is written to after having been executed,
+    /// and when a written word is executed later on.
+    pub fn run(
+        &mut self,
+        program: &mut Program,
+        max_instructions: u32,
+    ) -> Result<RunState, String> {
+        for _ in 0..max_instructions {
+            if program.is_halted() {
+                return Ok(RunState::Halted);
+            } else if program.needs_input() {
+                return Ok(RunState::NeedsInput);
+            }
+
+            let address = program.instruction_pointer();
+            let instruction = Instruction::decode(program.memory(), address);
+            let written_address = instruction
+                .as_ref()
+                .and_then(|instruction| instruction.written_address(program.relative_base()));
+            let state = program.run(1)?;
+
+            let end = address + instruction.map_or(1, |instruction| instruction.size());
+            grow(&mut self.hits, address + 1, 0);
+            self.hits[address] += 1;
+            grow(&mut self.executed_words, end, false);
+            for word_address in address..end {
+                self.executed_words[word_address] = true;
+                if let Some(&Some(writer)) = self.last_writers.get(word_address) {
+                    self.code_writes.insert(CodeWrite {
+                        instruction: writer,
+                        address: word_address,
+                    });
+                }
+            }
+            if let Some(written_address) = written_address {
+                if self.executed_words.get(written_address) == Some(&true) {
+                    self.code_writes.insert(CodeWrite {
+                        instruction: address,
+                        address: written_address,
+                    });
+                }
+                grow(&mut self.last_writers, written_address + 1, None);
+                self.last_writers[written_address] = Some(address);
+            }
+
+            if let RunState::Output(_) = state {
+                return Ok(state);
+            }
+        }
+        Ok(if program.is_halted() {
+            RunState::Halted
+        } else if program.needs_input() {
+            RunState::NeedsInput
+        } else {
+            RunState::BudgetExhausted
+        })
+    }
+
+    /// The number of times the instruction at `address` was executed.
+    pub fn hits(&self, address: usize) -> u64 {
+        self.hits.get(address).copied().unwrap_or(0)
+    }
+
+    /// The addresses of all executed instructions, in order.
+    pub fn executed_addresses(&self) -> impl Iterator<Item = usize> + '_ {
+        self.hits
+            .iter()
+            .enumerate()
+            .filter_map(|(address, &hits)| (hits > 0).then_some(address))
+    }
+
+    /// Writes to code made while running, as the writing instruction and address written.
+    pub const fn code_writes(&self) -> &BTreeSet<CodeWrite> {
+        &self.code_writes
+    }
+
+    /// Disassembles the program with each instruction line starting with the number of
+    /// times it was executed, or `-` if never executed. Instructions which have been
+    /// written to are marked with `*`. A summary of the coverage and the writes to code
+    /// follows the listing.
+    ///
+    /// Code is found both from the instruction pointer of the program and from the
+    /// executed addresses, so that code only reached through indirect jumps is included.
+    pub fn annotated_listing(&self, program: &Program) -> String {
+        let disassembly = disassemble_from(
+            program,
+            std::iter::once(program.instruction_pointer()).chain(self.executed_addresses()),
+        );
+        let written_addresses = self
+            .code_writes
+            .iter()
+            .map(|write| write.address)
+            .collect::<BTreeSet<_>>();
+
+        let mut result = disassembly.listing(|address| {
+            let Some(address) = address else {
+                return " ".repeat(12);
+            };
+            let end = address
+                + disassembly
+                    .instruction_at(address)
+                    .map_or(1, Instruction::size);
+            let marker = if written_addresses.range(address..end).next().is_some() {
+                '*'
+            } else {
+                ' '
+            };
+            match self.hits(address) {
+                0 => format!("{:>10}{marker} ", "-"),
+                hits => format!("{hits:>10}{marker} "),
+            }
+        });
+
+        let instruction_count = disassembly.instructions().count();
+        let executed_count = disassembly
+            .instructions()
+            .filter(|(address, _)| self.hits(*address) > 0)
+            .count();
+        result.push_str(&format!(
+            "; {executed_count} of {instruction_count} instructions executed\n"
+        ));
+        for write in &self.code_writes {
+            result.push_str(&format!(
+                "; Instruction at {} wrote to code at {}\n",
+                write.instruction, write.address
+            ));
+        }
+        result
+    }
+}
+
+fn grow<T: Clone>(values: &mut Vec<T>, len: usize, value: T) {
+    if values.len() < len {
+        values.resize(len, value);
+    }
+}
+
+#[test]
+#[allow(clippy::unwrap_used)]
+fn test() {
+    // A function call pushing a return address and a loop counting down from 3:
+    let mut program = Program::parse(
+        "109,100,21101,9,0,0,1105,1,13,4,23,99,99,1001,23,-1,23,1005,23,13,2106,0,0,3",
+    )
+    .unwrap();
+    let initial_program = program.clone();
+    let mut coverage = Coverage::new();
+    assert_eq!(coverage.run(&mut program, 1000), Ok(RunState::Output(0)));
+    assert_eq!(coverage.run(&mut program, 1000), Ok(RunState::Halted));
+    assert_eq!(
+        coverage
+            .executed_addresses()
+            .map(|address| (address, coverage.hits(address)))
+            .collect::<Vec<_>>(),
+        vec![
+            (0, 1),
+            (2, 1),
+            (6, 1),
+            (9, 1),
+            (11, 1),
+            (13, 3),
+            (17, 3),
+            (20, 1)
+        ]
+    );
+    assert!(coverage.code_writes().is_empty());
+    assert_eq!(
+        coverage.annotated_listing(&initial_program),
+        "         1      arb #100                         ; 0: 109,100
+         1      add #L9, #0, [rb+0]              ; 2: 21101,9,0,0
+         1      jt #1, #L13                      ; 6: 1105,1,13
+            L9:
+         1      out [23]                         ; 9: 4,23
+         1      hlt                              ; 11: 99
+                data 99                          ; 12
+            L13:
+         3      add [23], #-1, [23]              ; 13: 1001,23,-1,23
+         3      jt [23], #L13                    ; 17: 1005,23,13
+         1      jf #0, [rb+0]                    ; 20: 2106,0,0
+                data 3                           ; 23
+; 8 of 8 instructions executed
+"
+    );
+
+    // Patches the address of its output instruction before executing it:
+    let mut program = Program::parse("1101,0,8,5,4,0,99,0,42").unwrap();
+    let initial_program = program.clone();
+    let mut coverage = Coverage::new();
+    assert_eq!(coverage.run(&mut program, 1000), Ok(RunState::Output(42)));
+    assert_eq!(
+        coverage.code_writes().iter().collect::<Vec<_>>(),
+        vec![&CodeWrite {
+            instruction: 0,
+            address: 5
+        }]
+    );
+    assert_eq!(
+        coverage.annotated_listing(&initial_program),
+        "         1      add #0, #8, [5]                  ; 0: 1101,0,8,5
+         1*     out [0]                          ; 4: 4,0
+         -      hlt                              ; 6: 99
+                data 0, 42                       ; 7
+; 2 of 3 instructions executed
+; Instruction at 0 wrote to code at 5
+"
+    );
+}
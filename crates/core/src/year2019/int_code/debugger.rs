@@ -0,0 +1,238 @@
+//! A debugger for stepping through Intcode programs.
+
+use std::collections::BTreeSet;
+use std::io::Write;
+
+use super::disassembler::Instruction;
+use super::{Program, RunState, Word};
+
+/// Why execution stopped after [`Debugger::step`] or [`Debugger::resume`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StopReason {
+    /// A single instruction was executed.
+    Stepped,
+    /// The instruction at a breakpoint address is about to be executed.
+    Breakpoint(usize),
+    /// An instruction with a breakpoint opcode is about to be executed.
+    OpcodeBreakpoint(u8),
+    /// A watched memory location was written to.
+    Watchpoint {
+        address: usize,
+        old_value: Word,
+        new_value: Word,
+    },
+    Halted,
+    NeedsInput,
+    /// The maximum number of instructions given to [`Debugger::resume`] were executed.
+    BudgetExhausted,
+}
+
+/// Runs a program with breakpoints, watchpoints and an optional execution trace.
+pub struct Debugger {
+    program: Program,
+    breakpoints: BTreeSet<usize>,
+    opcode_breakpoints: BTreeSet<u8>,
+    watchpoints: BTreeSet<usize>,
+    output: Vec<Word>,
+    executed_instructions: u64,
+    trace: Option<Box<dyn Write>>,
+}
+
+impl Debugger {
+    pub fn new(program: Program) -> Self {
+        Self {
+            program,
+            breakpoints: BTreeSet::new(),
+            opcode_breakpoints: BTreeSet::new(),
+            watchpoints: BTreeSet::new(),
+            output: Vec::new(),
+            executed_instructions: 0,
+            trace: None,
+        }
+    }
+
+    pub const fn program(&self) -> &Program {
+        &self.program
+    }
+
+    /// Gives access to the program, e.g. to supply input or modify memory.
+    pub fn program_mut(&mut self) -> &mut Program {
+        &mut self.program
+    }
+
+    pub const fn executed_instructions(&self) -> u64 {
+        self.executed_instructions
+    }
+
+    /// Output values produced by the program and not yet taken.
+    pub fn pending_output(&self) -> &[Word] {
+        &self.output
+    }
+
+    pub fn take_output(&mut self) -> Vec<Word> {
+        std::mem::take(&mut self.output)
+    }
+
+    pub const fn breakpoints(&self) -> &BTreeSet<usize> {
+        &self.breakpoints
+    }
+
+    pub const fn opcode_breakpoints(&self) -> &BTreeSet<u8> {
+        &self.opcode_breakpoints
+    }
+
+    pub const fn watchpoints(&self) -> &BTreeSet<usize> {
+        &self.watchpoints
+    }
+
+    /// Toggles a breakpoint at an address, returning whether it is now set.
+    pub fn toggle_breakpoint(&mut self, address: usize) -> bool {
+        toggle(&mut self.breakpoints, address)
+    }
+
+    /// Toggles a breakpoint on all instructions with an opcode, returning whether it is now set.
+    pub fn toggle_opcode_breakpoint(&mut self, opcode: u8) -> bool {
+        toggle(&mut self.opcode_breakpoints, opcode)
+    }
+
+    /// Toggles a watchpoint on a memory address, returning whether it is now set.
+    pub fn toggle_watchpoint(&mut self, address: usize) -> bool {
+        toggle(&mut self.watchpoints, address)
+    }
+
+    /// Writes each executed instruction as a line to `trace`, or stops tracing if `None`.
+    pub fn set_trace(&mut self, trace: Option<Box<dyn Write>>) {
+        self.trace = trace;
+    }
+
+    /// Executes a single instruction, unless the program is halted or needs input.
+    pub fn step(&mut self) -> Result<StopReason, String> {
+        if self.program.is_halted() {
+            return Ok(StopReason::Halted);
+        } else if self.program.needs_input() {
+            return Ok(StopReason::NeedsInput);
+        }
+
+        let address = self.program.instruction_pointer();
+        let instruction = Instruction::decode(self.program.memory(), address);
+        let written_address = instruction
+            .as_ref()
+            .and_then(|instruction| instruction.written_address(self.program.relative_base()));
+        let old_value = written_address.map(|address| self.program.read_memory(address));
+
+        if let (Some(trace), Some(instruction)) = (&mut self.trace, &instruction) {
+            writeln!(
+                trace,
+                "{address}: {:<32} ; rb={}",
+                instruction.to_string(),
+                self.program.relative_base()
+            )
+            .map_err(|error| format!("Unable to write trace: {error}"))?;
+        }
+
+        let state = self.program.run(1)?;
+        self.executed_instructions += 1;
+        if let RunState::Output(value) = state {
+            self.output.push(value);
+        }
+
+        if let (Some(address), Some(old_value)) = (written_address, old_value) {
+            // An input instruction with no input available does not write until resumed:
+            if self.watchpoints.contains(&address) && !self.program.needs_input() {
+                return Ok(StopReason::Watchpoint {
+                    address,
+                    old_value,
+                    new_value: self.program.read_memory(address),
+                });
+            }
+        }
+        Ok(match state {
+            RunState::Halted => StopReason::Halted,
+            RunState::NeedsInput => StopReason::NeedsInput,
+            RunState::Output(_) | RunState::BudgetExhausted => StopReason::Stepped,
+        })
+    }
+
+    /// Executes instructions until a breakpoint or watchpoint is hit, the program halts
+    /// or needs input, or `max_instructions` instructions have been executed.
+    ///
+    /// A breakpoint at the current instruction does not stop execution, so that resuming
+    /// from a breakpoint makes progress.
+    pub fn resume(&mut self, max_instructions: u64) -> Result<StopReason, String> {
+        for executed in 0..max_instructions {
+            if executed > 0 {
+                let address = self.program.instruction_pointer();
+                if self.breakpoints.contains(&address) {
+                    return Ok(StopReason::Breakpoint(address));
+                }
+                let opcode = (self.program.read_memory(address) % 100) as u8;
+                if self.opcode_breakpoints.contains(&opcode) {
+                    return Ok(StopReason::OpcodeBreakpoint(opcode));
+                }
+            }
+            match self.step()? {
+                StopReason::Stepped => {}
+                reason => return Ok(reason),
+            }
+        }
+        Ok(StopReason::BudgetExhausted)
+    }
+}
+
+fn toggle<T: Ord>(set: &mut BTreeSet<T>, value: T) -> bool {
+    if set.remove(&value) {
+        false
+    } else {
+        set.insert(value);
+        true
+    }
+}
+
+#[test]
+#[allow(clippy::unwrap_used)]
+fn test() {
+    use super::assembler::assemble;
+
+    let words = assemble(
+        "
+        in [counter]
+    loop:
+        out [counter]
+        add [counter], #-1, [counter]
+        jt [counter], #loop
+        hlt
+    counter: data 0
+    ",
+    )
+    .unwrap();
+    let words = words.iter().map(ToString::to_string).collect::<Vec<_>>();
+    let mut debugger = Debugger::new(Program::parse(&words.join(",")).unwrap());
+
+    assert_eq!(debugger.resume(1000), Ok(StopReason::NeedsInput));
+    debugger.program_mut().input(3);
+
+    assert!(debugger.toggle_breakpoint(4));
+    assert_eq!(debugger.resume(1000), Ok(StopReason::Breakpoint(4)));
+    assert_eq!(debugger.take_output(), vec![3]);
+    assert_eq!(debugger.step(), Ok(StopReason::Stepped));
+    assert_eq!(debugger.program().read_memory(12), 2);
+    assert!(!debugger.toggle_breakpoint(4));
+
+    assert!(debugger.toggle_watchpoint(12));
+    assert_eq!(
+        debugger.resume(1000),
+        Ok(StopReason::Watchpoint {
+            address: 12,
+            old_value: 2,
+            new_value: 1
+        })
+    );
+    assert!(!debugger.toggle_watchpoint(12));
+
+    assert!(debugger.toggle_opcode_breakpoint(99));
+    assert_eq!(debugger.resume(1000), Ok(StopReason::OpcodeBreakpoint(99)));
+    assert_eq!(debugger.take_output(), vec![2, 1]);
+    assert_eq!(debugger.resume(1000), Ok(StopReason::Halted));
+    assert_eq!(debugger.resume(1000), Ok(StopReason::Halted));
+    assert_eq!(debugger.executed_instructions(), 11);
+}
@@ -0,0 +1,422 @@
+//! Disassembly of Intcode programs into readable assembly.
+//!
+//! Instructions are written as a mnemonic followed by operands, where `[N]` is a
+//! position mode parameter, `#N` an immediate one and `[rb+N]` a relative one:
+//!
+//! | Opcode | Mnemonic | Operation                         |
+//! |--------|----------|-----------------------------------|
+//! | 1      | `add`    | `c = a + b`                       |
+//! | 2      | `mul`    | `c = a * b`                       |
+//! | 3      | `in`     | `a = input`                       |
+//! | 4      | `out`    | `output a`                        |
+//! | 5      | `jt`     | `if a != 0 jump to b`             |
+//! | 6      | `jf`     | `if a == 0 jump to b`             |
+//! | 7      | `lt`     | `c = if a < b { 1 } else { 0 }`   |
+//! | 8      | `eq`     | `c = if a == b { 1 } else { 0 }`  |
+//! | 9      | `arb`    | `relative_base += a`              |
+//! | 99     | `hlt`    | `halt`                            |
+//!
+//! Memory which is not found to be code is written as `data` directives.
+
+use std::collections::BTreeSet;
+use std::fmt::{Display, Formatter, Write};
+
+use super::{Program, Word};
+
+/// The number of data words written on each `data` line.
+const DATA_WORDS_PER_LINE: usize = 8;
+
+/// The opcode, mnemonic and number of parameters of each instruction.
+pub const INSTRUCTIONS: [(u8, &str, usize); 10] = [
+    (1, "add", 3),
+    (2, "mul", 3),
+    (3, "in", 1),
+    (4, "out", 1),
+    (5, "jt", 2),
+    (6, "jf", 2),
+    (7, "lt", 3),
+    (8, "eq", 3),
+    (9, "arb", 1),
+    (99, "hlt", 0),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    Position,
+    Immediate,
+    Relative,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Parameter {
+    pub mode: Mode,
+    pub value: Word,
+}
+
+impl Display for Parameter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.mode {
+            Mode::Position => write!(f, "[{}]", self.value),
+            Mode::Immediate => write!(f, "#{}", self.value),
+            Mode::Relative if self.value < 0 => write!(f, "[rb{}]", self.value),
+            Mode::Relative => write!(f, "[rb+{}]", self.value),
+        }
+    }
+}
+
+/// A decoded instruction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Instruction {
+    pub opcode: u8,
+    pub parameters: Vec<Parameter>,
+}
+
+impl Instruction {
+    /// Decodes the instruction at `address`, if it is a valid one.
+    ///
+    /// Instructions with unknown opcodes or parameter modes, writing to immediate
+    /// parameters or extending past the end of memory are not valid.
+    pub fn decode(memory: &[Word], address: usize) -> Option<Self> {
+        let word = *memory.get(address)?;
+        if word < 0 {
+            return None;
+        }
+        let opcode = (word % 100) as u8;
+        let &(_, _, parameter_count) = INSTRUCTIONS.iter().find(|(o, _, _)| *o == opcode)?;
+        if word / 10_i64.pow(2 + parameter_count as u32) != 0 {
+            return None;
+        }
+
+        let mut parameters = Vec::with_capacity(parameter_count);
+        for idx in 0..parameter_count {
+            let mode = match (word / 10_i64.pow(2 + idx as u32)) % 10 {
+                0 => Mode::Position,
+                1 => Mode::Immediate,
+                2 => Mode::Relative,
+                _ => return None,
+            };
+            let value = *memory.get(address + 1 + idx)?;
+            parameters.push(Parameter { mode, value });
+        }
+
+        let instruction = Self { opcode, parameters };
+        if instruction
+            .written_parameter()
+            .is_some_and(|parameter| parameter.mode == Mode::Immediate)
+        {
+            return None;
+        }
+        Some(instruction)
+    }
+
+    pub fn mnemonic(&self) -> &'static str {
+        INSTRUCTIONS
+            .iter()
+            .find(|(opcode, _, _)| *opcode == self.opcode)
+            .map_or("???", |(_, mnemonic, _)| mnemonic)
+    }
+
+    /// The number of words occupied by the instruction.
+    pub fn size(&self) -> usize {
+        1 + self.parameters.len()
+    }
+
+    /// The parameter giving the address written to, if any.
+    pub fn written_parameter(&self) -> Option<&Parameter> {
+        match self.opcode {
+            1 | 2 | 7 | 8 => self.parameters.get(2),
+            3 => self.parameters.first(),
+            _ => None,
+        }
+    }
+
+    /// The address written to when executed with the given relative base, if any.
+    pub fn written_address(&self, relative_base: Word) -> Option<usize> {
+        let parameter = self.written_parameter()?;
+        let address = match parameter.mode {
+            Mode::Position => parameter.value,
+            Mode::Relative => parameter.value + relative_base,
+            Mode::Immediate => return None,
+        };
+        usize::try_from(address).ok()
+    }
+
+    /// The target of a jump instruction, if it is known without running the program.
+    pub fn jump_target(&self) -> Option<Word> {
+        match (self.opcode, self.parameters.get(1)) {
+            (
+                5 | 6,
+                Some(Parameter {
+                    mode: Mode::Immediate,
+                    value,
+                }),
+            ) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// If the jump is always taken, due to an immediate condition.
+    pub fn is_unconditional_jump(&self) -> bool {
+        match (self.opcode, self.parameters.first()) {
+            (
+                jump_opcode @ (5 | 6),
+                Some(Parameter {
+                    mode: Mode::Immediate,
+                    value,
+                }),
+            ) => (*value != 0) == (jump_opcode == 5),
+            _ => false,
+        }
+    }
+
+    /// If execution may continue with the instruction directly following this one.
+    pub fn falls_through(&self) -> bool {
+        self.opcode != 99 && !self.is_unconditional_jump()
+    }
+
+    /// The first parameter of an `add #N, #0, [..]` or `mul #N, #1, [..]` instruction,
+    /// which is how return addresses are commonly pushed before calling a function.
+    pub(super) fn pushed_return_address(&self) -> Option<Word> {
+        match (self.opcode, &self.parameters[..]) {
+            (opcode @ (1 | 2), [a, b, _])
+                if a.mode == Mode::Immediate
+                    && b.mode == Mode::Immediate
+                    && b.value == Word::from(opcode - 1) =>
+            {
+                Some(a.value)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Display for Instruction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.mnemonic())?;
+        for (idx, parameter) in self.parameters.iter().enumerate() {
+            write!(f, "{}{parameter}", if idx == 0 { " " } else { ", " })?;
+        }
+        Ok(())
+    }
+}
+
+/// A disassembled program, which is displayed as assembly text.
+pub struct Disassembly {
+    memory: Vec<Word>,
+    /// The decoded instruction starting at each address, if any.
+    instructions: Vec<Option<Instruction>>,
+    /// Code addresses referred to by jumps or pushed return addresses.
+    labels: BTreeSet<usize>,
+    /// Immediate parameters, as (instruction address, parameter index), referring to a label.
+    label_references: BTreeSet<(usize, usize)>,
+}
+
+impl Disassembly {
+    /// The instruction starting at `address`, if it has been found to be code.
+    pub fn instruction_at(&self, address: usize) -> Option<&Instruction> {
+        self.instructions.get(address)?.as_ref()
+    }
+
+    /// The instructions found, with their addresses, in address order.
+    pub fn instructions(&self) -> impl Iterator<Item = (usize, &Instruction)> {
+        self.instructions
+            .iter()
+            .enumerate()
+            .filter_map(|(address, instruction)| Some((address, instruction.as_ref()?)))
+    }
+
+    /// The addresses which have been given a label.
+    pub const fn labels(&self) -> &BTreeSet<usize> {
+        &self.labels
+    }
+
+    pub fn label_name(address: usize) -> String {
+        format!("L{address}")
+    }
+
+    /// Formats an instruction, with labels in place of the addresses they refer to.
+    pub(super) fn format_instruction(&self, address: usize, instruction: &Instruction) -> String {
+        let mut result = instruction.mnemonic().to_string();
+        for (idx, parameter) in instruction.parameters.iter().enumerate() {
+            result.push_str(if idx == 0 { " " } else { ", " });
+            if self.label_references.contains(&(address, idx)) {
+                let _ = write!(result, "#{}", Self::label_name(parameter.value as usize));
+            } else {
+                let _ = write!(result, "{parameter}");
+            }
+        }
+        result
+    }
+
+    /// Formats the disassembly as [`Display`] does, but with each line starting with
+    /// `prefix`, which is given the address of the instruction on the line or `None` for
+    /// lines with labels or data.
+    pub fn listing(&self, prefix: impl Fn(Option<usize>) -> String) -> String {
+        let mut result = String::new();
+        let mut address = 0;
+        while address < self.memory.len() {
+            if self.labels.contains(&address) {
+                let _ = writeln!(result, "{}{}:", prefix(None), Self::label_name(address));
+            }
+            if let Some(instruction) = self.instruction_at(address) {
+                let end = address + instruction.size();
+                let words = self.memory[address..end]
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>();
+                let _ = writeln!(
+                    result,
+                    "{}    {:<32} ; {address}: {}",
+                    prefix(Some(address)),
+                    self.format_instruction(address, instruction),
+                    words.join(",")
+                );
+                address = end;
+            } else {
+                let mut end = address + 1;
+                while end < self.memory.len()
+                    && end - address < DATA_WORDS_PER_LINE
+                    && self.instructions[end].is_none()
+                    && !self.labels.contains(&end)
+                {
+                    end += 1;
+                }
+                let words = self.memory[address..end]
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>();
+                let _ = writeln!(
+                    result,
+                    "{}    {:<32} ; {address}",
+                    prefix(None),
+                    format!("data {}", words.join(", "))
+                );
+                address = end;
+            }
+        }
+        result
+    }
+}
+
+impl Display for Disassembly {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.listing(|_| String::new()))
+    }
+}
+
+/// Disassembles the memory of a program.
+///
+/// Code is found by following the control flow from the current instruction pointer,
+/// including jumps to immediate addresses. The common calling convention of pushing an
+/// immediate return address before an unconditional jump is also followed, so that the
+/// code after function calls is found. Memory not reached in this way is likely data.
+///
+/// As the program may modify itself while running, the result only reflects the current
+/// state of memory.
+pub fn disassemble(program: &Program) -> Disassembly {
+    disassemble_from(program, [program.instruction_pointer()])
+}
+
+/// Disassembles the memory of a program as [`disassemble`] does, but following the control
+/// flow from the given entry points, such as addresses known to have been executed.
+pub fn disassemble_from(
+    program: &Program,
+    entry_points: impl IntoIterator<Item = usize>,
+) -> Disassembly {
+    let memory = program.memory().to_vec();
+    let mut instructions = vec![None; memory.len()];
+    let mut covered = vec![false; memory.len()];
+    // Immediate parameters referring to code, as (instruction address, parameter index, target):
+    let mut references = Vec::new();
+
+    let mut to_visit = entry_points.into_iter().collect::<Vec<_>>();
+    while let Some(address) = to_visit.pop() {
+        if address >= memory.len() || covered[address] {
+            continue;
+        }
+        let Some(instruction) = Instruction::decode(&memory, address) else {
+            continue;
+        };
+        let end = address + instruction.size();
+        if covered[address..end].iter().any(|&c| c) {
+            continue;
+        }
+        covered[address..end].fill(true);
+
+        if let Some(target) = instruction.jump_target().and_then(to_address) {
+            references.push((address, 1, target));
+            to_visit.push(target);
+        }
+        if instruction.falls_through() {
+            to_visit.push(end);
+        }
+        if let (Some(return_address), Some(next)) = (
+            instruction.pushed_return_address().and_then(to_address),
+            Instruction::decode(&memory, end),
+        ) {
+            if next.is_unconditional_jump() && return_address == end + next.size() {
+                references.push((address, 0, return_address));
+                to_visit.push(return_address);
+            }
+        }
+        instructions[address] = Some(instruction);
+    }
+
+    let mut labels = BTreeSet::new();
+    let mut label_references = BTreeSet::new();
+    for (address, parameter_idx, target) in references {
+        // Only refer to addresses which turned out to be the start of an instruction:
+        if instructions.get(target).is_some_and(Option::is_some) {
+            labels.insert(target);
+            label_references.insert((address, parameter_idx));
+        }
+    }
+
+    Disassembly {
+        memory,
+        instructions,
+        labels,
+        label_references,
+    }
+}
+
+fn to_address(value: Word) -> Option<usize> {
+    usize::try_from(value).ok()
+}
+
+#[test]
+#[allow(clippy::unwrap_used)]
+fn test() {
+    let program = Program::parse("1,0,0,3,1,1,2,3,1,3,4,3,1,5,0,3,2,1,10,19,99").unwrap();
+    assert_eq!(
+        disassemble(&program).to_string(),
+        "    add [0], [0], [3]                ; 0: 1,0,0,3
+    add [1], [2], [3]                ; 4: 1,1,2,3
+    add [3], [4], [3]                ; 8: 1,3,4,3
+    add [5], [0], [3]                ; 12: 1,5,0,3
+    mul [1], [10], [19]              ; 16: 2,1,10,19
+    hlt                              ; 20: 99
+"
+    );
+
+    // A function call pushing a return address, a loop and trailing data:
+    let program =
+        Program::parse("109,100,21101,9,0,0,1105,1,12,4,22,99,1001,22,-1,22,1005,22,12,2106,0,0,3")
+            .unwrap();
+    assert_eq!(program.clone().run_for_output(), Ok(vec![0]));
+    assert_eq!(
+        disassemble(&program).to_string(),
+        "    arb #100                         ; 0: 109,100
+    add #L9, #0, [rb+0]              ; 2: 21101,9,0,0
+    jt #1, #L12                      ; 6: 1105,1,12
+L9:
+    out [22]                         ; 9: 4,22
+    hlt                              ; 11: 99
+L12:
+    add [22], #-1, [22]              ; 12: 1001,22,-1,22
+    jt [22], #L12                    ; 16: 1005,22,12
+    jf #0, [rb+0]                    ; 19: 2106,0,0
+    data 3                           ; 22
+"
+    );
+}
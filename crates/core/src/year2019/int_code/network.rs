@@ -0,0 +1,560 @@
+//! A runtime for networks of programs exchanging messages, such as the amplifiers of
+//! [day 7](https://adventofcode.com/2019/day/7) and the computers of
+//! [day 23](https://adventofcode.com/2019/day/23).
+//!
+//! Output values of each machine are grouped into [`Message`]s, which are handed to a
+//! [`Router`] deciding which machines should receive what. The router is also told when
+//! the network is idle, which is when no machine can make progress until it is sent more
+//! input.
+//!
+//! # Example
+//! ```
+//! use advent_of_code::int_code::network::{Flow, Network, Outbox, Router, Scheduler};
+//! use advent_of_code::int_code::{Program, Word};
+//!
+//! /// Passes values along a chain of machines, returning the last value output.
+//! struct Chain {
+//!     last_value: Word,
+//! }
+//!
+//! impl Router for Chain {
+//!     type Output = Word;
+//!     type Input = Word;
+//!     type Result = Word;
+//!
+//!     fn route(
+//!         &mut self,
+//!         from: usize,
+//!         value: Word,
+//!         outbox: &mut Outbox<Word>,
+//!     ) -> Result<Flow<Word>, String> {
+//!         if from + 1 == outbox.machine_count() {
+//!             self.last_value = value;
+//!         } else {
+//!             outbox.send(from + 1, value);
+//!         }
+//!         Ok(Flow::Continue)
+//!     }
+//!
+//!     fn idle(&mut self, _outbox: &mut Outbox<Word>) -> Result<Flow<Word>, String> {
+//!         Ok(Flow::Stop(self.last_value))
+//!     }
+//! }
+//!
+//! // Each machine reads a value and outputs it doubled:
+//! let program = Program::parse("3,9,102,2,9,10,4,10,99,0,0").unwrap();
+//! let mut network = Network::new(vec![program; 3]);
+//! network.send(0, 5);
+//! let mut router = Chain { last_value: 0 };
+//! assert_eq!(network.run(&mut router, Scheduler::RoundRobin), Ok(40));
+//! ```
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+
+use super::{Program, RunState, Word};
+
+/// A fixed number of words sent to or from a machine.
+pub trait Message: Sized + Send {
+    /// The number of words making up the message.
+    const WORDS: usize;
+
+    /// Creates a message from exactly [`Message::WORDS`] words.
+    fn decode(words: &[Word]) -> Self;
+
+    /// Appends the words of the message.
+    fn encode(self, words: &mut Vec<Word>);
+}
+
+impl Message for Word {
+    const WORDS: usize = 1;
+
+    fn decode(words: &[Word]) -> Self {
+        words[0]
+    }
+
+    fn encode(self, words: &mut Vec<Word>) {
+        words.push(self);
+    }
+}
+
+impl Message for (Word, Word) {
+    const WORDS: usize = 2;
+
+    fn decode(words: &[Word]) -> Self {
+        (words[0], words[1])
+    }
+
+    fn encode(self, words: &mut Vec<Word>) {
+        words.extend([self.0, self.1]);
+    }
+}
+
+impl Message for (Word, Word, Word) {
+    const WORDS: usize = 3;
+
+    fn decode(words: &[Word]) -> Self {
+        (words[0], words[1], words[2])
+    }
+
+    fn encode(self, words: &mut Vec<Word>) {
+        words.extend([self.0, self.1, self.2]);
+    }
+}
+
+/// Whether a network should continue running after a call to a [`Router`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Flow<T> {
+    Continue,
+    /// Stops the network, which returns the given result.
+    Stop(T),
+}
+
+/// Messages to be delivered to machines once a [`Router`] method returns.
+pub struct Outbox<M> {
+    machine_count: usize,
+    messages: Vec<(usize, M)>,
+}
+
+impl<M> Outbox<M> {
+    /// Sends a message to the machine with the given index.
+    pub fn send(&mut self, to: usize, message: M) {
+        self.messages.push((to, message));
+    }
+
+    /// The number of machines in the network.
+    pub const fn machine_count(&self) -> usize {
+        self.machine_count
+    }
+
+    fn take(&mut self) -> Result<Vec<(usize, M)>, String> {
+        if let Some((to, _)) = self
+            .messages
+            .iter()
+            .find(|(to, _)| *to >= self.machine_count)
+        {
+            return Err(format!("Message sent to non-existing machine {to}"));
+        }
+        Ok(std::mem::take(&mut self.messages))
+    }
+}
+
+/// Decides where the messages output by machines should go.
+pub trait Router {
+    /// Messages output by machines.
+    type Output: Message;
+    /// Messages sent to machines.
+    type Input: Message;
+    /// The result of running the network.
+    type Result;
+
+    /// The value read by a machine needing input when no message has been sent to it,
+    /// or `None` if it should wait for a message.
+    ///
+    /// A machine which has read this value and needs input again without having produced
+    /// any output is considered idle.
+    fn empty_input(&self) -> Option<Word> {
+        None
+    }
+
+    /// Called with each message output by a machine.
+    fn route(
+        &mut self,
+        from: usize,
+        message: Self::Output,
+        outbox: &mut Outbox<Self::Input>,
+    ) -> Result<Flow<Self::Result>, String>;
+
+    /// Called when all machines are halted or waiting for messages, with none in transit.
+    ///
+    /// Returning [`Flow::Continue`] without sending a message is an error, as the network
+    /// would wait forever.
+    fn idle(&mut self, outbox: &mut Outbox<Self::Input>) -> Result<Flow<Self::Result>, String>;
+}
+
+/// How the machines of a network are executed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scheduler {
+    /// Runs one machine at a time on the current thread, each until it needs input.
+    RoundRobin,
+    /// Runs each machine on its own thread, with the router on the current thread.
+    Threaded,
+}
+
+/// A number of programs running together, exchanging messages through a [`Router`].
+pub struct Network {
+    machines: Vec<Program>,
+    instruction_limit: u32,
+}
+
+/// What a machine thread reports to the router.
+enum Event<M> {
+    Output(usize, M),
+    /// The machine waits for a message, having received `received` messages in total.
+    Idle {
+        machine: usize,
+        received: u64,
+    },
+    Halted(usize),
+    Error(String),
+}
+
+impl Network {
+    pub const fn new(machines: Vec<Program>) -> Self {
+        Self {
+            machines,
+            instruction_limit: 1_000_000_000,
+        }
+    }
+
+    /// Sets the maximum number of instructions a machine may execute without producing
+    /// output or needing input, to abort programs stuck in a loop.
+    pub fn set_instruction_limit(&mut self, instruction_limit: u32) {
+        self.instruction_limit = instruction_limit;
+    }
+
+    pub fn machines(&self) -> &[Program] {
+        &self.machines
+    }
+
+    /// Supplies a message to a machine before running the network, such as its address.
+    pub fn send<M: Message>(&mut self, to: usize, message: M) {
+        let mut words = Vec::with_capacity(M::WORDS);
+        message.encode(&mut words);
+        for word in words {
+            self.machines[to].input(word);
+        }
+    }
+
+    /// Runs the machines until the router stops the network.
+    ///
+    /// The machines keep their state afterwards, so that the network can be inspected.
+    pub fn run<R: Router>(
+        &mut self,
+        router: &mut R,
+        scheduler: Scheduler,
+    ) -> Result<R::Result, String> {
+        match scheduler {
+            Scheduler::RoundRobin => self.run_round_robin(router),
+            Scheduler::Threaded => self.run_threaded(router),
+        }
+    }
+
+    fn run_round_robin<R: Router>(&mut self, router: &mut R) -> Result<R::Result, String> {
+        let machine_count = self.machines.len();
+        let mut outbox = Outbox {
+            machine_count,
+            messages: Vec::new(),
+        };
+        let mut output_words = vec![Vec::with_capacity(R::Output::WORDS); machine_count];
+        // If each machine has read the empty input value since it last did anything else:
+        let mut read_empty_input = vec![false; machine_count];
+        let mut words = Vec::new();
+
+        loop {
+            for machine in 0..machine_count {
+                let program = &mut self.machines[machine];
+                if program.needs_input() {
+                    match router.empty_input() {
+                        Some(value) if !read_empty_input[machine] => {
+                            program.input(value);
+                            read_empty_input[machine] = true;
+                        }
+                        _ => continue,
+                    }
+                }
+
+                loop {
+                    match self.machines[machine].run(self.instruction_limit)? {
+                        RunState::Output(value) => {
+                            read_empty_input[machine] = false;
+                            let output = &mut output_words[machine];
+                            output.push(value);
+                            if output.len() < R::Output::WORDS {
+                                continue;
+                            }
+                            let message = R::Output::decode(output);
+                            output.clear();
+
+                            let flow = router.route(machine, message, &mut outbox)?;
+                            self.deliver(outbox.take()?, &mut read_empty_input, &mut words);
+                            if let Flow::Stop(result) = flow {
+                                return Ok(result);
+                            }
+                        }
+                        RunState::Halted | RunState::NeedsInput => break,
+                        RunState::BudgetExhausted => {
+                            return Err(format!(
+                                "Machine {machine} aborted after {} instructions",
+                                self.instruction_limit
+                            ));
+                        }
+                    }
+                }
+            }
+
+            let idle = self.machines.iter().enumerate().all(|(machine, program)| {
+                program.is_halted()
+                    || (program.needs_input()
+                        && (read_empty_input[machine] || router.empty_input().is_none()))
+            });
+            if idle {
+                let flow = router.idle(&mut outbox)?;
+                let messages = outbox.take()?;
+                if let Flow::Stop(result) = flow {
+                    return Ok(result);
+                } else if messages.is_empty() {
+                    return Err("Network deadlocked - idle without any message sent".to_string());
+                }
+                self.deliver(messages, &mut read_empty_input, &mut words);
+            }
+        }
+    }
+
+    fn deliver<M: Message>(
+        &mut self,
+        messages: Vec<(usize, M)>,
+        read_empty_input: &mut [bool],
+        words: &mut Vec<Word>,
+    ) {
+        for (to, message) in messages {
+            words.clear();
+            message.encode(words);
+            for &word in words.iter() {
+                self.machines[to].input(word);
+            }
+            read_empty_input[to] = false;
+        }
+    }
+
+    fn run_threaded<R: Router>(&mut self, router: &mut R) -> Result<R::Result, String> {
+        let machine_count = self.machines.len();
+        let empty_input = router.empty_input();
+        let instruction_limit = self.instruction_limit;
+        let stopped = AtomicBool::new(false);
+        let (event_sender, events) = mpsc::channel();
+        let mut inboxes = Vec::with_capacity(machine_count);
+
+        std::thread::scope(|scope| {
+            for (machine, program) in self.machines.iter_mut().enumerate() {
+                let (inbox, messages) = mpsc::channel();
+                inboxes.push(inbox);
+                let event_sender = event_sender.clone();
+                let stopped = &stopped;
+                scope.spawn(move || {
+                    let event = run_machine(
+                        machine,
+                        program,
+                        &messages,
+                        &event_sender,
+                        empty_input,
+                        instruction_limit,
+                        stopped,
+                    )
+                    .map_or_else(Event::Error, |()| Event::Halted(machine));
+                    // The router may no longer be listening, which is fine:
+                    event_sender.send(event).ok();
+                });
+            }
+            drop(event_sender);
+
+            let result = route_events(router, &events, &inboxes);
+            // Unblock machines waiting for messages and stop running ones:
+            stopped.store(true, Ordering::Relaxed);
+            inboxes.clear();
+            result
+        })
+    }
+}
+
+/// Runs a machine on its own thread, reporting its output and when it is waiting for a
+/// message to the router.
+fn run_machine<I: Message, O: Message>(
+    machine: usize,
+    program: &mut Program,
+    messages: &Receiver<I>,
+    events: &Sender<Event<O>>,
+    empty_input: Option<Word>,
+    instruction_limit: u32,
+    stopped: &AtomicBool,
+) -> Result<(), String> {
+    let mut output_words = Vec::with_capacity(O::WORDS);
+    let mut words = Vec::with_capacity(I::WORDS);
+    let mut received = 0;
+    let mut read_empty_input = false;
+    let mut deliver = |program: &mut Program, message: I| {
+        words.clear();
+        message.encode(&mut words);
+        for &word in &words {
+            program.input(word);
+        }
+    };
+
+    while !stopped.load(Ordering::Relaxed) {
+        match program.run(instruction_limit)? {
+            RunState::Output(value) => {
+                read_empty_input = false;
+                output_words.push(value);
+                if output_words.len() == O::WORDS {
+                    let message = O::decode(&output_words);
+                    output_words.clear();
+                    if events.send(Event::Output(machine, message)).is_err() {
+                        return Ok(());
+                    }
+                }
+            }
+            RunState::NeedsInput => match messages.try_recv() {
+                Ok(message) => {
+                    received += 1;
+                    read_empty_input = false;
+                    deliver(program, message);
+                }
+                Err(TryRecvError::Empty) => match empty_input {
+                    Some(value) if !read_empty_input => {
+                        read_empty_input = true;
+                        program.input(value);
+                    }
+                    _ => {
+                        if events.send(Event::Idle { machine, received }).is_err() {
+                            return Ok(());
+                        }
+                        let Ok(message) = messages.recv() else {
+                            return Ok(());
+                        };
+                        received += 1;
+                        read_empty_input = false;
+                        deliver(program, message);
+                    }
+                },
+                Err(TryRecvError::Disconnected) => return Ok(()),
+            },
+            RunState::Halted => return Ok(()),
+            RunState::BudgetExhausted => {
+                return Err(format!(
+                    "Machine {machine} aborted after {instruction_limit} instructions"
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The state of a machine as last reported to the router.
+#[derive(Clone, Copy)]
+enum MachineState {
+    Running,
+    Idle { received: u64 },
+    Halted,
+}
+
+/// Passes events from machine threads to the router until it stops the network.
+fn route_events<R: Router>(
+    router: &mut R,
+    events: &Receiver<Event<R::Output>>,
+    inboxes: &[Sender<R::Input>],
+) -> Result<R::Result, String> {
+    let mut outbox = Outbox {
+        machine_count: inboxes.len(),
+        messages: Vec::new(),
+    };
+    let mut states = vec![MachineState::Running; inboxes.len()];
+    let mut sent = vec![0_u64; inboxes.len()];
+    let deliver = |messages: Vec<(usize, R::Input)>, sent: &mut [u64]| {
+        for (to, message) in messages {
+            sent[to] += 1;
+            // Messages to halted machines are dropped:
+            inboxes[to].send(message).ok();
+        }
+    };
+
+    loop {
+        let event = events
+            .recv()
+            .map_err(|_| "All machines stopped unexpectedly".to_string())?;
+        match event {
+            Event::Output(from, message) => {
+                states[from] = MachineState::Running;
+                let flow = router.route(from, message, &mut outbox)?;
+                deliver(outbox.take()?, &mut sent);
+                if let Flow::Stop(result) = flow {
+                    return Ok(result);
+                }
+            }
+            Event::Idle { machine, received } => states[machine] = MachineState::Idle { received },
+            Event::Halted(machine) => states[machine] = MachineState::Halted,
+            Event::Error(error) => return Err(error),
+        }
+
+        // A machine is only idle if it has received all messages sent to it:
+        let idle = states
+            .iter()
+            .zip(sent.iter())
+            .all(|(state, &sent)| match state {
+                MachineState::Running => false,
+                MachineState::Idle { received } => *received == sent,
+                MachineState::Halted => true,
+            });
+        if idle {
+            let flow = router.idle(&mut outbox)?;
+            let messages = outbox.take()?;
+            if let Flow::Stop(result) = flow {
+                return Ok(result);
+            } else if messages.is_empty() {
+                return Err("Network deadlocked - idle without any message sent".to_string());
+            }
+            deliver(messages, &mut sent);
+        }
+    }
+}
+
+#[test]
+#[allow(clippy::unwrap_used)]
+fn test() {
+    /// Sends each output to the next machine in a loop, stopping at the first value of 100 or more.
+    struct Ring;
+
+    impl Router for Ring {
+        type Output = Word;
+        type Input = Word;
+        type Result = (usize, Word);
+
+        fn route(
+            &mut self,
+            from: usize,
+            value: Word,
+            outbox: &mut Outbox<Word>,
+        ) -> Result<Flow<(usize, Word)>, String> {
+            if value >= 100 {
+                return Ok(Flow::Stop((from, value)));
+            }
+            outbox.send((from + 1) % outbox.machine_count(), value);
+            Ok(Flow::Continue)
+        }
+
+        fn idle(&mut self, _outbox: &mut Outbox<Word>) -> Result<Flow<(usize, Word)>, String> {
+            Ok(Flow::Continue)
+        }
+    }
+
+    // Reads a value and outputs it doubled, forever:
+    let doubler = Program::parse("3,11,102,2,11,11,4,11,1105,1,0,0").unwrap();
+    for scheduler in [Scheduler::RoundRobin, Scheduler::Threaded] {
+        let mut network = Network::new(vec![doubler.clone(); 3]);
+        network.send(0, 1);
+        assert_eq!(network.run(&mut Ring, scheduler), Ok((0, 128)));
+
+        let mut network = Network::new(vec![doubler.clone(); 3]);
+        assert_eq!(
+            network.run(&mut Ring, scheduler),
+            Err("Network deadlocked - idle without any message sent".to_string())
+        );
+
+        let mut network = Network::new(vec![doubler.clone(); 3]);
+        network.send(1, 200);
+        network.set_instruction_limit(2);
+        assert_eq!(
+            network.run(&mut Ring, scheduler),
+            Err("Machine 1 aborted after 2 instructions".to_string())
+        );
+    }
+}
@@ -0,0 +1,274 @@
+//! Serialization and comparison of the complete state of a program.
+//!
+//! A state is saved as text with one field per line, such as:
+//!
+//! ```text
+//! intcode-state 1
+//! instruction-pointer 2
+//! relative-base 0
+//! memory-limit 1048576
+//! halted false
+//! requires-input-to 9
+//! input
+//! output 4,5
+//! memory 3,9,102,2,9,10,4,10,99,0,0
+//! ```
+//!
+//! A program can be cloned to snapshot it in memory, while serializing it allows it to be
+//! stored and restored later.
+
+use std::fmt::{Display, Formatter};
+
+use super::{DecodedInstruction, Program, Word};
+
+const HEADER: &str = "intcode-state 1";
+
+/// A difference between two program states, as found by [`diff`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Difference {
+    InstructionPointer(usize, usize),
+    RelativeBase(Word, Word),
+    MemoryLimit(usize, usize),
+    Halted(bool, bool),
+    RequiresInputTo(Option<usize>, Option<usize>),
+    Input(Vec<Word>, Vec<Word>),
+    Output(Vec<Word>, Vec<Word>),
+    Memory {
+        address: usize,
+        before: Word,
+        after: Word,
+    },
+}
+
+impl Display for Difference {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let optional = |value: &Option<usize>| value.map_or("none".to_string(), |v| v.to_string());
+        match self {
+            Self::InstructionPointer(before, after) => {
+                write!(f, "instruction-pointer: {before} -> {after}")
+            }
+            Self::RelativeBase(before, after) => write!(f, "relative-base: {before} -> {after}"),
+            Self::MemoryLimit(before, after) => write!(f, "memory-limit: {before} -> {after}"),
+            Self::Halted(before, after) => write!(f, "halted: {before} -> {after}"),
+            Self::RequiresInputTo(before, after) => write!(
+                f,
+                "requires-input-to: {} -> {}",
+                optional(before),
+                optional(after)
+            ),
+            Self::Input(before, after) => {
+                write!(f, "input: [{}] -> [{}]", join(before), join(after))
+            }
+            Self::Output(before, after) => {
+                write!(f, "output: [{}] -> [{}]", join(before), join(after))
+            }
+            Self::Memory {
+                address,
+                before,
+                after,
+            } => write!(f, "memory[{address}]: {before} -> {after}"),
+        }
+    }
+}
+
+fn join(values: &[Word]) -> String {
+    values
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn parse_words(text: &str) -> Result<Vec<Word>, String> {
+    if text.is_empty() {
+        return Ok(Vec::new());
+    }
+    text.split(',')
+        .map(|word| {
+            word.parse::<Word>()
+                .map_err(|_| format!("Invalid word '{word}' in state"))
+        })
+        .collect()
+}
+
+impl Program {
+    /// Serializes the complete state of the program, to be restored with
+    /// [`Program::deserialize_state`].
+    pub fn serialize_state(&self) -> String {
+        // Trailing zeros are implied, as memory outside of what is stored reads as zero:
+        let memory_len = self
+            .memory
+            .iter()
+            .rposition(|&word| word != 0)
+            .map_or(0, |idx| idx + 1);
+        let fields = [
+            ("instruction-pointer", self.instruction_pointer.to_string()),
+            ("relative-base", self.relative_base.to_string()),
+            ("memory-limit", self.memory_limit.to_string()),
+            ("halted", self.halted.to_string()),
+            (
+                "requires-input-to",
+                self.requires_input_to
+                    .map_or("none".to_string(), |address| address.to_string()),
+            ),
+            ("input", join(&Vec::from(self.input_values.clone()))),
+            ("output", join(&self.output_values)),
+            ("memory", join(&self.memory[..memory_len])),
+        ];
+
+        let mut result = format!("{HEADER}\n");
+        for (key, value) in fields {
+            result.push_str(key);
+            if !value.is_empty() {
+                result.push(' ');
+                result.push_str(&value);
+            }
+            result.push('\n');
+        }
+        result
+    }
+
+    /// Restores a program from a state serialized with [`Program::serialize_state`].
+    pub fn deserialize_state(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines();
+        if lines.next() != Some(HEADER) {
+            return Err(format!("Invalid state - expected '{HEADER}' header"));
+        }
+
+        let mut program = Self::from_memory(Vec::new());
+        for line in lines {
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            let invalid = || format!("Invalid value for {key} in state: '{value}'");
+            match key {
+                "instruction-pointer" => {
+                    program.instruction_pointer = value.parse().map_err(|_| invalid())?;
+                }
+                "relative-base" => program.relative_base = value.parse().map_err(|_| invalid())?,
+                "memory-limit" => program.memory_limit = value.parse().map_err(|_| invalid())?,
+                "halted" => program.halted = value.parse().map_err(|_| invalid())?,
+                "requires-input-to" => {
+                    program.requires_input_to = if value == "none" {
+                        None
+                    } else {
+                        Some(value.parse().map_err(|_| invalid())?)
+                    };
+                }
+                "input" => program.input_values = parse_words(value)?.into(),
+                "output" => program.output_values = parse_words(value)?,
+                "memory" => program.memory = parse_words(value)?,
+                "" => {}
+                _ => return Err(format!("Unknown field '{key}' in state")),
+            }
+        }
+        program.decoded = DecodedInstruction::decode_all(&program.memory);
+        Ok(program)
+    }
+}
+
+/// Lists the differences between two program states, with memory compared word by word.
+pub fn diff(before: &Program, after: &Program) -> Vec<Difference> {
+    let mut differences = Vec::new();
+    if before.instruction_pointer != after.instruction_pointer {
+        differences.push(Difference::InstructionPointer(
+            before.instruction_pointer,
+            after.instruction_pointer,
+        ));
+    }
+    if before.relative_base != after.relative_base {
+        differences.push(Difference::RelativeBase(
+            before.relative_base,
+            after.relative_base,
+        ));
+    }
+    if before.memory_limit != after.memory_limit {
+        differences.push(Difference::MemoryLimit(
+            before.memory_limit,
+            after.memory_limit,
+        ));
+    }
+    if before.halted != after.halted {
+        differences.push(Difference::Halted(before.halted, after.halted));
+    }
+    if before.requires_input_to != after.requires_input_to {
+        differences.push(Difference::RequiresInputTo(
+            before.requires_input_to,
+            after.requires_input_to,
+        ));
+    }
+    if before.input_values != after.input_values {
+        differences.push(Difference::Input(
+            before.input_values.iter().copied().collect(),
+            after.input_values.iter().copied().collect(),
+        ));
+    }
+    if before.output_values != after.output_values {
+        differences.push(Difference::Output(
+            before.output_values.clone(),
+            after.output_values.clone(),
+        ));
+    }
+    for address in 0..before.memory.len().max(after.memory.len()) {
+        let (before, after) = (before.read_memory(address), after.read_memory(address));
+        if before != after {
+            differences.push(Difference::Memory {
+                address,
+                before,
+                after,
+            });
+        }
+    }
+    differences
+}
+
+#[test]
+#[allow(clippy::unwrap_used)]
+fn test() {
+    let mut program = Program::parse("3,9,102,2,9,10,4,10,99,0,0").unwrap();
+    let initial = program.clone();
+    program.set_memory_limit(50);
+    program.run_until_halt_or_input(100).unwrap();
+    program.input(21);
+    program.input(7);
+
+    let state = program.serialize_state();
+    assert_eq!(
+        state,
+        "intcode-state 1
+instruction-pointer 2
+relative-base 0
+memory-limit 50
+halted false
+requires-input-to none
+input 7
+output
+memory 3,9,102,2,9,10,4,10,99,21
+"
+    );
+    let mut restored = Program::deserialize_state(&state).unwrap();
+    assert!(diff(&program, &restored).is_empty());
+    assert_eq!(restored.run_for_output(), Ok(vec![42]));
+    assert_eq!(
+        Program::deserialize_state(&restored.serialize_state())
+            .unwrap()
+            .serialize_state(),
+        restored.serialize_state()
+    );
+
+    assert_eq!(
+        diff(&initial, &restored)
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>(),
+        vec![
+            "instruction-pointer: 0 -> 8",
+            "memory-limit: 1048576 -> 50",
+            "halted: false -> true",
+            "input: [] -> [7]",
+            "memory[9]: 0 -> 21",
+            "memory[10]: 0 -> 42",
+        ]
+    );
+
+    assert!(Program::deserialize_state("1,2,3").is_err());
+    assert!(Program::deserialize_state("intcode-state 1\nhalted maybe").is_err());
+}
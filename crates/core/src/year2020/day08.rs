@@ -1,7 +1,27 @@
+//! The handheld game console of [2020 day 8](https://adventofcode.com/2020/day/8), whose
+//! boot code is analysed by [`control_flow`].
+//!
+//! # Example
+//! ```
+//! use advent_of_code::handheld::Computer;
+//!
+//! // Loops forever unless the `jmp` is changed into a `nop`:
+//! let computer = Computer::parse("acc +1\njmp -1").unwrap();
+//! let control_flow = Computer::control_flow(&computer);
+//! assert_eq!(control_flow.cycle, Some(vec![0, 1]));
+//! assert_eq!(control_flow.instruction_to_flip(&computer.instructions), Some(1));
+//! ```
+pub mod control_flow;
+
+use std::fmt::{Display, Formatter};
+
+use crate::common::register_machine::{Effect, InstructionSet, Machine, Stop};
 use crate::input::Input;
 
 type Word = i32;
 
+const MAX_INSTRUCTIONS: u64 = 1_000_000;
+
 #[derive(Copy, Clone)]
 pub enum Instruction {
     Acc(Word),
@@ -9,14 +29,21 @@ pub enum Instruction {
     Nop(Word),
 }
 
-pub struct Computer {
-    pub instructions: Vec<Instruction>,
-    pub accumulator: Word,
-    pub instruction_pointer: Word,
+impl Display for Instruction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Self::Acc(argument) => write!(f, "acc {argument:+}"),
+            Self::Jmp(argument) => write!(f, "jmp {argument:+}"),
+            Self::Nop(argument) => write!(f, "nop {argument:+}"),
+        }
+    }
 }
 
+/// The handheld game console, with an accumulator as its only register.
+pub struct Computer;
+
 impl Computer {
-    pub fn parse(program_text: &str) -> Result<Self, String> {
+    pub fn parse(program_text: &str) -> Result<Machine<Self>, String> {
         let instructions = program_text
             .lines()
             .enumerate()
@@ -47,103 +74,61 @@ impl Computer {
             })
             .collect::<Result<Vec<_>, _>>()?;
 
-        Ok(Self {
-            instructions,
-            accumulator: 0,
-            instruction_pointer: 0,
-        })
+        Ok(Machine::new(instructions))
     }
-
-    pub fn execute_instruction(&mut self) -> Result<(), String> {
-        match self.instructions.get(self.instruction_pointer as usize) {
-            Some(Instruction::Acc(parameter)) => {
-                self.accumulator += parameter;
-            }
-            Some(Instruction::Jmp(parameter)) => {
-                self.instruction_pointer += parameter;
-                return Ok(());
-            }
-            Some(Instruction::Nop(_)) => {}
-            None => {
-                return Err("Cannot executed an exited program".to_string());
-            }
-        };
-
-        self.instruction_pointer += 1;
-        Ok(())
-    }
-
-    pub fn has_exited(&self) -> bool {
-        self.instruction_pointer < 0 || self.instruction_pointer >= self.instructions.len() as Word
-    }
-}
-
-struct ComputerChecker {
-    executed_instructions: Vec<bool>,
 }
 
-impl ComputerChecker {
-    fn new(computer: &Computer) -> Self {
-        Self {
-            executed_instructions: vec![false; computer.instructions.len()],
-        }
-    }
-
-    fn check_if_exits(&mut self, computer: &mut Computer) -> Result<bool, String> {
-        self.executed_instructions
-            .iter_mut()
-            .for_each(|v| *v = false);
-
-        while !computer.has_exited()
-            && !self.executed_instructions[computer.instruction_pointer as usize]
-        {
-            self.executed_instructions[computer.instruction_pointer as usize] = true;
-            computer.execute_instruction()?;
-        }
-        Ok(computer.has_exited())
+impl InstructionSet for Computer {
+    type Word = Word;
+    type Registers = [Word; 1];
+    type Instruction = Instruction;
+    type State = ();
+    const REGISTER_NAMES: &'static [&'static str] = &["acc"];
+
+    fn execute(
+        instruction: Instruction,
+        machine: &mut Machine<Self>,
+    ) -> Result<Effect<Word>, String> {
+        Ok(match instruction {
+            Instruction::Acc(parameter) => {
+                machine.registers[0] += parameter;
+                Effect::Next
+            }
+            Instruction::Jmp(parameter) => Effect::Jump(parameter.into()),
+            Instruction::Nop(_) => Effect::Next,
+        })
     }
 }
 
 pub fn solve(input: &Input) -> Result<Word, String> {
     let mut computer = Computer::parse(input.text)?;
-    let mut computer_checker = ComputerChecker::new(&computer);
+    let mut executed_instructions = Vec::new();
 
-    computer_checker.check_if_exits(&mut computer)?;
+    computer.run_until_loop(MAX_INSTRUCTIONS, &mut executed_instructions)?;
 
     if input.is_part_one() {
-        Ok(computer.accumulator)
+        Ok(computer.registers[0])
     } else {
-        // We only need to patch instructions that are actually executed in the unpatched program:
-        let executed_instructions_without_patch = computer_checker.executed_instructions.clone();
-
-        for i in executed_instructions_without_patch
-            .iter()
-            .enumerate()
-            .filter_map(|(idx, &executed)| if executed { Some(idx) } else { None })
-        {
-            let instruction = computer.instructions[i];
-            match instruction {
-                Instruction::Jmp(parameter) | Instruction::Nop(parameter) => {
-                    computer.instructions[i] = if matches!(instruction, Instruction::Jmp(_)) {
-                        Instruction::Nop(parameter)
-                    } else {
-                        Instruction::Jmp(parameter)
-                    };
-
-                    if computer_checker.check_if_exits(&mut computer)? {
-                        return Ok(computer.accumulator);
-                    }
+        let control_flow = Computer::control_flow(&computer);
+        let index = control_flow
+            .instruction_to_flip(&computer.instructions)
+            .ok_or_else(|| {
+                format!(
+                    "No instruction modification causes program to exit:\n{}",
+                    control_flow.describe(&computer.instructions).trim_end()
+                )
+            })?;
+        computer.instructions[index] = match computer.instructions[index] {
+            Instruction::Jmp(parameter) => Instruction::Nop(parameter),
+            Instruction::Nop(parameter) => Instruction::Jmp(parameter),
+            Instruction::Acc(_) => return Err("Cannot flip an acc instruction".to_string()),
+        };
 
-                    computer.instruction_pointer = 0;
-                    computer.accumulator = 0;
-                    computer.instructions[i] = instruction;
-                }
-                _ => {
-                    continue;
-                }
-            }
+        computer.reset();
+        match computer.run_until_loop(MAX_INSTRUCTIONS, &mut executed_instructions)? {
+            Stop::Halted => Ok(computer.registers[0]),
+            _ => Err("The patched program does not exit".to_string()),
         }
-        Err("No instruction modification causes program to exit".to_string())
     }
 }
 
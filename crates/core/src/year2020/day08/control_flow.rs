@@ -0,0 +1,209 @@
+//! Control-flow analysis of programs for the handheld game console.
+//!
+//! Since each instruction has a single successor, the control-flow graph is a function from
+//! instructions to instructions. The instructions from which the program terminates are
+//! found by walking the edges backwards from those leaving the program, which also makes it
+//! possible to find the instruction to flip between `jmp` and `nop` in linear time.
+
+use std::fmt::Write;
+
+use super::{Computer, Instruction};
+use crate::common::register_machine::Machine;
+
+pub struct ControlFlow {
+    /// The instruction executed after each instruction, or `None` if it leaves the program.
+    pub successors: Vec<Option<usize>>,
+    /// If running from each instruction terminates the program.
+    pub terminates: Vec<bool>,
+    /// The instructions executed when running the program, in order, until it terminates
+    /// or an instruction is about to be executed a second time.
+    pub execution: Vec<usize>,
+    /// The instructions of the infinite loop the program ends up in, if it does, starting
+    /// with the first instruction executed a second time.
+    pub cycle: Option<Vec<usize>>,
+}
+
+impl Computer {
+    pub fn control_flow(machine: &Machine<Self>) -> ControlFlow {
+        ControlFlow::new(&machine.instructions)
+    }
+}
+
+/// The instruction executed after one, or `None` if it leaves the program.
+fn successor(instructions: &[Instruction], index: usize) -> Option<usize> {
+    let offset = match instructions[index] {
+        Instruction::Jmp(offset) => offset as isize,
+        Instruction::Acc(_) | Instruction::Nop(_) => 1,
+    };
+    index
+        .checked_add_signed(offset)
+        .filter(|&successor| successor < instructions.len())
+}
+
+impl ControlFlow {
+    pub fn new(instructions: &[Instruction]) -> Self {
+        let successors = (0..instructions.len())
+            .map(|index| successor(instructions, index))
+            .collect::<Vec<_>>();
+
+        let mut predecessors = vec![Vec::new(); instructions.len()];
+        let mut terminates = vec![false; instructions.len()];
+        let mut to_visit = Vec::new();
+        for (index, &successor) in successors.iter().enumerate() {
+            match successor {
+                Some(successor) => predecessors[successor].push(index),
+                None => {
+                    terminates[index] = true;
+                    to_visit.push(index);
+                }
+            }
+        }
+        while let Some(index) = to_visit.pop() {
+            for &predecessor in &predecessors[index] {
+                if !std::mem::replace(&mut terminates[predecessor], true) {
+                    to_visit.push(predecessor);
+                }
+            }
+        }
+
+        let mut executed = vec![false; instructions.len()];
+        let mut execution = Vec::new();
+        let mut current = (!instructions.is_empty()).then_some(0);
+        let mut cycle = None;
+        while let Some(index) = current {
+            if executed[index] {
+                let cycle_start = execution.iter().position(|&i| i == index).unwrap_or(0);
+                cycle = Some(execution[cycle_start..].to_vec());
+                break;
+            }
+            executed[index] = true;
+            execution.push(index);
+            current = successors[index];
+        }
+
+        Self {
+            successors,
+            terminates,
+            execution,
+            cycle,
+        }
+    }
+
+    /// The instruction to flip between `jmp` and `nop` for a program stuck in an infinite
+    /// loop to terminate.
+    ///
+    /// Only instructions executed before the loop is detected need to be considered, and
+    /// flipping one of them makes the program terminate exactly if its new successor
+    /// terminates the unchanged program: running from there can not reach the flipped
+    /// instruction, since that would lead into the loop.
+    pub fn instruction_to_flip(&self, instructions: &[Instruction]) -> Option<usize> {
+        self.cycle.as_ref()?;
+        self.execution.iter().copied().find(|&index| {
+            let flipped_successor = match instructions[index] {
+                Instruction::Acc(_) => return false,
+                Instruction::Jmp(_) => index.checked_add(1),
+                Instruction::Nop(offset) => index.checked_add_signed(offset as isize),
+            };
+            flipped_successor
+                .and_then(|successor| self.terminates.get(successor))
+                .is_none_or(|&terminates| terminates)
+        })
+    }
+
+    /// Lists the instructions with their successors, noting for each if it is executed, part
+    /// of the infinite loop and if running from it terminates the program.
+    pub fn describe(&self, instructions: &[Instruction]) -> String {
+        let mut executed = vec![false; instructions.len()];
+        for &index in &self.execution {
+            executed[index] = true;
+        }
+        let mut in_cycle = vec![false; instructions.len()];
+        for &index in self.cycle.iter().flatten() {
+            in_cycle[index] = true;
+        }
+
+        let mut result = String::new();
+        for (index, instruction) in instructions.iter().enumerate() {
+            let notes = [
+                (executed[index], "executed"),
+                (in_cycle[index], "in loop"),
+                (self.terminates[index], "terminates"),
+            ]
+            .into_iter()
+            .filter_map(|(applies, note)| applies.then_some(note))
+            .collect::<Vec<_>>();
+            let successor = self.successors[index].map_or_else(
+                || "-> exit".to_string(),
+                |successor| format!("-> {successor}"),
+            );
+            let line = format!(
+                "{index:>4}: {:<8} {successor:<8} {}",
+                instruction.to_string(),
+                notes.join(", ")
+            );
+            let _ = writeln!(result, "{}", line.trim_end());
+        }
+        result
+    }
+}
+
+#[test]
+#[allow(clippy::unwrap_used)]
+fn test() {
+    let computer = Computer::parse(
+        "nop +0
+acc +1
+jmp +4
+acc +3
+jmp -3
+acc -99
+acc +1
+jmp -4
+acc +6",
+    )
+    .unwrap();
+    let control_flow = Computer::control_flow(&computer);
+    assert_eq!(control_flow.execution, [0, 1, 2, 6, 7, 3, 4]);
+    assert_eq!(control_flow.cycle, Some(vec![1, 2, 6, 7, 3, 4]));
+    assert_eq!(
+        control_flow.successors,
+        [
+            Some(1),
+            Some(2),
+            Some(6),
+            Some(4),
+            Some(1),
+            Some(6),
+            Some(7),
+            Some(3),
+            None
+        ]
+    );
+    assert_eq!(
+        control_flow.instruction_to_flip(&computer.instructions),
+        Some(7)
+    );
+    assert_eq!(
+        control_flow.describe(&computer.instructions),
+        "   0: nop +0   -> 1     executed
+   1: acc +1   -> 2     executed, in loop
+   2: jmp +4   -> 6     executed, in loop
+   3: acc +3   -> 4     executed, in loop
+   4: jmp -3   -> 1     executed, in loop
+   5: acc -99  -> 6
+   6: acc +1   -> 7     executed, in loop
+   7: jmp -4   -> 3     executed, in loop
+   8: acc +6   -> exit  terminates
+"
+    );
+
+    // A program which already terminates has no loop to get out of:
+    let computer = Computer::parse("nop +0\njmp +2").unwrap();
+    let control_flow = Computer::control_flow(&computer);
+    assert_eq!(control_flow.terminates, [true, true]);
+    assert_eq!(control_flow.cycle, None);
+    assert_eq!(
+        control_flow.instruction_to_flip(&computer.instructions),
+        None
+    );
+}
@@ -1,13 +1,13 @@
 use crate::input::Input;
 
-struct HeightMap {
+pub struct HeightMap {
     width: u8,
     height: u8,
     height_data: Vec<u8>,
 }
 
 impl HeightMap {
-    fn parse(input: &str) -> Result<Self, String> {
+    pub(crate) fn parse(input: &str) -> Result<Self, String> {
         let width = input.lines().next().map(str::len).unwrap_or_default();
         let height = input.lines().count();
 
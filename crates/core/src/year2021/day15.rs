@@ -9,7 +9,7 @@ pub struct Graph {
 }
 
 impl Graph {
-    fn parse(text: &str, multiplier: usize) -> Result<Self, String> {
+    pub(crate) fn parse(text: &str, multiplier: usize) -> Result<Self, String> {
         let original_height = text.lines().count();
         let original_width = text.lines().next().unwrap_or_default().len();
         if original_height < 4 || original_width < 4 {
@@ -44,7 +44,7 @@ impl Graph {
         Ok(Self {
             risk_levels,
             width: width as u16,
-            height: width as u16,
+            height: height as u16,
         })
     }
 
@@ -129,6 +129,7 @@ pub fn tests() {
 2311944581";
     test_part_one!(example => 40);
     test_part_two!(example => 315);
+    test_part_one!("11111111\n99999991\n99999991\n99999991" => 10);
 
     test_part_one_error!("" => "Too small input");
     test_part_one_error!("1234\n12345\n1234\n1234" => "Not all lines have equal length");
@@ -1,3 +1,8 @@
+mod symbolic;
+
+use std::fmt::{Display, Formatter};
+
+use crate::common::register_machine::{Effect, InstructionSet, Machine};
 use crate::input::Input;
 
 const NUM_DIGITS_IN_MODEL_NUMBER: usize = 14;
@@ -10,8 +15,20 @@ pub fn solve(input: &Input) -> Result<u64, String> {
         .collect::<Option<Vec<_>>>()
         .ok_or_else(|| "Invalid input".to_string())?;
 
-    let input_blocks = extract_input_blocks(&instructions)?;
+    // Programs with the structure of MONAD are solved directly, and others by a search
+    // pruned using symbolic execution:
+    extract_input_blocks(&instructions)
+        .and_then(|input_blocks| {
+            solve_input_blocks(&instructions, &input_blocks, input.is_part_one())
+        })
+        .or_else(|_| symbolic::find_model_number(&instructions, input.is_part_one()))
+}
 
+fn solve_input_blocks(
+    instructions: &[Instruction],
+    input_blocks: &InputBlocks,
+    part_one: bool,
+) -> Result<u64, String> {
     let mut model_number = [0; NUM_DIGITS_IN_MODEL_NUMBER];
     let mut stack = Vec::new();
 
@@ -55,7 +72,7 @@ pub fn solve(input: &Input) -> Result<u64, String> {
                     "Assumption broken: input difference is not in the range [-8,8]".to_string(),
                 );
             }
-            let w_pushed = if input.is_part_one() {
+            let w_pushed = if part_one {
                 // We need highest value on this leftmost digit that results in rightmost digit <= 9.
                 std::cmp::min(9 - input_difference, 9)
             } else {
@@ -67,6 +84,15 @@ pub fn solve(input: &Input) -> Result<u64, String> {
         }
     }
 
+    // Check the model number found by running the program on the ALU:
+    let mut alu = Machine::<Alu>::new(instructions.to_vec());
+    alu.input
+        .extend(model_number.iter().map(|&digit| i64::from(digit)));
+    alu.run_until_halt(10_000)?;
+    if alu.registers[Variable::Z as usize] != 0 {
+        return Err("Assumption broken: the model number is not valid when checked".to_string());
+    }
+
     Ok(model_number
         .iter()
         .fold(0, |acc, digit| acc * 10 + *digit as u64))
@@ -81,6 +107,9 @@ fn extract_input_blocks(instructions: &[Instruction]) -> Result<InputBlocks, Str
     let mut input_instructions_count = 0;
     for (instruction_idx, instruction) in instructions.iter().enumerate() {
         if let Instruction::Input(variable) = instruction {
+            if instruction_idx + 18 > instructions.len() {
+                return Err("Assumption broken: Input block is too short".to_string());
+            }
             if *variable == Variable::W {
                 // After every input to w:
                 // Start: "x = z % 26"
@@ -252,6 +281,12 @@ impl Variable {
     }
 }
 
+impl Display for Variable {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Alu::REGISTER_NAMES[*self as usize])
+    }
+}
+
 #[derive(Copy, Clone)]
 enum VariableOrNumber {
     Variable(Variable),
@@ -267,6 +302,7 @@ impl VariableOrNumber {
     }
 }
 
+#[derive(Copy, Clone)]
 enum Instruction {
     // Read an input value and write it to variable a.
     Input(Variable),
@@ -306,6 +342,84 @@ impl Instruction {
             }
         })
     }
+
+    /// Executes an instruction other than input on the registers.
+    fn apply(self, registers: &mut [i64; 4]) -> Result<(), String> {
+        let value_of = |b: VariableOrNumber| match b {
+            VariableOrNumber::Variable(variable) => registers[variable as usize],
+            VariableOrNumber::Number(number) => i64::from(number),
+        };
+        let (a, result) = match self {
+            Self::Input(_) => return Err("Input instruction executed without input".to_string()),
+            Self::Add(a, b) => (a, registers[a as usize].checked_add(value_of(b))),
+            Self::Multiply(a, b) => (a, registers[a as usize].checked_mul(value_of(b))),
+            Self::Divide(a, b) => {
+                let divisor = value_of(b);
+                if divisor == 0 {
+                    return Err("Division by zero".to_string());
+                }
+                (a, registers[a as usize].checked_div(divisor))
+            }
+            Self::Modulo(a, b) => {
+                let divisor = value_of(b);
+                if registers[a as usize] < 0 || divisor <= 0 {
+                    return Err("Modulo with negative dividend or non-positive divisor".to_string());
+                }
+                (a, Some(registers[a as usize] % divisor))
+            }
+            Self::Equal(a, b) => (a, Some(i64::from(registers[a as usize] == value_of(b)))),
+        };
+        registers[a as usize] = result.ok_or_else(|| "Overflow".to_string())?;
+        Ok(())
+    }
+}
+
+impl Display for VariableOrNumber {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Variable(variable) => write!(f, "{variable}"),
+            Self::Number(number) => write!(f, "{number}"),
+        }
+    }
+}
+
+impl Display for Instruction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Input(a) => write!(f, "inp {a}"),
+            Self::Add(a, b) => write!(f, "add {a} {b}"),
+            Self::Multiply(a, b) => write!(f, "mul {a} {b}"),
+            Self::Divide(a, b) => write!(f, "div {a} {b}"),
+            Self::Modulo(a, b) => write!(f, "mod {a} {b}"),
+            Self::Equal(a, b) => write!(f, "eql {a} {b}"),
+        }
+    }
+}
+
+/// The arithmetic logic unit, with the instructions reading input digits.
+struct Alu;
+
+impl InstructionSet for Alu {
+    type Word = i64;
+    type Registers = [i64; 4];
+    type Instruction = Instruction;
+    type State = ();
+    const REGISTER_NAMES: &'static [&'static str] = &["w", "x", "y", "z"];
+
+    fn execute(
+        instruction: Instruction,
+        machine: &mut Machine<Self>,
+    ) -> Result<Effect<i64>, String> {
+        if let Instruction::Input(a) = instruction {
+            match machine.input.pop_front() {
+                Some(value) => machine.registers[a as usize] = value,
+                None => return Ok(Effect::NeedsInput),
+            }
+        } else {
+            instruction.apply(&mut machine.registers)?;
+        }
+        Ok(Effect::Next)
+    }
 }
 
 #[derive(Copy, Clone)]
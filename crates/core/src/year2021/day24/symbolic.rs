@@ -0,0 +1,450 @@
+//! Symbolic execution of ALU programs, tracking the value of each variable as an expression
+//! of the input digits together with an interval containing all values it can have.
+//!
+//! Expressions are simplified using the intervals, so that for example `(d0 + 6) % 26` is
+//! kept as `d0 + 6` since it is always less than 26, and an equality check between values
+//! with disjoint intervals is replaced by zero.
+//!
+//! A depth-first search over the digits uses this to prune states from which z can not end
+//! up as zero, without depending on the structure of the program.
+
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use std::rc::Rc;
+
+use super::{Instruction, Variable, VariableOrNumber};
+
+/// The values of an input digit.
+const DIGITS: Interval = Interval { min: 1, max: 9 };
+
+/// The largest number of digits of a model number fitting in a `u64`.
+const MAX_DIGITS: usize = 19;
+
+/// The inclusive range of values a variable can have.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Interval {
+    pub min: i64,
+    pub max: i64,
+}
+
+impl Interval {
+    const fn constant(value: i64) -> Self {
+        Self {
+            min: value,
+            max: value,
+        }
+    }
+
+    pub const fn contains(self, value: i64) -> bool {
+        self.min <= value && value <= self.max
+    }
+
+    fn spanning(values: [i64; 4]) -> Self {
+        Self {
+            min: values.into_iter().min().unwrap_or(i64::MIN),
+            max: values.into_iter().max().unwrap_or(i64::MAX),
+        }
+    }
+
+    fn union(self, other: Self) -> Self {
+        Self {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+
+    const fn add(self, other: Self) -> Self {
+        Self {
+            min: self.min.saturating_add(other.min),
+            max: self.max.saturating_add(other.max),
+        }
+    }
+
+    fn multiply(self, other: Self) -> Self {
+        Self::spanning([
+            self.min.saturating_mul(other.min),
+            self.min.saturating_mul(other.max),
+            self.max.saturating_mul(other.min),
+            self.max.saturating_mul(other.max),
+        ])
+    }
+
+    /// The values of a truncating division, or `None` if the divisor is always zero.
+    fn divide(self, divisor: Self) -> Option<Self> {
+        let negative = (divisor.min <= -1).then(|| Self {
+            min: divisor.min,
+            max: divisor.max.min(-1),
+        });
+        let positive = (divisor.max >= 1).then(|| Self {
+            min: divisor.min.max(1),
+            max: divisor.max,
+        });
+        // Truncating division is monotonic in both operands when the divisor has one sign:
+        let quotient = |a: i64, b: i64| a.checked_div(b).unwrap_or(i64::MAX);
+        negative
+            .into_iter()
+            .chain(positive)
+            .map(|divisor| {
+                Self::spanning([
+                    quotient(self.min, divisor.min),
+                    quotient(self.min, divisor.max),
+                    quotient(self.max, divisor.min),
+                    quotient(self.max, divisor.max),
+                ])
+            })
+            .reduce(Self::union)
+    }
+
+    /// The values of a remainder, or `None` if the dividend is always negative or the
+    /// divisor never positive, which makes the program fail.
+    fn modulo(self, divisor: Self) -> Option<Self> {
+        if self.max < 0 || divisor.max < 1 {
+            return None;
+        }
+        let dividend = Self {
+            min: self.min.max(0),
+            max: self.max,
+        };
+        Some(if dividend.max < divisor.min.max(1) {
+            dividend
+        } else {
+            Self {
+                min: 0,
+                max: dividend.max.min(divisor.max - 1),
+            }
+        })
+    }
+
+    fn equal(self, other: Self) -> Self {
+        if self.min == self.max && self == other {
+            Self::constant(1)
+        } else if self.max < other.min || other.max < self.min {
+            Self::constant(0)
+        } else {
+            Self { min: 0, max: 1 }
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+pub enum Operator {
+    Add,
+    Multiply,
+    Divide,
+    Modulo,
+    Equal,
+}
+
+/// A value computed from the input digits, numbered from zero.
+pub enum Expression {
+    Number(i64),
+    Digit(usize),
+    Operation(Operator, Rc<Expression>, Rc<Expression>),
+}
+
+impl Display for Expression {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Number(number) => write!(f, "{number}"),
+            Self::Digit(index) => write!(f, "d{index}"),
+            Self::Operation(operator, a, b) => {
+                let symbol = match operator {
+                    Operator::Add => "+",
+                    Operator::Multiply => "*",
+                    Operator::Divide => "/",
+                    Operator::Modulo => "%",
+                    Operator::Equal => "==",
+                };
+                write!(f, "({a} {symbol} {b})")
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Value {
+    pub expression: Rc<Expression>,
+    pub interval: Interval,
+}
+
+impl Value {
+    pub fn constant(value: i64) -> Self {
+        Self {
+            expression: Rc::new(Expression::Number(value)),
+            interval: Interval::constant(value),
+        }
+    }
+
+    fn digit(index: usize) -> Self {
+        Self {
+            expression: Rc::new(Expression::Digit(index)),
+            interval: DIGITS,
+        }
+    }
+
+    const fn is_constant(&self, value: i64) -> bool {
+        self.interval.min == value && self.interval.max == value
+    }
+
+    /// The result of an operation, or `None` if it makes the program fail for all values.
+    fn apply(operator: Operator, a: &Self, b: &Self) -> Option<Self> {
+        let interval = match operator {
+            Operator::Add => a.interval.add(b.interval),
+            Operator::Multiply => a.interval.multiply(b.interval),
+            Operator::Divide => a.interval.divide(b.interval)?,
+            Operator::Modulo => a.interval.modulo(b.interval)?,
+            Operator::Equal => a.interval.equal(b.interval),
+        };
+        if interval.min == interval.max {
+            return Some(Self::constant(interval.min));
+        }
+        let expression = match operator {
+            Operator::Add if a.is_constant(0) => b.expression.clone(),
+            Operator::Add if b.is_constant(0) => a.expression.clone(),
+            Operator::Multiply if a.is_constant(1) => b.expression.clone(),
+            Operator::Multiply | Operator::Divide if b.is_constant(1) => a.expression.clone(),
+            Operator::Modulo if a.interval.min >= 0 && a.interval.max < b.interval.min => {
+                a.expression.clone()
+            }
+            _ => Rc::new(Expression::Operation(
+                operator,
+                a.expression.clone(),
+                b.expression.clone(),
+            )),
+        };
+        Some(Self {
+            expression,
+            interval,
+        })
+    }
+}
+
+/// Runs the instructions on symbolic values of the variables, with each input instruction
+/// reading an unknown digit numbered from `first_digit`. Returns `None` if the program fails
+/// for all inputs.
+pub fn evaluate(
+    instructions: &[Instruction],
+    mut variables: [Value; 4],
+    first_digit: usize,
+) -> Option<[Value; 4]> {
+    let mut next_digit = first_digit;
+    for &instruction in instructions {
+        let (operator, a, b) = match instruction {
+            Instruction::Input(a) => {
+                variables[a as usize] = Value::digit(next_digit);
+                next_digit += 1;
+                continue;
+            }
+            Instruction::Add(a, b) => (Operator::Add, a, b),
+            Instruction::Multiply(a, b) => (Operator::Multiply, a, b),
+            Instruction::Divide(a, b) => (Operator::Divide, a, b),
+            Instruction::Modulo(a, b) => (Operator::Modulo, a, b),
+            Instruction::Equal(a, b) => (Operator::Equal, a, b),
+        };
+        let b = match b {
+            VariableOrNumber::Variable(variable) => variables[variable as usize].clone(),
+            VariableOrNumber::Number(number) => Value::constant(i64::from(number)),
+        };
+        variables[a as usize] = Value::apply(operator, &variables[a as usize], &b)?;
+    }
+    Some(variables)
+}
+
+/// The variables read before being written by the instructions, where the final check of z
+/// counts as a read at the end.
+fn live_variables(instructions: &[Instruction]) -> [bool; 4] {
+    let (mut live, mut written) = ([false; 4], [false; 4]);
+    for &instruction in instructions {
+        let (a, b, reads_a) = match instruction {
+            Instruction::Input(a) | Instruction::Multiply(a, VariableOrNumber::Number(0)) => {
+                (a, None, false)
+            }
+            Instruction::Add(a, b)
+            | Instruction::Multiply(a, b)
+            | Instruction::Divide(a, b)
+            | Instruction::Modulo(a, b)
+            | Instruction::Equal(a, b) => (a, Some(b), true),
+        };
+        let b = match b {
+            Some(VariableOrNumber::Variable(variable)) => Some(variable),
+            _ => None,
+        };
+        for read in b.into_iter().chain(reads_a.then_some(a)) {
+            live[read as usize] |= !written[read as usize];
+        }
+        written[a as usize] = true;
+    }
+    live[Variable::Z as usize] |= !written[Variable::Z as usize];
+    live
+}
+
+struct Search<'a> {
+    instructions: &'a [Instruction],
+    /// The index of each input instruction, with the variables live before it.
+    inputs: Vec<(usize, [bool; 4])>,
+    /// The digits to try, in order of preference.
+    digits: Vec<i64>,
+    /// The states, by the index of the next digit, from which z can not end up as zero.
+    dead_ends: HashSet<(usize, [i64; 4])>,
+    model_number: Vec<i64>,
+}
+
+impl Search<'_> {
+    /// If the remaining digits can be chosen to end up with z as zero, in which case they
+    /// are appended to the model number.
+    fn accepts(&mut self, digit_index: usize, mut variables: [i64; 4]) -> bool {
+        let (position, live) = self.inputs[digit_index];
+        for (value, live) in variables.iter_mut().zip(live) {
+            if !live {
+                *value = 0;
+            }
+        }
+        if self.dead_ends.contains(&(digit_index, variables)) {
+            return false;
+        }
+
+        let symbolic = evaluate(
+            &self.instructions[position..],
+            variables.map(Value::constant),
+            digit_index,
+        );
+        if symbolic.is_some_and(|symbolic| symbolic[Variable::Z as usize].interval.contains(0)) {
+            for digit_position in 0..self.digits.len() {
+                let digit = self.digits[digit_position];
+                let Some(after) = self.run_block(digit_index, variables, digit) else {
+                    continue;
+                };
+                self.model_number.push(digit);
+                let accepted = if digit_index + 1 == self.inputs.len() {
+                    after[Variable::Z as usize] == 0
+                } else {
+                    self.accepts(digit_index + 1, after)
+                };
+                if accepted {
+                    return true;
+                }
+                self.model_number.pop();
+            }
+        }
+
+        self.dead_ends.insert((digit_index, variables));
+        false
+    }
+
+    /// Runs the instructions from an input instruction up to the next one, or `None` if
+    /// the program fails.
+    fn run_block(
+        &self,
+        digit_index: usize,
+        mut variables: [i64; 4],
+        digit: i64,
+    ) -> Option<[i64; 4]> {
+        let position = self.inputs[digit_index].0;
+        let end = self
+            .inputs
+            .get(digit_index + 1)
+            .map_or(self.instructions.len(), |&(next, _)| next);
+        if let Instruction::Input(a) = self.instructions[position] {
+            variables[a as usize] = digit;
+        }
+        for instruction in &self.instructions[position + 1..end] {
+            instruction.apply(&mut variables).ok()?;
+        }
+        Some(variables)
+    }
+}
+
+/// Finds the largest or smallest model number accepted by a program, which has a non-zero
+/// digit for each input instruction and leaves z as zero.
+pub fn find_model_number(instructions: &[Instruction], largest: bool) -> Result<u64, String> {
+    let inputs = instructions
+        .iter()
+        .enumerate()
+        .filter(|(_, instruction)| matches!(instruction, Instruction::Input(_)))
+        .map(|(position, _)| (position, live_variables(&instructions[position..])))
+        .collect::<Vec<_>>();
+    let Some(&(first_input, _)) = inputs.first() else {
+        return Err("The program does not read any digits".to_string());
+    };
+    if inputs.len() > MAX_DIGITS {
+        return Err(format!(
+            "The program reads more than {MAX_DIGITS} digits, which is not supported"
+        ));
+    }
+
+    let mut variables = [0; 4];
+    for instruction in &instructions[..first_input] {
+        instruction.apply(&mut variables)?;
+    }
+
+    let mut search = Search {
+        instructions,
+        inputs,
+        digits: if largest {
+            (DIGITS.min..=DIGITS.max).rev().collect()
+        } else {
+            (DIGITS.min..=DIGITS.max).collect()
+        },
+        dead_ends: HashSet::new(),
+        model_number: Vec::new(),
+    };
+    if !search.accepts(0, variables) {
+        return Err("No model number is accepted by the program".to_string());
+    }
+    Ok(search
+        .model_number
+        .iter()
+        .fold(0, |acc, &digit| acc * 10 + digit as u64))
+}
+
+#[test]
+#[allow(clippy::unwrap_used)]
+fn test() {
+    use super::{Instruction, NUM_DIGITS_IN_MODEL_NUMBER};
+
+    let parse = |program: &str| {
+        program
+            .lines()
+            .map(Instruction::parse)
+            .collect::<Option<Vec<_>>>()
+            .unwrap()
+    };
+
+    // Accepting two digits where the first is two more than the second:
+    let program = parse("inp w\ninp x\nadd x 2\neql x w\neql x 0\nadd z x");
+    assert_eq!(find_model_number(&program, true), Ok(97));
+    assert_eq!(find_model_number(&program, false), Ok(31));
+    let [_, _, _, z] = evaluate(&program, std::array::from_fn(|_| Value::constant(0)), 0).unwrap();
+    assert_eq!(z.expression.to_string(), "(((d1 + 2) == d0) == 0)");
+    assert_eq!(z.interval, Interval { min: 0, max: 1 });
+
+    // The remainder of a value known to be smaller than the divisor is simplified away,
+    // and an equality check between disjoint intervals is known to be false:
+    let program = parse("inp z\nadd z 6\nmod z 26\ninp x\nadd x 20\neql x z");
+    let [_, x, _, z] = evaluate(&program, std::array::from_fn(|_| Value::constant(0)), 0).unwrap();
+    assert_eq!(z.expression.to_string(), "(d0 + 6)");
+    assert_eq!(z.interval, Interval { min: 7, max: 15 });
+    assert_eq!(x.expression.to_string(), "0");
+
+    // A program which never leaves z as zero, and one failing for all inputs:
+    let program = parse("inp w\nadd z w");
+    assert!(find_model_number(&program, true).is_err());
+    let program = parse("inp w\nmul w -1\nmod w 2");
+    assert!(find_model_number(&program, true).is_err());
+
+    // z must be kept when it is only read by the final check:
+    let program = parse("inp w\nadd w -5\nadd z w\ninp x");
+    assert_eq!(find_model_number(&program, true), Ok(59));
+
+    // MONAD, solved without relying on its structure:
+    let program = parse(include_str!("../day24_input.txt"));
+    assert_eq!(
+        program
+            .iter()
+            .filter(|i| matches!(i, Instruction::Input(_)))
+            .count(),
+        NUM_DIGITS_IN_MODEL_NUMBER
+    );
+    assert_eq!(find_model_number(&program, true), Ok(99_299_513_899_971));
+    assert_eq!(find_model_number(&program, false), Ok(93_185_111_127_911));
+}
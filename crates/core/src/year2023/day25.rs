@@ -17,42 +17,60 @@ impl InputStruct {
         let (start, end) = self.nodes[node];
         (start..end).map(|edge| (edge, self.edges[edge]))
     }
-}
-
-/// Solution from https://github.com/maneatingape/advent-of-code-rust/blob/main/src/year2023/day25.rs
-/// Convert the input to use numeric indices instead of string keys for speed.
-/// Each node is assigned a unique index on a first come first served basis.
-/// Then the edges are gathered into a single vec so that each edge also has a unique index.
-///
-/// As both node and edge indices are contigous this allows us to use a vec to store previously
-/// seen values which is must faster than using a `HashMap`.
-pub fn solve(input: &Input) -> Result<u64, String> {
-    let mut lookup = [usize::MAX; 26 * 26 * 26];
-    let mut neighbours = Vec::with_capacity(2_000);
 
-    for line in input.text.lines().map(str::as_bytes) {
-        let first = perfect_minimal_hash(&mut lookup, &mut neighbours, line);
+    /// Convert the input to use numeric indices instead of string keys for speed.
+    /// Each node is assigned a unique index on a first come first served basis.
+    /// Then the edges are gathered into a single vec so that each edge also has a unique index.
+    ///
+    /// As both node and edge indices are contigous this allows us to use a vec to store previously
+    /// seen values which is must faster than using a `HashMap`.
+    pub(crate) fn parse(text: &str) -> Result<Self, String> {
+        let mut lookup = [usize::MAX; 26 * 26 * 26];
+        let mut neighbours = Vec::with_capacity(2_000);
+
+        for (line_idx, line) in text.lines().map(str::as_bytes).enumerate() {
+            if line.len() < 8
+                || &line[3..5] != b": "
+                || (line.len() - 4) % 4 != 0
+                || !line.iter().enumerate().all(|(idx, &b)| match idx {
+                    3 => true,
+                    _ if idx % 4 == 0 && idx > 0 => b == b' ',
+                    _ => b.is_ascii_lowercase(),
+                })
+            {
+                return Err(format!(
+                    "Line {}: Not in the format 'abc: def ghi ...'",
+                    line_idx + 1
+                ));
+            }
+            let first = perfect_minimal_hash(&mut lookup, &mut neighbours, line);
 
-        // The graph is undirected so each link is bidirectional.
-        for chunk in line[5..].chunks(4) {
-            let second = perfect_minimal_hash(&mut lookup, &mut neighbours, chunk);
-            neighbours[first].push(second);
-            neighbours[second].push(first);
+            // The graph is undirected so each link is bidirectional.
+            for chunk in line[5..].chunks(4) {
+                let second = perfect_minimal_hash(&mut lookup, &mut neighbours, chunk);
+                neighbours[first].push(second);
+                neighbours[second].push(first);
+            }
         }
-    }
 
-    // Assign each edge a unique index. Each node then specifies a range into the edges vec.
-    let mut edges = Vec::with_capacity(5_000);
-    let mut nodes = Vec::with_capacity(neighbours.len());
+        // Assign each edge a unique index. Each node then specifies a range into the edges vec.
+        let mut edges = Vec::with_capacity(5_000);
+        let mut nodes = Vec::with_capacity(neighbours.len());
+
+        for list in neighbours {
+            let start = edges.len();
+            let end = edges.len() + list.len();
+            edges.extend(list);
+            nodes.push((start, end));
+        }
 
-    for list in neighbours {
-        let start = edges.len();
-        let end = edges.len() + list.len();
-        edges.extend(list);
-        nodes.push((start, end));
+        Ok(Self { edges, nodes })
     }
+}
 
-    let input_struct = InputStruct { edges, nodes };
+/// Solution from https://github.com/maneatingape/advent-of-code-rust/blob/main/src/year2023/day25.rs
+pub fn solve(input: &Input) -> Result<u64, String> {
+    let input_struct = InputStruct::parse(input.text)?;
 
     // Arbitrarily pick the first node then find the furthest node from it.
     let start = furthest(&input_struct, 0);
@@ -1,7 +1,14 @@
-use crate::common::array_deque::ArrayDeque;
+mod disassembler;
+mod reverse;
+
+use std::fmt::{Display, Formatter};
+
 use crate::common::array_stack::ArrayStack;
+use crate::common::register_machine::{Effect, InstructionSet, Machine, Stop};
 use crate::input::{on_error, Input};
 
+const MAX_INSTRUCTIONS: u64 = 1_000_000;
+
 pub fn solve(input: &Input) -> Result<String, String> {
     let mut program = ArrayStack::<32, u8>::new();
     let mut registers = [0_u64; 3];
@@ -12,20 +19,22 @@ pub fn solve(input: &Input) -> Result<String, String> {
             current_register += 1;
         } else if let Some(program_str) = line.strip_prefix("Program: ") {
             for n in program_str.split(',') {
-                program.push(n.parse().map_err(|_| on_error())?)?;
+                let n = n.parse::<u8>().map_err(|_| on_error())?;
+                if n > 7 {
+                    return Err("Program contains a value which is not a 3-bit number".to_string());
+                }
+                program.push(n)?;
             }
         }
     }
 
-    let mut computer = Computer {
-        program: program.slice(),
-        instruction_pointer: 0,
-        registers,
-    };
+    let program = program.slice();
+    let mut computer = Computer::parse(program);
+    computer.registers = registers;
 
     if input.is_part_one() {
         let mut result = String::new();
-        while let Some(output) = computer.run_for_output() {
+        while let Some(output) = run_for_output(&mut computer)? {
             if !result.is_empty() {
                 result.push(',');
             }
@@ -33,120 +42,167 @@ pub fn solve(input: &Input) -> Result<String, String> {
         }
         Ok(result)
     } else {
-        if (
-            computer.program[computer.program.len() - 2],
-            computer.program[computer.program.len() - 1],
-        ) != (3, 0)
-        {
-            return Err("Program does not end with 'jnz 0'".to_string());
-        }
+        let shift = reverse::check_properties(&computer.instructions).map_err(|error| {
+            format!(
+                "{error}\n\n{}",
+                disassembler::disassemble(program).trim_end()
+            )
+        })?;
+        Ok(reverse::find_quine(&mut computer, program, shift)?.to_string())
+    }
+}
 
-        // Program ends with:
-        //   adv 3        which can be written as A >>= 4
-        //   jnz 0 .      which ends if A is zero
-        // and the whole program can be written as:
-        //   loop {
-        //     B = ..(computed from A)..
-        //     output(B);
-        //     A >>= 3;
-        //     if (A == 0) break;
-        //   }
-        // So the last loop iteration, which outputs the last program instruction, can only have the lowest 0..3 bits set (as A>>3 is zero).
-        // And the second to last last output can only have bits 0..6 bits set (as A>>3 twice is zero).
-        // So starting from the last instruction we can determine which of the 0..3 lowest bits can give the desired last instruction output,
-        // then determine the next bits 3..6 which gives the second to last output, and so on.
-        let mut stack = ArrayDeque::<320, (u64, u64)>::new();
-        stack.push_back((computer.program.len() as u64, 0))?;
-        while let Some((offset_from_end, register_a_bits_so_far)) = stack.pop_front() {
-            for first_three_bits in 0..=0b111 {
-                let register_a_next_three_bits = (register_a_bits_so_far << 3) | first_three_bits;
-                computer.registers[0] = register_a_next_three_bits;
-                computer.instruction_pointer = 0;
-                if computer.run_for_output()
-                    == Some(computer.program[offset_from_end as usize - 1] as u64)
-                {
-                    if offset_from_end - 1 == 0 {
-                        return Ok(format!("{}", register_a_next_three_bits));
-                    }
-                    stack.push_back((offset_from_end - 1, register_a_next_three_bits))?;
-                }
-            }
+/// The 3-bit computer, with each instruction being a pair of an opcode and an operand.
+struct Computer;
+
+#[derive(Clone, Copy)]
+struct Instruction {
+    opcode: u8,
+    operand: u8,
+}
+
+/// The operand of an instruction taking a combo operand, decoded.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Combo {
+    Literal(u8),
+    /// The index of a register.
+    Register(usize),
+    /// The reserved combo operand 7.
+    Invalid,
+}
+
+impl Display for Combo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Self::Literal(value) => write!(f, "{value}"),
+            Self::Register(register) => f.write_str(Computer::REGISTER_NAMES[register]),
+            Self::Invalid => f.write_str("invalid"),
         }
+    }
+}
 
-        Err("No solution found".to_string())
+impl Instruction {
+    /// The combo operand, or `None` if the operand of the instruction is a literal one or
+    /// ignored.
+    const fn combo(self) -> Option<Combo> {
+        if matches!(self.opcode, 1 | 3 | 4) {
+            return None;
+        }
+        Some(match self.operand {
+            operand @ 0..=3 => Combo::Literal(operand),
+            operand @ 4..=6 => Combo::Register(operand as usize - 4),
+            _ => Combo::Invalid,
+        })
     }
 }
 
-struct Computer<'a> {
-    instruction_pointer: usize,
-    registers: [u64; 3],
-    program: &'a [u8],
+impl Display for Instruction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let literal_operand = self.operand;
+        let Some(combo_operand) = self.combo() else {
+            return match self.opcode {
+                1 => write!(f, "bxl {literal_operand}"),
+                3 => write!(f, "jnz {literal_operand}"),
+                _ => write!(f, "bxc"),
+            };
+        };
+        let mnemonic = match self.opcode {
+            0 => "adv",
+            2 => "bst",
+            5 => "out",
+            6 => "bdv",
+            _ => "cdv",
+        };
+        write!(f, "{mnemonic} {combo_operand}")
+    }
 }
 
-impl Computer<'_> {
-    fn combo_operand(&self) -> u64 {
-        let val = self.program[self.instruction_pointer + 1];
-        match val {
-            0..=3 => val as u64,
-            4..=6 => self.registers[val as usize - 4],
-            _ => {
-                unreachable!();
-            }
-        }
+impl Computer {
+    /// Splits a program into instructions, ignoring a trailing opcode without operand.
+    fn parse(program: &[u8]) -> Machine<Self> {
+        Machine::new(
+            program
+                .chunks_exact(2)
+                .map(|pair| Instruction {
+                    opcode: pair[0],
+                    operand: pair[1],
+                })
+                .collect(),
+        )
     }
 
-    const fn literal_operand(&self) -> u64 {
-        self.program[self.instruction_pointer + 1] as u64
+    fn combo_operand(registers: &[u64; 3], instruction: Instruction) -> Result<u64, String> {
+        match instruction.combo() {
+            Some(Combo::Literal(value)) => Ok(u64::from(value)),
+            Some(Combo::Register(register)) => Ok(registers[register]),
+            _ => Err(format!("Invalid combo operand {}", instruction.operand)),
+        }
     }
+}
 
-    fn run_for_output(&mut self) -> Option<u64> {
-        loop {
-            if (self.instruction_pointer + 1) >= self.program.len() {
-                return None;
-            }
-            let instruction_val = self.program[self.instruction_pointer];
-            match instruction_val {
+impl InstructionSet for Computer {
+    type Word = u64;
+    type Registers = [u64; 3];
+    type Instruction = Instruction;
+    type State = ();
+    const REGISTER_NAMES: &'static [&'static str] = &["A", "B", "C"];
+
+    #[inline]
+    fn execute(
+        instruction: Instruction,
+        machine: &mut Machine<Self>,
+    ) -> Result<Effect<u64>, String> {
+        let registers = &mut machine.registers;
+        let literal_operand = u64::from(instruction.operand);
+        let shifted_a = |shift: u64| registers[0].checked_shr(shift as u32).unwrap_or(0);
+        match instruction.opcode {
             0 /* adv */ => {
-                self.registers[0] >>= self.combo_operand() as u32;
+                registers[0] = shifted_a(Self::combo_operand(registers, instruction)?);
             }
             1 /* bxl */ => {
-                self.registers[1] ^= self.literal_operand();
+                registers[1] ^= literal_operand;
             }
             2 /* bst */ => {
-                self.registers[1] = self.combo_operand() % 8;
+                registers[1] = Self::combo_operand(registers, instruction)? % 8;
             }
             3 /* jnz */ => {
-                if self.registers[0] != 0 {
-                    self.instruction_pointer = self.literal_operand() as usize;
-                    continue;
+                if registers[0] != 0 {
+                    if literal_operand % 2 != 0 {
+                        return Err("Jump to the middle of an instruction".to_string());
+                    }
+                    return Ok(Effect::JumpTo(literal_operand as usize / 2));
                 }
             }
             4 /* bxc */ => {
-                self.registers[1] ^= self.registers[2];
+                registers[1] ^= registers[2];
             }
             5 /* out */ => {
-                let result = Some(self.combo_operand() % 8);
-                self.instruction_pointer += 2;
-                return result;
+                return Ok(Effect::Output(
+                    Self::combo_operand(registers, instruction)? % 8,
+                ));
             }
             6 /* bdv */ => {
-                self.registers[1] = self.registers[0] >> self.combo_operand() as u32;
-            }
-            7 /* cdv */ => {
-                self.registers[2] = self.registers[0] >> self.combo_operand() as u32;
+                registers[1] = shifted_a(Self::combo_operand(registers, instruction)?);
             }
-            _ => {
-                return None;
+            _ /* cdv */ => {
+                registers[2] = shifted_a(Self::combo_operand(registers, instruction)?);
             }
         }
-            self.instruction_pointer += 2;
-        }
+        Ok(Effect::Next)
+    }
+}
+
+fn run_for_output(computer: &mut Machine<Computer>) -> Result<Option<u64>, String> {
+    match computer.run(MAX_INSTRUCTIONS)? {
+        Stop::Output(value) => Ok(Some(value)),
+        Stop::Halted => Ok(None),
+        _ => Err(format!("Aborted after {MAX_INSTRUCTIONS} instructions")),
     }
 }
 
 #[test]
 pub fn tests() {
-    use crate::input::{test_part_one, test_part_two};
+    use crate::input::{test_part_one, test_part_two, test_part_two_error};
 
     let test_input = "Register A: 729
 Register B: 0
@@ -155,6 +211,20 @@ Register C: 0
 Program: 0,1,5,4,3,0";
     test_part_one!(test_input => "4,6,3,5,6,3,5,2,1,0".to_string());
 
+    let test_input = "Register A: 2024
+Register B: 0
+Register C: 0
+
+Program: 0,3,5,4,3,0";
+    test_part_two!(test_input => "117440".to_string());
+    test_part_two_error!(
+        "Register A: 0\nRegister B: 0\nRegister C: 0\n\nProgram: 0,3,5,4" =>
+        "Property 'single loop' does not hold: the program does not end with jnz 0
+
+ 0: adv 3   A = A >> 3
+ 2: out A   output A % 8"
+    );
+
     let real_input = include_str!("day17_input.txt");
     test_part_one!(real_input => "3,7,1,7,2,1,0,6,3".to_string());
     test_part_two!(real_input => "37221334433268".to_string());
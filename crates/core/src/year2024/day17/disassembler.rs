@@ -0,0 +1,101 @@
+//! Disassembly of programs for the 3-bit computer, with the operation of each instruction
+//! written out:
+//!
+//! | Opcode | Mnemonic | Operand | Operation                   |
+//! |--------|----------|---------|-----------------------------|
+//! | 0      | `adv`    | combo   | `A = A >> operand`          |
+//! | 1      | `bxl`    | literal | `B = B ^ operand`           |
+//! | 2      | `bst`    | combo   | `B = operand % 8`           |
+//! | 3      | `jnz`    | literal | `if A != 0 jump to operand` |
+//! | 4      | `bxc`    | ignored | `B = B ^ C`                 |
+//! | 5      | `out`    | combo   | `output operand % 8`        |
+//! | 6      | `bdv`    | combo   | `B = A >> operand`          |
+//! | 7      | `cdv`    | combo   | `C = A >> operand`          |
+//!
+//! Combo operands 0 to 3 are literal values, while 4, 5 and 6 are the registers A, B and C.
+
+use std::fmt::Write;
+
+use super::{Combo, Instruction};
+
+/// The width of the column with the instructions.
+const INSTRUCTION_WIDTH: usize = 7;
+
+/// Disassembles a program into one line for each instruction, starting with its offset.
+pub fn disassemble(program: &[u8]) -> String {
+    let mut result = String::new();
+    let mut pairs = program.chunks_exact(2);
+    for (index, pair) in pairs.by_ref().enumerate() {
+        let instruction = Instruction {
+            opcode: pair[0],
+            operand: pair[1],
+        };
+        let mnemonic = instruction.to_string();
+        let _ = writeln!(
+            result,
+            "{:>2}: {mnemonic:<INSTRUCTION_WIDTH$} {}",
+            index * 2,
+            operation(instruction)
+        );
+    }
+    if let [opcode] = pairs.remainder() {
+        let _ = writeln!(
+            result,
+            "{:>2}: {opcode:<INSTRUCTION_WIDTH$} ignored, since it has no operand",
+            program.len() - 1
+        );
+    }
+    result
+}
+
+fn operation(instruction: Instruction) -> String {
+    let literal = instruction.operand;
+    let combo = match instruction.combo() {
+        None => {
+            return match instruction.opcode {
+                1 => format!("B = B ^ {literal}"),
+                3 => format!("if A != 0 jump to {literal}"),
+                _ => "B = B ^ C".to_string(),
+            };
+        }
+        Some(Combo::Invalid) => return "invalid combo operand 7".to_string(),
+        Some(combo) => combo,
+    };
+    match instruction.opcode {
+        0 => format!("A = A >> {combo}"),
+        2 => format!("B = {combo} % 8"),
+        5 => format!("output {combo} % 8"),
+        6 => format!("B = A >> {combo}"),
+        _ => format!("C = A >> {combo}"),
+    }
+}
+
+#[test]
+fn test() {
+    assert_eq!(
+        disassemble(&[2, 4, 1, 2, 7, 5, 1, 3, 4, 3, 5, 5, 0, 3, 3, 0]),
+        " 0: bst A   B = A % 8
+ 2: bxl 2   B = B ^ 2
+ 4: cdv B   C = A >> B
+ 6: bxl 3   B = B ^ 3
+ 8: bxc     B = B ^ C
+10: out B   output B % 8
+12: adv 3   A = A >> 3
+14: jnz 0   if A != 0 jump to 0
+"
+    );
+    // Operand 7 is only invalid for instructions taking a combo operand:
+    assert_eq!(
+        disassemble(&[1, 7, 3, 7, 4, 7]),
+        " 0: bxl 7   B = B ^ 7
+ 2: jnz 7   if A != 0 jump to 7
+ 4: bxc     B = B ^ C
+"
+    );
+    assert_eq!(
+        disassemble(&[5, 7, 3]),
+        " 0: out invalid invalid combo operand 7
+ 2: 3       ignored, since it has no operand
+"
+    );
+}
@@ -0,0 +1,241 @@
+//! Finding the value of register A making a program output itself, by building it from its
+//! most significant bits.
+//!
+//! This works for any program with the following properties, which are checked:
+//!
+//! 1. *Valid operands*: no instruction has the reserved combo operand 7.
+//! 2. *Single loop*: the last instruction is `jnz 0`, and no other instruction jumps, so the
+//!    program loops until A is zero.
+//! 3. *Constant shift*: A is only written by a single `adv` with a literal operand from 1 to
+//!    3, so each iteration shifts A right by the same number of bits.
+//! 4. *Single output*: there is exactly one `out` instruction, so each iteration outputs one
+//!    value.
+//! 5. *No carried state*: B and C are written before being read in the loop, so the output
+//!    of an iteration only depends on the value of A when it starts.
+//!
+//! The last iteration then starts with only the lowest `shift` bits of A set, the one before
+//! it with the lowest `2 * shift` bits, and so on. So candidate values of A are built from
+//! the most significant bits, keeping those where an iteration outputs the expected value.
+
+use super::{run_for_output, Combo, Computer, Instruction};
+use crate::common::register_machine::Machine;
+
+const A: u8 = 0b001;
+const B: u8 = 0b010;
+const C: u8 = 0b100;
+
+/// The registers read by the combo operand of an instruction, as a bit set.
+const fn combo_reads(instruction: Instruction) -> u8 {
+    match instruction.combo() {
+        Some(Combo::Register(register)) => 1 << register,
+        _ => 0,
+    }
+}
+
+/// The registers read and written by an instruction, as bit sets.
+const fn register_usage(instruction: Instruction) -> (u8, u8) {
+    let combo = combo_reads(instruction);
+    match instruction.opcode {
+        0 => (A | combo, A),
+        1 => (B, B),
+        2 => (combo, B),
+        3 => (A, 0),
+        4 => (B | C, B),
+        5 => (combo, 0),
+        6 => (A | combo, B),
+        _ => (A | combo, C),
+    }
+}
+
+/// Checks the structural properties of a program, returning the number of bits A is shifted
+/// by in each iteration, or an error naming the property which does not hold.
+pub fn check_properties(instructions: &[Instruction]) -> Result<u32, String> {
+    let violation = |property: &str, reason: String| {
+        Err(format!("Property '{property}' does not hold: {reason}"))
+    };
+
+    if let Some(offset) = instructions
+        .iter()
+        .position(|instruction| instruction.combo() == Some(Combo::Invalid))
+    {
+        return violation(
+            "valid operands",
+            format!("the instruction at {} has combo operand 7", offset * 2),
+        );
+    }
+
+    let Some((last, body)) = instructions.split_last() else {
+        return violation("single loop", "the program is empty".to_string());
+    };
+    if (last.opcode, last.operand) != (3, 0) {
+        return violation(
+            "single loop",
+            "the program does not end with jnz 0".to_string(),
+        );
+    }
+    if let Some(offset) = body.iter().position(|instruction| instruction.opcode == 3) {
+        return violation(
+            "single loop",
+            format!("the instruction at {} jumps inside the loop", offset * 2),
+        );
+    }
+
+    let shifts = body
+        .iter()
+        .filter(|instruction| instruction.opcode == 0)
+        .collect::<Vec<_>>();
+    let shift = match shifts[..] {
+        [] => return violation("constant shift", "A is never shifted".to_string()),
+        [shift] if (1..=3).contains(&shift.operand) => u32::from(shift.operand),
+        [shift] => {
+            return violation(
+                "constant shift",
+                format!("{shift} does not shift A by 1 to 3 bits"),
+            )
+        }
+        _ => return violation("constant shift", "A is shifted more than once".to_string()),
+    };
+
+    let outputs = body
+        .iter()
+        .filter(|instruction| instruction.opcode == 5)
+        .count();
+    if outputs != 1 {
+        return violation(
+            "single output",
+            format!("the loop has {outputs} out instructions"),
+        );
+    }
+
+    let mut written = A;
+    for (offset, &instruction) in body.iter().enumerate() {
+        let (reads, writes) = register_usage(instruction);
+        let carried = reads & !written;
+        if carried != 0 {
+            let register = if carried & B == 0 { 'C' } else { 'B' };
+            return violation(
+                "no carried state",
+                format!(
+                    "the instruction at {} reads {register} before it is written",
+                    offset * 2
+                ),
+            );
+        }
+        written |= writes;
+    }
+
+    Ok(shift)
+}
+
+/// Finds the lowest value of register A making the program output itself.
+pub fn find_quine(
+    computer: &mut Machine<Computer>,
+    program: &[u8],
+    shift: u32,
+) -> Result<u64, String> {
+    if program.len() as u64 * u64::from(shift) > u64::from(u64::BITS) {
+        return Err("Register A would need more than 64 bits".to_string());
+    }
+
+    // The candidate values of A when starting the iterations from the current one, in
+    // increasing order:
+    let mut candidates = vec![0];
+    for &expected in program.iter().rev() {
+        let mut next_candidates = Vec::new();
+        for candidate in candidates {
+            for bits in 0..(1 << shift) {
+                let value = (candidate << shift) | bits;
+                // A is non-zero when starting any iteration but the first:
+                if value == 0 {
+                    continue;
+                }
+                computer.reset();
+                computer.registers = [value, 0, 0];
+                if run_for_output(computer)? == Some(u64::from(expected)) {
+                    next_candidates.push(value);
+                }
+            }
+        }
+        candidates = next_candidates;
+    }
+
+    for candidate in candidates {
+        computer.reset();
+        computer.registers = [candidate, 0, 0];
+        let mut output = Vec::new();
+        while let Some(value) = run_for_output(computer)? {
+            output.push(value);
+        }
+        if output
+            .iter()
+            .copied()
+            .eq(program.iter().map(|&value| u64::from(value)))
+        {
+            return Ok(candidate);
+        }
+    }
+    Err("No value of register A makes the program output itself".to_string())
+}
+
+#[test]
+fn test() {
+    let check = |program: &[u8]| check_properties(&Computer::parse(program).instructions);
+
+    assert_eq!(
+        check(&[2, 4, 1, 2, 7, 5, 1, 3, 4, 3, 5, 5, 0, 3, 3, 0]),
+        Ok(3)
+    );
+    assert_eq!(check(&[0, 2, 5, 4, 3, 0]), Ok(2));
+    assert_eq!(
+        check(&[0, 3, 5, 7, 3, 0]),
+        Err(
+            "Property 'valid operands' does not hold: the instruction at 2 has combo operand 7"
+                .to_string()
+        )
+    );
+    assert_eq!(
+        check(&[0, 3, 5, 4]),
+        Err(
+            "Property 'single loop' does not hold: the program does not end with jnz 0".to_string()
+        )
+    );
+    assert_eq!(
+        check(&[3, 4, 0, 3, 5, 4, 3, 0]),
+        Err(
+            "Property 'single loop' does not hold: the instruction at 0 jumps inside the loop"
+                .to_string()
+        )
+    );
+    assert_eq!(
+        check(&[0, 4, 5, 4, 3, 0]),
+        Err(
+            "Property 'constant shift' does not hold: adv A does not shift A by 1 to 3 bits"
+                .to_string()
+        )
+    );
+    assert_eq!(
+        check(&[0, 3, 0, 3, 5, 4, 3, 0]),
+        Err("Property 'constant shift' does not hold: A is shifted more than once".to_string())
+    );
+    assert_eq!(
+        check(&[0, 3, 3, 0]),
+        Err("Property 'single output' does not hold: the loop has 0 out instructions".to_string())
+    );
+    assert_eq!(
+        check(&[1, 2, 0, 3, 5, 5, 3, 0]),
+        Err("Property 'no carried state' does not hold: the instruction at 0 reads B before it is written".to_string())
+    );
+    assert_eq!(
+        check(&[2, 4, 4, 0, 0, 3, 5, 5, 3, 0]),
+        Err("Property 'no carried state' does not hold: the instruction at 2 reads C before it is written".to_string())
+    );
+
+    // A program shifting A by one bit at a time, where consecutive outputs share bits:
+    let program = [0, 1, 5, 4, 3, 0];
+    let mut computer = Computer::parse(&program);
+    assert_eq!(check_properties(&computer.instructions), Ok(1));
+    assert_eq!(
+        find_quine(&mut computer, &program, 1),
+        Err("No value of register A makes the program output itself".to_string())
+    );
+}
@@ -4,20 +4,42 @@ use std::collections::{HashMap, HashSet};
 use crate::common::array_stack::ArrayStack;
 use crate::input::{on_error, Input};
 
-pub fn solve(input: &Input) -> Result<String, String> {
-    let mut nodes = HashMap::<u16, Vec<u16>>::with_capacity(1_000);
-    let mut edges = vec![[false; 676]; 676];
+pub struct Network {
+    nodes: HashMap<u16, Vec<u16>>,
+    edges: Vec<[bool; 676]>,
+}
+
+impl Network {
+    pub(crate) fn parse(text: &str) -> Result<Self, String> {
+        let mut nodes = HashMap::<u16, Vec<u16>>::with_capacity(1_000);
+        let mut edges = vec![[false; 676]; 676];
+
+        for (line_idx, line) in text.lines().enumerate() {
+            let line = line.as_bytes();
+            if line.len() != 5
+                || line[2] != b'-'
+                || ![line[0], line[1], line[3], line[4]]
+                    .iter()
+                    .all(u8::is_ascii_lowercase)
+            {
+                return Err(format!("Line {}: Not in the format 'ab-cd'", line_idx + 1));
+            }
+            let from = to_num(&line[..2]);
+            let to = to_num(&line[3..]);
 
-    for edge in input.text.as_bytes().chunks(6) {
-        let from = to_num(&edge[..2]);
-        let to = to_num(&edge[3..]);
+            nodes.entry(from).or_default().push(to);
+            nodes.entry(to).or_default().push(from);
 
-        nodes.entry(from).or_default().push(to);
-        nodes.entry(to).or_default().push(from);
+            edges[from as usize][to as usize] = true;
+            edges[to as usize][from as usize] = true;
+        }
 
-        edges[from as usize][to as usize] = true;
-        edges[to as usize][from as usize] = true;
+        Ok(Self { nodes, edges })
     }
+}
+
+pub fn solve(input: &Input) -> Result<String, String> {
+    let Network { nodes, edges } = Network::parse(input.text)?;
 
     let mut seen = [false; 1024];
 
@@ -1,98 +1,59 @@
-use crate::{
-    common::{
-        array_stack::ArrayStack,
-        id_assigner::{self, IdAssigner},
-    },
-    input::Input,
-};
+use crate::common::netlist::Netlist;
+use crate::input::Input;
 
-type Wire = u16;
+/// The number of wires swapped between pairs of gates.
+const SWAPPED_WIRES: usize = 8;
 
-pub fn solve(input: &Input) -> Result<u64, String> {
-    let mut device = Device::parse(input.text);
-    Ok(device.compute_z())
-}
-
-#[derive(Clone, Copy, Debug)]
-enum Gate {
-    And(Wire, Wire),
-    Or(Wire, Wire),
-    Xor(Wire, Wire),
-    ComputedValue(bool),
-}
-
-struct Device {
-    wire_values: [Gate; 1000],
-    z_values: [Wire; 64],
-    num_z_values: usize,
-}
-
-impl Device {
-    fn parse(input: &str) -> Self {
-        let mut id_assigner = IdAssigner::<1024, str>::new("");
-        let mut z_values = [0; 64];
-        let mut wire_values = [Gate::ComputedValue(false); 1000];
-        let mut num_z_values = 0;
-
-        let mut add_z_if = |wire_name: &str, wire_id: Wire| {
-            if let Some(num_part) = wire_name.strip_prefix('z') {
-                let num = num_part.parse::<u8>().unwrap();
-                z_values[num as usize] = wire_id;
-                num_z_values += 1;
-            }
-        };
-
-        for line in input.lines() {
-            if let Some((wire_name, initial_value)) = line.split_once(": ") {
-                let wire_id = id_assigner.id_of(wire_name).unwrap();
-                let initial_value = initial_value.eq("1");
-                wire_values[wire_id as usize] = Gate::ComputedValue(initial_value);
-            } else if let Some((from, to)) = line.split_once(" -> ") {
-                let mut from_parts = from.split(' ');
-                let p1_wire_name = from_parts.next().unwrap();
-                let p1_wire_id = id_assigner.id_of(p1_wire_name).unwrap();
-                add_z_if(p1_wire_name, p1_wire_id);
-                let op_type = from_parts.next().unwrap();
-                let p2_wire_name = from_parts.next().unwrap();
-                let p2_wire_id = id_assigner.id_of(p2_wire_name).unwrap();
-                add_z_if(p2_wire_name, p2_wire_id);
-                let to_wire_id = id_assigner.id_of(to).unwrap();
-                add_z_if(to, to_wire_id);
-                wire_values[to_wire_id as usize] = match op_type {
-                    "AND" => Gate::And(p1_wire_id, p2_wire_id),
-                    "OR" => Gate::Or(p1_wire_id, p2_wire_id),
-                    "XOR" => Gate::Xor(p1_wire_id, p2_wire_id),
-                    _ => unreachable!("Strange op: '{op_type}'"),
-                };
+pub fn solve(input: &Input) -> Result<String, String> {
+    let netlist = Netlist::parse(input.text)?;
+    if input.is_part_one() {
+        let signals = netlist.evaluate()?;
+        let (mut z, mut output_bits) = (0_u64, 0_u64);
+        for (wire, name) in netlist.names.iter().enumerate() {
+            if let Some(bit) = name
+                .strip_prefix('z')
+                .and_then(|bit| bit.parse::<u32>().ok())
+            {
+                let bit_mask = 1_u64
+                    .checked_shl(bit)
+                    .ok_or_else(|| format!("Too many output bits, as '{name}' exists"))?;
+                output_bits |= bit_mask;
+                if signals[wire] & 1 == 1 {
+                    z |= bit_mask;
+                }
             }
         }
-
-        Self {
-            wire_values,
-            z_values,
-            num_z_values,
+        let first_missing = (!output_bits).trailing_zeros();
+        if first_missing < u64::BITS && output_bits >> first_missing != 0 {
+            return Err(format!("No wire named 'z{first_missing:02}'"));
         }
-    }
-
-    fn compute_z(&mut self) -> u64 {
-        (0..self.num_z_values).fold(0, |acc, i| {
-            acc | (u64::from(self.compute_wire(self.z_values[i])) * (1 << i))
-        })
-    }
-
-    fn compute_wire(&mut self, wire_id: Wire) -> bool {
-        match self.wire_values[wire_id as usize] {
-            Gate::And(w1, w2) => self.compute_wire(w1) & self.compute_wire(w2),
-            Gate::Or(w1, w2) => self.compute_wire(w1) | self.compute_wire(w2),
-            Gate::Xor(w1, w2) => self.compute_wire(w1) ^ self.compute_wire(w2),
-            Gate::ComputedValue(value) => value,
+        Ok(z.to_string())
+    } else {
+        // Each swapped wire makes exactly one gate deviate from the structure of an adder:
+        let deviations = netlist.verify_adder()?;
+        if deviations.len() != SWAPPED_WIRES {
+            return Err(format!(
+                "Expected {SWAPPED_WIRES} gates deviating from a ripple-carry adder, found {}:\n{}",
+                deviations.len(),
+                deviations
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            ));
         }
+        let mut wires = deviations
+            .iter()
+            .map(|deviation| deviation.wire)
+            .collect::<Vec<_>>();
+        wires.sort_unstable();
+        Ok(wires.join(","))
     }
 }
 
 #[test]
 pub fn tests() {
-    use crate::input::{test_part_one_no_allocations, test_part_two_no_allocations};
+    use crate::input::{test_part_one, test_part_one_error, test_part_two};
 
     let test_input = "x00: 1
 x01: 1
@@ -104,7 +65,7 @@ y02: 0
 x00 AND y00 -> z00
 x01 XOR y01 -> z01
 x02 OR y02 -> z02";
-    test_part_one_no_allocations!(test_input => 4);
+    test_part_one!(test_input => "4".to_string());
     let test_input = "x00: 1
 x01: 0
 x02: 1
@@ -152,10 +113,12 @@ y03 OR x01 -> nrd
 hwm AND bqk -> z03
 tgd XOR rvg -> z12
 tnw OR pbm -> gnj";
-    test_part_one_no_allocations!(test_input => 2024);
-    //test_part_two_no_allocations!(test_input => 0);
+    test_part_one!(test_input => "2024".to_string());
+
+    test_part_one_error!("x00: 1\n\nx00 AND b -> a\nx00 AND a -> b\na OR b -> z00" => "The circuit has a cycle: a -> b -> a");
+    test_part_one_error!("x00: 1\n\nx00 -> z00\nx00 -> z02" => "No wire named 'z01'");
 
     let real_input = include_str!("day24_input.txt");
-    test_part_one_no_allocations!(real_input => 65_740_327_379_952);
-    //test_part_two_no_allocations!(real_input => 0);
+    test_part_one!(real_input => "65740327379952".to_string());
+    test_part_two!(real_input => "bgs,pqc,rjm,swt,wsv,z07,z13,z31".to_string());
 }